@@ -0,0 +1,32 @@
+@echo off
+title RabbitMQ Integration - Legacy Topology Cleanup
+
+echo 🧹 Removing the exchange and queue of the single queue topology...
+echo.
+
+if not defined DOCKER_RABBITMQ_USER set DOCKER_RABBITMQ_USER=guest
+if not defined DOCKER_RABBITMQ_PASSWORD set DOCKER_RABBITMQ_PASSWORD=guest
+
+:: Messages still in main_queue would be lost with it
+set MESSAGES=
+for /f "tokens=2" %%m in ('docker compose exec -T rabbitmq rabbitmqctl list_queues name messages --no-table-headers --quiet ^| findstr /b "main_queue"') do set MESSAGES=%%m
+
+if not defined MESSAGES (
+    echo ✅ main_queue does not exist
+) else if not "%MESSAGES%"=="0" (
+    echo ❌ main_queue still holds %MESSAGES% messages. Keep the previous worker running until it is empty.
+    pause
+    exit /b 1
+) else (
+    docker compose exec -T rabbitmq rabbitmqadmin -u %DOCKER_RABBITMQ_USER% -p %DOCKER_RABBITMQ_PASSWORD% delete queue name=main_queue
+)
+
+echo.
+echo ⚠️  Check in the management UI that delayed_exchange has no delayed messages left.
+choice /m "Delete delayed_exchange"
+if errorlevel 2 exit /b 1
+docker compose exec -T rabbitmq rabbitmqadmin -u %DOCKER_RABBITMQ_USER% -p %DOCKER_RABBITMQ_PASSWORD% delete exchange name=delayed_exchange
+
+echo.
+echo ✅ Legacy topology removed
+pause
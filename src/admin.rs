@@ -0,0 +1,254 @@
+use actix_web::{HttpResponse, get, post, put, web};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::auth::AuthContext;
+use crate::connection::{TenantPool, get_rabbitmq_pool};
+use crate::dlq::{self, AuditRecord, DlqActionResult, DlqFilter, DlqListing, DlqMessage};
+use crate::error::{Error, ErrorBody};
+use crate::handlers::resolve_tenant;
+use crate::telemetry::get_log_filter;
+
+/// The server's log filter.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LogLevel {
+    /// Directives in `RUST_LOG` syntax, e.g. `info,integration_rust_rabbitmq=debug`.
+    pub filter: String,
+}
+
+/// Log filter currently in effect.
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, description = "Current log filter", body = LogLevel)),
+    security(("api_key" = []), ("bearer" = []))
+)]
+#[get("/admin/log-level")]
+pub async fn get_log_level() -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok().json(LogLevel {
+        filter: get_log_filter()?.current(),
+    }))
+}
+
+/// Replaces the log filter until the next restart, e.g. to turn on debug logs for one module.
+#[utoipa::path(
+    tag = "admin",
+    request_body = LogLevel,
+    responses(
+        (status = 200, description = "Log filter replaced", body = LogLevel),
+        (status = 422, description = "Invalid filter directives", body = ErrorBody),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+#[put("/admin/log-level")]
+pub async fn set_log_level(
+    payload: web::Json<LogLevel>,
+    auth: web::ReqData<AuthContext>,
+) -> Result<HttpResponse, Error> {
+    let log_filter = get_log_filter()?;
+    let previous = log_filter.current();
+    log_filter.set(&payload.filter)?;
+    info!(
+        "🎚️ Log filter changed from '{}' to '{}' by {}",
+        previous,
+        payload.filter,
+        &auth.key_id
+    );
+    Ok(HttpResponse::Ok().json(payload.into_inner()))
+}
+
+// Tenant whose dead letter queue an admin works on: always the caller's own. Naming another
+// tenant is refused rather than silently ignored.
+fn dlq_tenant(auth: &AuthContext, requested: Option<&str>) -> Result<&'static TenantPool, Error> {
+    let tenant = resolve_tenant(Some(auth))?;
+    let pool = get_rabbitmq_pool().inspect_err(|e| error!("RabbitMQ pool error: {}", e))?;
+    let tenant = pool
+        .tenant(tenant.name())
+        .ok_or_else(|| Error::Config(format!("Tenant {} has no connection", tenant.name())))?;
+    match requested {
+        Some(name) if name != tenant.name() => {
+            warn!(
+                "🔒 {} (tenant {}) may not use the dead letter queue of tenant {}",
+                auth.key_id,
+                tenant.name(),
+                name
+            );
+            Err(Error::Forbidden(format!(
+                "Tenant {} is not the caller's tenant",
+                name
+            )))
+        }
+        _ => Ok(tenant),
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DlqTenantQuery {
+    /// Tenant whose dead letter queue is read; only the caller's own tenant is allowed.
+    pub tenant: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DlqLimitQuery {
+    /// Messages or records returned, at most 1000.
+    #[serde(default = "default_dlq_limit")]
+    pub limit: usize,
+}
+
+/// Dead-lettered messages to publish again.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ReplayRequest {
+    pub tenant: Option<String>,
+    pub ids: Vec<String>,
+    /// JSON merge patch applied to each body before it is published, e.g. to fix a field.
+    #[schema(value_type = Option<Object>)]
+    pub patch: Option<serde_json::Value>,
+}
+
+/// Dead-lettered messages to drop: those with the given ids and matching the filter.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PurgeRequest {
+    #[serde(flatten)]
+    pub filter: DlqFilter,
+    #[serde(default)]
+    pub ids: Vec<String>,
+    /// Required to purge every message when no ids or filter are given.
+    #[serde(default)]
+    pub all: bool,
+}
+
+fn default_dlq_limit() -> usize {
+    50
+}
+
+const MAX_DLQ_LIMIT: usize = 1000;
+
+/// Messages in a tenant's dead letter queue, oldest first, with their failure reason and
+/// `x-death` entries. Only the first `DLQ_SCAN_LIMIT` messages of the queue are read.
+#[utoipa::path(
+    tag = "admin",
+    params(DlqFilter, DlqLimitQuery),
+    responses(
+        (status = 200, description = "Dead-lettered messages", body = DlqListing),
+        (status = 403, description = "Another tenant's queue", body = ErrorBody),
+        (status = 503, description = "Broker unavailable", body = ErrorBody),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+#[get("/admin/dlq")]
+pub async fn list_dlq(
+    filter: web::Query<DlqFilter>,
+    query: web::Query<DlqLimitQuery>,
+    auth: web::ReqData<AuthContext>,
+) -> Result<HttpResponse, Error> {
+    let tenant = dlq_tenant(&auth, filter.tenant.as_deref())?;
+    let limit = query.limit.clamp(1, MAX_DLQ_LIMIT);
+    Ok(HttpResponse::Ok().json(dlq::list(tenant, &filter, limit).await?))
+}
+
+/// Replays and purges of dead-lettered messages, newest first.
+#[utoipa::path(
+    tag = "admin",
+    params(DlqLimitQuery),
+    responses((status = 200, description = "Audit records", body = Vec<AuditRecord>)),
+    security(("api_key" = []), ("bearer" = []))
+)]
+#[get("/admin/dlq/audit")]
+pub async fn get_dlq_audit(
+    query: web::Query<DlqLimitQuery>,
+    auth: web::ReqData<AuthContext>,
+) -> Result<HttpResponse, Error> {
+    let tenant = dlq_tenant(&auth, None)?;
+    let limit = query.limit.clamp(1, MAX_DLQ_LIMIT);
+    Ok(HttpResponse::Ok().json(dlq::audit_log()?.recent(tenant.name(), limit)?))
+}
+
+/// A dead-lettered message with its body and headers.
+#[utoipa::path(
+    tag = "admin",
+    params(("id" = String, Path), DlqTenantQuery),
+    responses(
+        (status = 200, description = "Dead-lettered message", body = DlqMessage),
+        (status = 403, description = "Another tenant's queue", body = ErrorBody),
+        (status = 404, description = "No such message among the scanned ones", body = ErrorBody),
+        (status = 503, description = "Broker unavailable", body = ErrorBody),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+#[get("/admin/dlq/{id}")]
+pub async fn get_dlq_message(
+    path: web::Path<String>,
+    query: web::Query<DlqTenantQuery>,
+    auth: web::ReqData<AuthContext>,
+) -> Result<HttpResponse, Error> {
+    let id = path.into_inner();
+    let tenant = dlq_tenant(&auth, query.tenant.as_deref())?;
+    match dlq::show(tenant, &id).await? {
+        Some(message) => Ok(HttpResponse::Ok().json(message)),
+        None => Err(Error::NotFound(format!(
+            "Message {} not found in the dead letter queue of tenant {}",
+            id,
+            tenant.name()
+        ))),
+    }
+}
+
+/// Publishes dead-lettered messages back to the delayed exchange with their original routing
+/// key, optionally patched, and records who replayed them.
+#[utoipa::path(
+    tag = "admin",
+    request_body = ReplayRequest,
+    responses(
+        (status = 200, description = "Messages replayed", body = DlqActionResult),
+        (status = 403, description = "Another tenant's queue", body = ErrorBody),
+        (status = 422, description = "No ids, or a patch that cannot be applied", body = ErrorBody),
+        (status = 503, description = "Broker unavailable", body = ErrorBody),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+#[post("/admin/dlq/replay")]
+pub async fn replay_dlq(
+    payload: web::Json<ReplayRequest>,
+    auth: web::ReqData<AuthContext>,
+) -> Result<HttpResponse, Error> {
+    let tenant = dlq_tenant(&auth, payload.tenant.as_deref())?;
+    let result = dlq::replay(
+        tenant,
+        &payload.ids,
+        payload.patch.as_ref(),
+        &auth.key_id,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(result))
+}
+
+/// Drops dead-lettered messages by id, reason or queue, and records who purged them.
+#[utoipa::path(
+    tag = "admin",
+    request_body = PurgeRequest,
+    responses(
+        (status = 200, description = "Messages purged", body = DlqActionResult),
+        (status = 403, description = "Another tenant's queue", body = ErrorBody),
+        (status = 422, description = "Neither ids, a filter nor all given", body = ErrorBody),
+        (status = 503, description = "Broker unavailable", body = ErrorBody),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+#[post("/admin/dlq/purge")]
+pub async fn purge_dlq(
+    payload: web::Json<PurgeRequest>,
+    auth: web::ReqData<AuthContext>,
+) -> Result<HttpResponse, Error> {
+    let tenant = dlq_tenant(&auth, payload.filter.tenant.as_deref())?;
+    let result = dlq::purge(
+        tenant,
+        &payload.ids,
+        &payload.filter,
+        payload.all,
+        &auth.key_id,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(result))
+}
@@ -0,0 +1,413 @@
+use actix_web::{
+    Error, HttpMessage, ResponseError,
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::Method,
+    middleware::Next,
+    web,
+};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+
+use crate::config::{get_config, init_config};
+use crate::jwt::get_jwt_verifier;
+use crate::store::JsonFile;
+
+pub const API_KEY_HEADER: &str = "X-API-Key";
+pub const KEY_ID_HEADER: &str = "X-Key-Id";
+pub const TIMESTAMP_HEADER: &str = "X-Timestamp";
+pub const SIGNATURE_HEADER: &str = "X-Signature";
+
+/// Probes, metrics and API documentation, served without authentication.
+const PUBLIC_PATHS: [&str; 5] = ["/healthz", "/readyz", "/metrics", "/openapi.json", "/docs"];
+
+/// Signed requests older or newer than this are rejected to limit replays.
+const MAX_CLOCK_SKEW_SECS: u64 = 300;
+
+/// Message MACed with the API key secret to derive its request signing secret.
+const SIGNING_SECRET_CONTEXT: &[u8] = b"notification-request-signing";
+
+/// Permission attached to an API key.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    #[serde(rename = "notify:send")]
+    NotifySend,
+    #[serde(rename = "schedule:write")]
+    ScheduleWrite,
+    /// Delivery status of notifications, one at a time or streamed.
+    #[serde(rename = "status:read")]
+    StatusRead,
+    /// Users' in-app inboxes, including marking entries as read.
+    #[serde(rename = "inbox:read")]
+    InboxRead,
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::NotifySend => "notify:send",
+            Scope::ScheduleWrite => "schedule:write",
+            Scope::StatusRead => "status:read",
+            Scope::InboxRead => "inbox:read",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+/// An API client. Only the SHA-256 hash of the secret is stored.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApiKey {
+    pub id: String,
+    /// Hex SHA-256 of the secret.
+    pub hash: String,
+    pub tenant: String,
+    pub scopes: Vec<Scope>,
+    /// Default webhook for notifications sent with this key.
+    #[serde(default)]
+    pub callback_url: Option<String>,
+    /// Id of the callback secret signing that webhook, see `CALLBACK_SECRETS_PATH`.
+    #[serde(default)]
+    pub callback_secret_id: Option<String>,
+    /// Secret for HMAC-signed requests, see [`derive_signing_secret`]. Keys without one only
+    /// accept the plain secret.
+    #[serde(default)]
+    pub signing_secret: Option<String>,
+}
+
+/// How the caller proved its identity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMethod {
+    ApiKey,
+    Signature,
+    Jwt,
+}
+
+/// The authenticated caller, available to handlers as `web::ReqData<AuthContext>`.
+#[derive(Debug, Clone)]
+pub struct AuthContext {
+    pub key_id: String,
+    pub tenant: String,
+    pub scopes: Vec<Scope>,
+    pub callback_url: Option<String>,
+    pub callback_secret_id: Option<String>,
+    pub method: AuthMethod,
+}
+
+impl AuthContext {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&Scope::Admin) || self.scopes.contains(&scope)
+    }
+
+    pub fn with_method(mut self, method: AuthMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Gateway tokens may only target users of their own tenant, namespaced as
+    /// `<tenant>:<user>`. Admins may target anyone.
+    pub fn permits_user(&self, user_id: &str) -> bool {
+        if self.method != AuthMethod::Jwt || self.has_scope(Scope::Admin) {
+            return true;
+        }
+        user_id
+            .strip_prefix(self.tenant.as_str())
+            .is_some_and(|rest| rest.starts_with(':'))
+    }
+}
+
+impl From<&ApiKey> for AuthContext {
+    fn from(key: &ApiKey) -> Self {
+        AuthContext {
+            key_id: key.id.clone(),
+            tenant: key.tenant.clone(),
+            scopes: key.scopes.clone(),
+            callback_url: key.callback_url.clone(),
+            callback_secret_id: key.callback_secret_id.clone(),
+            method: AuthMethod::ApiKey,
+        }
+    }
+}
+
+pub fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+/// Hex HMAC-SHA256 of a fixed context keyed with the API key secret. Computed when the key is
+/// issued, so the stored hash, which only verifies plain keys, can never sign requests.
+pub fn derive_signing_secret(secret: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(SIGNING_SECRET_CONTEXT);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Compares without short-circuiting so timing does not reveal how much of a hash matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub struct ApiKeyStore {
+    keys: Vec<ApiKey>,
+}
+
+impl ApiKeyStore {
+    pub fn new(keys: Vec<ApiKey>) -> Self {
+        ApiKeyStore { keys }
+    }
+
+    fn find(&self, key_id: &str) -> Option<&ApiKey> {
+        self.keys.iter().find(|k| k.id == key_id)
+    }
+
+    /// Checks a plain `<key id>.<secret>` credential.
+    pub fn verify_key(&self, credential: &str) -> Option<&ApiKey> {
+        let (key_id, secret) = credential.split_once('.')?;
+        let key = self.find(key_id)?;
+        constant_time_eq(hash_secret(secret).as_bytes(), key.hash.as_bytes()).then_some(key)
+    }
+
+    /// Checks an HMAC-signed request, keyed with the key's `signing_secret`.
+    pub fn verify_signature(
+        &self,
+        key_id: &str,
+        timestamp: i64,
+        signature: &str,
+        method: &str,
+        path: &str,
+        body: &[u8],
+    ) -> Option<&ApiKey> {
+        // `abs_diff` cannot overflow, whatever timestamp the client sent
+        if Utc::now().timestamp().abs_diff(timestamp) > MAX_CLOCK_SKEW_SECS {
+            return None;
+        }
+        let key = self.find(key_id)?;
+        let signing_secret = key.signing_secret.as_deref()?;
+        let expected = hex::decode(signature.strip_prefix("sha256=").unwrap_or(signature)).ok()?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(signing_secret.as_bytes()).ok()?;
+        mac.update(signing_string(method, path, timestamp).as_bytes());
+        mac.update(body);
+        mac.verify_slice(&expected).ok().map(|_| key)
+    }
+}
+
+/// `<METHOD>\n<path and query>\n<timestamp>\n` followed by the raw body.
+pub fn signing_string(method: &str, path: &str, timestamp: i64) -> String {
+    format!("{}\n{}\n{}\n", method, path, timestamp)
+}
+
+// Singleton global; `None` when no API keys are configured
+lazy_static::lazy_static! {
+    static ref API_KEYS: tokio::sync::OnceCell<Option<ApiKeyStore>> = tokio::sync::OnceCell::new();
+}
+
+pub fn init_api_keys() -> Result<(), Box<dyn std::error::Error>> {
+    let config = init_config()?;
+    let store = match (&config.api_keys, &config.api_keys_path) {
+        (Some(json), _) => {
+            // serde's messages can quote the input, so only the position is reported
+            let keys: Vec<ApiKey> = serde_json::from_str(json.expose_secret()).map_err(|e| {
+                format!(
+                    "API_KEYS is not a valid API key list (line {}, column {})",
+                    e.line(),
+                    e.column()
+                )
+            })?;
+            if keys.is_empty() {
+                return Err("No API keys found in API_KEYS".into());
+            }
+            info!("🔑 Loaded {} API keys from API_KEYS", keys.len());
+            Some(ApiKeyStore::new(keys))
+        }
+        (None, Some(path)) => {
+            let keys: Vec<ApiKey> = JsonFile::new(path).load()?;
+            if keys.is_empty() {
+                return Err(format!("No API keys found in {}", path).into());
+            }
+            info!("🔑 Loaded {} API keys from {}", keys.len(), path);
+            Some(ApiKeyStore::new(keys))
+        }
+        (None, None) => {
+            warn!("⚠️ API_KEYS_PATH and API_KEYS not set: API keys are not accepted");
+            None
+        }
+    };
+    API_KEYS
+        .set(store)
+        .map_err(|_| "Failed to set API key store")?;
+    Ok(())
+}
+
+/// Refuses to serve the API without credentials unless `AUTH_DISABLED=true`. Call after the
+/// API keys and JWT keys are loaded.
+pub fn check_auth_configured() -> Result<(), Box<dyn std::error::Error>> {
+    let config = init_config()?;
+    if API_KEYS.get().and_then(Option::as_ref).is_some() || get_jwt_verifier().is_some() {
+        return Ok(());
+    }
+    if !config.auth_disabled {
+        return Err("No API_KEYS_PATH, API_KEYS, JWT_JWKS_PATH, JWT_PUBLIC_KEY_PATH or \
+            JWT_HMAC_SECRET set; set one, or AUTH_DISABLED=true to serve the API without \
+            authentication"
+            .into());
+    }
+    warn!("⚠️ AUTH_DISABLED=true: the API accepts requests without credentials, except /admin");
+    Ok(())
+}
+
+/// Scope a route requires; `None` means any authenticated caller may use it.
+pub fn required_scope(method: &Method, path: &str) -> Option<Scope> {
+    if path.starts_with("/admin") {
+        return Some(Scope::Admin);
+    }
+    match (method, path) {
+        (&Method::POST, "/notify" | "/notify-delayed" | "/notify-at") => Some(Scope::NotifySend),
+        (&Method::POST, "/schedule-notification") => Some(Scope::ScheduleWrite),
+        (_, path) if path.starts_with("/notifications/") => Some(Scope::StatusRead),
+        (_, path) if path.starts_with("/users/") => Some(Scope::InboxRead),
+        _ => None,
+    }
+}
+
+fn reject(req: ServiceRequest, error: crate::error::Error) -> ServiceResponse<BoxBody> {
+    let mut response = error.error_response();
+    response.headers_mut().insert(
+        actix_web::http::header::WWW_AUTHENTICATE,
+        actix_web::http::header::HeaderValue::from_static("ApiKey, Bearer"),
+    );
+    req.into_response(response)
+}
+
+fn header<'a>(req: &'a ServiceRequest, name: &str) -> Option<&'a str> {
+    req.headers().get(name).and_then(|v| v.to_str().ok())
+}
+
+/// Checks an API key or HMAC-signed request. `Ok(Err(reason))` means the caller is rejected.
+async fn authenticate_api_key(
+    req: &mut ServiceRequest,
+    store: &'static ApiKeyStore,
+) -> Result<Result<AuthContext, &'static str>, Error> {
+    if let Some(key_id) = header(req, KEY_ID_HEADER).map(str::to_string) {
+        let timestamp = header(req, TIMESTAMP_HEADER).and_then(|t| t.parse::<i64>().ok());
+        let signature = header(req, SIGNATURE_HEADER).map(str::to_string);
+        let (Some(timestamp), Some(signature)) = (timestamp, signature) else {
+            return Ok(Err("Signed requests need X-Timestamp and X-Signature"));
+        };
+
+        // The body is part of the signature; read it and hand it back to the handler
+        let body = req.extract::<web::Bytes>().await?;
+        let method = req.method().to_string();
+        let path = req
+            .uri()
+            .path_and_query()
+            .map(|p| p.as_str().to_string())
+            .unwrap_or_default();
+        let key = store.verify_signature(&key_id, timestamp, &signature, &method, &path, &body);
+        req.set_payload(body.into());
+        return Ok(match key {
+            Some(key) => Ok(AuthContext::from(key).with_method(AuthMethod::Signature)),
+            None => {
+                warn!(
+                    "🔒 Rejected signed request for key id {}: bad signature or timestamp",
+                    key_id
+                );
+                Err("Invalid credentials")
+            }
+        });
+    }
+
+    let credential = header(req, API_KEY_HEADER)
+        .or_else(|| header(req, "Authorization").and_then(|v| v.strip_prefix("ApiKey ")));
+    let Some(credential) = credential else {
+        return Ok(Err("Missing API key or bearer token"));
+    };
+    Ok(match store.verify_key(credential) {
+        Some(key) => Ok(AuthContext::from(key)),
+        None => {
+            let key_id = credential
+                .split_once('.')
+                .map(|(id, _)| id)
+                .unwrap_or("<malformed>");
+            warn!("🔒 Rejected API key with id {}", key_id);
+            Err("Invalid credentials")
+        }
+    })
+}
+
+/// Authenticates every request by bearer JWT, API key (`X-API-Key` / `Authorization: ApiKey`)
+/// or HMAC signature, then checks the route's scope. Logs key ids, never secrets.
+/// Probes, metrics and the API documentation are public; with `AUTH_DISABLED=true` and no keys
+/// configured, so is everything but `/admin`.
+pub async fn authenticate(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    if PUBLIC_PATHS.contains(&req.path()) {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+
+    let api_keys = API_KEYS.get().and_then(Option::as_ref);
+    let jwt = get_jwt_verifier();
+    if api_keys.is_none() && jwt.is_none() {
+        let auth_disabled = get_config().is_ok_and(|c| c.auth_disabled);
+        if auth_disabled && !req.path().starts_with("/admin") {
+            return Ok(next.call(req).await?.map_into_boxed_body());
+        }
+        return Ok(reject(
+            req,
+            crate::error::Error::Unauthorized("No credentials are configured".to_string()),
+        ));
+    }
+
+    let bearer = header(&req, "Authorization")
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::to_string);
+    let result = match (bearer, jwt, api_keys) {
+        (Some(token), Some(verifier), _) => verifier.verify(&token).map_err(|e| {
+            warn!("🔒 Rejected bearer token: {}", e);
+            "Invalid bearer token"
+        }),
+        (Some(_), None, _) => Err("Bearer tokens are not accepted"),
+        (None, _, Some(store)) => authenticate_api_key(&mut req, store).await?,
+        (None, _, None) => Err("Missing bearer token"),
+    };
+
+    let context = match result {
+        Ok(context) => context,
+        Err(reason) => {
+            return Ok(reject(
+                req,
+                crate::error::Error::Unauthorized(reason.to_string()),
+            ));
+        }
+    };
+
+    if let Some(scope) = required_scope(req.method(), req.path())
+        && !context.has_scope(scope)
+    {
+        warn!(
+            "🔒 Key {} lacks scope {} for {} {}",
+            context.key_id,
+            scope.as_str(),
+            req.method(),
+            req.path()
+        );
+        return Ok(reject(
+            req,
+            crate::error::Error::Forbidden(format!("Missing scope {}", scope.as_str())),
+        ));
+    }
+
+    debug!(
+        "🔑 {} {} authenticated with key {}",
+        req.method(),
+        req.path(),
+        context.key_id
+    );
+    req.extensions_mut().insert(context);
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
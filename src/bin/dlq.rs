@@ -0,0 +1,408 @@
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use reqwest::{Method, RequestBuilder};
+use serde_json::{Value, json};
+
+const DEFAULT_URL: &str = "http://localhost:8081";
+
+fn command() -> Command {
+    Command::new("dlq")
+        .about("Inspects, replays and purges dead-lettered notifications through the admin API")
+        .version(env!("CARGO_PKG_VERSION"))
+        .subcommand_required(true)
+        .arg(
+            Arg::new("url")
+                .long("url")
+                .global(true)
+                .value_name("URL")
+                .help(format!(
+                    "Base URL of the notification server [env: NOTIFY_URL] [default: {}]",
+                    DEFAULT_URL
+                )),
+        )
+        .arg(
+            Arg::new("api-key")
+                .long("api-key")
+                .global(true)
+                .value_name("KEY")
+                .help("API key with the admin scope [env: NOTIFY_API_KEY]"),
+        )
+        .arg(
+            Arg::new("token")
+                .long("token")
+                .global(true)
+                .value_name("JWT")
+                .help("Bearer token with the admin scope [env: NOTIFY_TOKEN]"),
+        )
+        .arg(
+            Arg::new("tenant")
+                .long("tenant")
+                .global(true)
+                .value_name("TENANT")
+                .help("Tenant whose dead letter queue is used; must be the caller's own"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Print the server's JSON response"),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("List dead-lettered messages, oldest first")
+                .arg(filter_arg(
+                    "reason",
+                    "Only messages with this failure reason",
+                ))
+                .arg(filter_arg(
+                    "queue",
+                    "Only messages dead-lettered from this queue",
+                ))
+                .arg(
+                    Arg::new("limit")
+                        .long("limit")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("50")
+                        .help("Messages listed"),
+                ),
+        )
+        .subcommand(
+            Command::new("show")
+                .about("Show a message with its body and headers")
+                .arg(Arg::new("id").required(true).help("Message id")),
+        )
+        .subcommand(
+            Command::new("replay")
+                .about("Publish messages again on the delayed exchange")
+                .arg(
+                    Arg::new("ids")
+                        .required(true)
+                        .num_args(1..)
+                        .value_name("ID")
+                        .help("Ids of the messages to replay"),
+                )
+                .arg(
+                    Arg::new("patch")
+                        .long("patch")
+                        .value_name("JSON")
+                        .conflicts_with("patch-file")
+                        .help(
+                            "JSON merge patch applied to each body, e.g. '{\"channel\":\"sms\"}'",
+                        ),
+                )
+                .arg(
+                    Arg::new("patch-file")
+                        .long("patch-file")
+                        .value_name("FILE")
+                        .help("File holding the JSON merge patch"),
+                ),
+        )
+        .subcommand(
+            Command::new("purge")
+                .about("Drop messages by id, reason or queue")
+                .arg(
+                    Arg::new("id")
+                        .long("id")
+                        .value_name("ID")
+                        .action(ArgAction::Append)
+                        .help("Id of a message to drop; repeat for several"),
+                )
+                .arg(filter_arg(
+                    "reason",
+                    "Drop messages with this failure reason",
+                ))
+                .arg(filter_arg(
+                    "queue",
+                    "Drop messages dead-lettered from this queue",
+                ))
+                .arg(
+                    Arg::new("all")
+                        .long("all")
+                        .action(ArgAction::SetTrue)
+                        .help("Drop every message when no id or filter is given"),
+                ),
+        )
+        .subcommand(
+            Command::new("audit")
+                .about("Show who replayed or purged which messages, newest first")
+                .arg(
+                    Arg::new("limit")
+                        .long("limit")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("50")
+                        .help("Records shown"),
+                ),
+        )
+}
+
+fn filter_arg(name: &'static str, help: &'static str) -> Arg {
+    Arg::new(name).long(name).value_name("VALUE").help(help)
+}
+
+/// The admin API, authenticated like any other caller.
+struct Client {
+    http: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+    token: Option<String>,
+}
+
+impl Client {
+    fn new(matches: &ArgMatches) -> Self {
+        let option = |arg: &str, env: &str| {
+            matches
+                .get_one::<String>(arg)
+                .cloned()
+                .or_else(|| std::env::var(env).ok().filter(|v| !v.trim().is_empty()))
+        };
+        Client {
+            http: reqwest::Client::new(),
+            url: option("url", "NOTIFY_URL")
+                .unwrap_or_else(|| DEFAULT_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            api_key: option("api-key", "NOTIFY_API_KEY"),
+            token: option("token", "NOTIFY_TOKEN"),
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let mut request = self.http.request(method, format!("{}{}", self.url, path));
+        if let Some(api_key) = &self.api_key {
+            request = request.header("X-API-Key", api_key);
+        }
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        request
+    }
+
+    /// Sends the request and returns the JSON body, or the server's error message.
+    async fn send(&self, request: RequestBuilder) -> Result<Value, String> {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Cannot reach {}: {}", self.url, e))?;
+        let status = response.status();
+        let body: Value = response
+            .json()
+            .await
+            .map_err(|e| format!("Invalid response ({}): {}", status, e))?;
+        if !status.is_success() {
+            let message = body["message"].as_str().unwrap_or("request failed");
+            let mut error = format!("{} ({})", message, status);
+            for field_error in body["field_errors"].as_array().into_iter().flatten() {
+                error.push_str(&format!(
+                    "\n  {}: {}",
+                    field_error["field"].as_str().unwrap_or_default(),
+                    field_error["message"].as_str().unwrap_or_default()
+                ));
+            }
+            return Err(error);
+        }
+        Ok(body)
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let matches = command().get_matches();
+    if let Err(e) = run(&matches).await {
+        eprintln!("❌ {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(matches: &ArgMatches) -> Result<(), String> {
+    let client = Client::new(matches);
+    let tenant = matches.get_one::<String>("tenant");
+    let json_output = matches.get_flag("json");
+    let (name, args) = matches.subcommand().expect("a subcommand is required");
+
+    let mut query: Vec<(&str, String)> =
+        tenant.map(|t| ("tenant", t.clone())).into_iter().collect();
+    let response = match name {
+        "list" => {
+            for filter in ["reason", "queue"] {
+                if let Some(value) = args.get_one::<String>(filter) {
+                    query.push((filter, value.clone()));
+                }
+            }
+            query.push(("limit", args.get_one::<usize>("limit").unwrap().to_string()));
+            let response = client
+                .send(client.request(Method::GET, "/admin/dlq").query(&query))
+                .await?;
+            if !json_output {
+                print_listing(&response);
+                return Ok(());
+            }
+            response
+        }
+        "show" => {
+            let id = args.get_one::<String>("id").unwrap();
+            let path = format!("/admin/dlq/{}", urlencode(id));
+            client
+                .send(client.request(Method::GET, &path).query(&query))
+                .await?
+        }
+        "replay" => {
+            let ids: Vec<&String> = args.get_many("ids").unwrap().collect();
+            let patch = match (
+                args.get_one::<String>("patch"),
+                args.get_one::<String>("patch-file"),
+            ) {
+                (Some(patch), _) => Some(patch.clone()),
+                (None, Some(file)) => Some(
+                    std::fs::read_to_string(file)
+                        .map_err(|e| format!("Cannot read {}: {}", file, e))?,
+                ),
+                (None, None) => None,
+            };
+            let patch: Option<Value> = patch
+                .map(|patch| serde_json::from_str(&patch))
+                .transpose()
+                .map_err(|e| format!("Invalid patch: {}", e))?;
+            let body = json!({"tenant": tenant, "ids": ids, "patch": patch});
+            let response = client
+                .send(
+                    client
+                        .request(Method::POST, "/admin/dlq/replay")
+                        .json(&body),
+                )
+                .await?;
+            if !json_output {
+                print_action(&response, "Replayed");
+                return Ok(());
+            }
+            response
+        }
+        "purge" => {
+            let ids: Vec<&String> = args.get_many("id").into_iter().flatten().collect();
+            let body = json!({
+                "tenant": tenant,
+                "ids": ids,
+                "reason": args.get_one::<String>("reason"),
+                "queue": args.get_one::<String>("queue"),
+                "all": args.get_flag("all"),
+            });
+            let response = client
+                .send(client.request(Method::POST, "/admin/dlq/purge").json(&body))
+                .await?;
+            if !json_output {
+                print_action(&response, "Purged");
+                return Ok(());
+            }
+            response
+        }
+        "audit" => {
+            let limit = args.get_one::<usize>("limit").unwrap().to_string();
+            let response = client
+                .send(
+                    client
+                        .request(Method::GET, "/admin/dlq/audit")
+                        .query(&[("limit", limit)]),
+                )
+                .await?;
+            if !json_output {
+                print_audit(&response);
+                return Ok(());
+            }
+            response
+        }
+        other => unreachable!("unknown subcommand {}", other),
+    };
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&response).unwrap_or_default()
+    );
+    Ok(())
+}
+
+/// Percent-encodes a path segment; ids are UUIDs or `sha256:<hex>`.
+fn urlencode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn text(value: &Value) -> &str {
+    value.as_str().unwrap_or("-")
+}
+
+fn print_listing(listing: &Value) {
+    let messages = listing["messages"].as_array().cloned().unwrap_or_default();
+    println!(
+        "Tenant {}: {} messages in the dead letter queue, {} scanned, {} listed",
+        text(&listing["tenant"]),
+        listing["total"],
+        listing["scanned"],
+        messages.len()
+    );
+    if messages.is_empty() {
+        return;
+    }
+    println!(
+        "{:<40}  {:<20}  {:<34}  {:<25}  {:>6}",
+        "ID", "REASON", "ROUTING KEY", "DEAD-LETTERED AT", "DEATHS"
+    );
+    for message in &messages {
+        let deaths: i64 = message["x_death"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|d| d["count"].as_i64())
+            .sum();
+        println!(
+            "{:<40}  {:<20}  {:<34}  {:<25}  {:>6}",
+            text(&message["id"]),
+            text(&message["reason"]),
+            text(&message["routing_key"]),
+            text(&message["dead_lettered_at"]),
+            deaths
+        );
+    }
+}
+
+fn print_action(result: &Value, done: &str) {
+    println!(
+        "{} {} messages of tenant {}",
+        done,
+        result["count"],
+        text(&result["tenant"])
+    );
+    for id in result["ids"].as_array().into_iter().flatten() {
+        println!("  {}", text(id));
+    }
+    let missing = result["missing"].as_array().cloned().unwrap_or_default();
+    if !missing.is_empty() {
+        let missing: Vec<&str> = missing.iter().map(text).collect();
+        eprintln!("⚠️ Not found: {}", missing.join(", "));
+    }
+}
+
+fn print_audit(records: &Value) {
+    for record in records.as_array().into_iter().flatten() {
+        let ids: Vec<&str> = record["ids"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(text)
+            .collect();
+        println!(
+            "{}  {:<8}  {:<20}  {:<12}  {}",
+            text(&record["at"]),
+            text(&record["action"]),
+            text(&record["actor"]),
+            text(&record["tenant"]),
+            ids.join(", ")
+        );
+    }
+}
@@ -1,42 +1,56 @@
-use integration_rust_rabbitmq::worker_utils;
-use tracing::{error, info, warn};
-
-const MAX_RETRIES: u32 = 3;
-const RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(5);
+use integration_rust_rabbitmq::{config, health, settings, telemetry, worker_utils};
+use tracing::{error, info};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let sources = settings::Sources::from_args(
+        "notification-worker",
+        "Consumes notification queues and delivers notifications",
+    );
+    if sources.print_config {
+        match config::print_config(&sources) {
+            Ok(toml) => print!("{}", toml),
+            Err(e) => {
+                eprintln!("❌ Invalid configuration: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
     // Configure tracing
-    tracing_subscriber::fmt()
-        .with_env_filter("worker=info,integration_rust_rabbitmq=info")
-        .init();
+    let config = config::init_config_with(&sources);
+    let tracer_provider = telemetry::init_tracing(
+        config.as_deref().ok(),
+        "notification-worker",
+        "worker=info,integration_rust_rabbitmq=info",
+    )?;
+    let config = config?;
 
     info!("🔧 Worker starting...");
 
+    // Apply safe configuration changes on SIGHUP
+    tokio::spawn(config::config_reload_task());
+
+    // The worker signs callbacks; messages only carry the secrets' ids
+    integration_rust_rabbitmq::callbacks::init_callback_secrets()?;
+
     // Initialize RabbitMQ pool once at startup
     integration_rust_rabbitmq::connection::init_rabbitmq_pool().await?;
 
-    let mut retry_count = 0;
-    loop {
-        match worker_utils::run_worker().await {
-            Ok(_) => {
-                info!("Worker completed successfully");
-                break;
-            }
-            Err(e) => {
-                error!("Worker failed: {}", e);
-                retry_count += 1;
-                if retry_count >= MAX_RETRIES {
-                    error!("Max retries ({}) exceeded. Exiting.", MAX_RETRIES);
-                    return Err(e);
-                }
-                warn!(
-                    "Retrying in {:?} (attempt {}/{})",
-                    RETRY_DELAY, retry_count, MAX_RETRIES
-                );
-                tokio::time::sleep(RETRY_DELAY).await;
-            }
-        }
+    // Probes for the container orchestrator
+    if config.worker_health_port != 0 {
+        tokio::spawn(health::worker_health_server()?);
     }
-    Ok(())
+
+    // Consumers restart on their own; an error means one could not be brought back
+    let result = worker_utils::run_worker().await;
+    match &result {
+        Ok(()) => info!("Worker stopped"),
+        Err(e) => error!("Worker failed: {}", e),
+    }
+
+    // Export the spans of the last deliveries before exiting
+    telemetry::shutdown(tracer_provider);
+    Ok(result?)
 }
@@ -0,0 +1,359 @@
+use chrono::{DateTime, Utc};
+use futures_util::stream::StreamExt;
+use hmac::{Hmac, Mac};
+use lapin::message::Delivery;
+use lapin::{
+    BasicProperties,
+    options::*,
+    types::{AMQPValue, FieldTable},
+};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{Instrument, error, info, warn};
+use uuid::Uuid;
+
+use crate::config::init_config;
+use crate::error::Error;
+use crate::health::{ConsumerState, LAST_MESSAGE, set_consumer_state};
+use crate::connection::TenantPool;
+use crate::status::DeliveryState;
+use crate::store::JsonFile;
+use crate::validation::is_public_address;
+use crate::worker_utils::TrackedDelivery;
+
+pub const SIGNATURE_HEADER: &str = "X-Notification-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Notification-Timestamp";
+
+/// A pending webhook call telling an upstream service how a notification ended.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CallbackJob {
+    pub notification_id: Uuid,
+    pub user_id: String,
+    pub url: String,
+    /// Id of the tenant's signing secret; the job never carries the secret itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_id: Option<String>,
+    pub outcome: DeliveryState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+    #[serde(default)]
+    pub attempt: u32,
+}
+
+/// Body POSTed to the callback URL.
+#[derive(Debug, Serialize)]
+struct CallbackPayload<'a> {
+    notification_id: Uuid,
+    user_id: &'a str,
+    outcome: DeliveryState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+    occurred_at: DateTime<Utc>,
+    attempt: u32,
+}
+
+impl<'a> From<&'a CallbackJob> for CallbackPayload<'a> {
+    fn from(job: &'a CallbackJob) -> Self {
+        CallbackPayload {
+            notification_id: job.notification_id,
+            user_id: &job.user_id,
+            outcome: job.outcome,
+            error: job.error.as_deref(),
+            occurred_at: job.occurred_at,
+            attempt: job.attempt,
+        }
+    }
+}
+
+/// Callback signing secrets by tenant and id, from `CALLBACK_SECRETS_PATH`:
+/// `{"<tenant>": {"<secret id>": "<secret>"}}`. Notifications, callback jobs and dead letters
+/// only name a secret by id, so secrets never pass through the broker.
+#[derive(Default, Deserialize)]
+#[serde(transparent)]
+pub struct CallbackSecrets(HashMap<String, HashMap<String, String>>);
+
+impl CallbackSecrets {
+    pub fn new(secrets: HashMap<String, HashMap<String, String>>) -> Self {
+        CallbackSecrets(secrets)
+    }
+
+    /// A tenant can only sign with its own secrets.
+    pub fn get(&self, tenant: &str, id: &str) -> Option<&str> {
+        self.0.get(tenant)?.get(id).map(String::as_str)
+    }
+}
+
+// Singleton global; empty when no callback secrets are configured
+lazy_static::lazy_static! {
+    static ref CALLBACK_SECRETS: tokio::sync::OnceCell<CallbackSecrets> = tokio::sync::OnceCell::new();
+}
+
+/// Loads `CALLBACK_SECRETS_PATH`. The server checks the ids notifications refer to, the worker
+/// signs with the secrets.
+pub fn init_callback_secrets() -> Result<(), Box<dyn std::error::Error>> {
+    let config = init_config()?;
+    let secrets = match &config.callback_secrets_path {
+        Some(path) => {
+            let secrets: CallbackSecrets = JsonFile::new(path).load()?;
+            let count: usize = secrets.0.values().map(HashMap::len).sum();
+            info!("🔑 Loaded {} callback secrets from {}", count, path);
+            secrets
+        }
+        None => CallbackSecrets::default(),
+    };
+    CALLBACK_SECRETS
+        .set(secrets)
+        .map_err(|_| "Failed to set callback secrets")?;
+    Ok(())
+}
+
+/// A tenant's callback secret, `None` when the id is unknown or no secrets are loaded.
+pub fn callback_secret(tenant: &str, id: &str) -> Option<&'static str> {
+    CALLBACK_SECRETS.get()?.get(tenant, id)
+}
+
+/// Hex HMAC-SHA256 over `<timestamp>.<body>`, sent as `sha256=<hex>`.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Publishes a callback job on the tenant's callback queue, optionally delayed for a retry backoff,
+/// and waits for the broker's confirm.
+pub async fn enqueue_callback(
+    tenant: &TenantPool,
+    job: &CallbackJob,
+    delay_ms: i32,
+) -> Result<(), Error> {
+    let channel = tenant.get_publish_channel().await?;
+    let body = serde_json::to_vec(job)?;
+
+    let properties = if delay_ms > 0 {
+        BasicProperties::default().with_headers({
+            let mut table = FieldTable::default();
+            table.insert("x-delay".into(), AMQPValue::LongInt(delay_ms));
+            table
+        })
+    } else {
+        BasicProperties::default()
+    };
+
+    let confirm = channel
+        .basic_publish(
+            &tenant.topology().delayed_exchange,
+            &crate::topology::Topology::callback_routing_key(job.outcome),
+            BasicPublishOptions::default(),
+            &body,
+            properties,
+        )
+        .await
+        .map_err(|e| Error::Publish(e.to_string()))?
+        .await
+        .map_err(|e| Error::Publish(format!("Publish was not confirmed: {}", e)))?;
+    if confirm.is_nack() {
+        return Err(Error::Publish("Broker rejected the message".to_string()));
+    }
+    Ok(())
+}
+
+/// Consumes a tenant's callback queue on its own channel so slow callback targets never hold
+/// up notification deliveries. Up to `callback_concurrency` calls run at once.
+/// Only returns on failure; the stream ending is a connection error so the caller restarts it.
+pub async fn run_callback_worker(
+    tenant: &'static TenantPool,
+    consuming: Arc<AtomicBool>,
+) -> Result<Infallible, Error> {
+    let config = init_config()?;
+
+    let channel = tenant.get_channel().await?;
+    channel
+        .basic_qos(config.callback_concurrency, BasicQosOptions::default())
+        .await?;
+
+    let tag = format!("callback_worker.{}", tenant.name());
+    let queue = &tenant.topology().callback_queue;
+    let mut consumer = channel
+        .basic_consume(
+            queue,
+            &tag,
+            BasicConsumeOptions::default(),
+            FieldTable::default(),
+        )
+        .await?;
+    set_consumer_state(&tag, tenant.name(), queue, ConsumerState::Consuming);
+    consuming.store(true, Ordering::SeqCst);
+    info!("🪝 Consuming callbacks from queue: {}", tenant.topology().callback_queue);
+
+    let mut client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(config.callback_timeout_secs))
+        // A redirect could lead to an address the resolver never saw
+        .redirect(reqwest::redirect::Policy::none());
+    if !config.callback_allow_private_targets {
+        client = client.dns_resolver(Arc::new(PublicResolver));
+    }
+    let client = client
+        .build()
+        .map_err(|e| Error::Config(format!("Failed to build HTTP client: {}", e)))?;
+
+    while let Some(delivery) = consumer.next().await {
+        match delivery {
+            Ok(delivery) => {
+                LAST_MESSAGE.beat();
+                let client = client.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_callback_delivery(tenant, &client, delivery).await {
+                        error!("Failed to handle callback: {}", e);
+                    }
+                });
+            }
+            Err(e) => {
+                error!("Error receiving callback: {}", e);
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            }
+        }
+    }
+
+    set_consumer_state(&tag, tenant.name(), queue, ConsumerState::Stopped);
+    Err(Error::Connection(format!(
+        "Callback consumer stream of tenant {} ended",
+        tenant.name()
+    )))
+}
+
+async fn handle_callback_delivery(
+    tenant: &TenantPool,
+    client: &reqwest::Client,
+    delivery: Delivery,
+) -> Result<(), Error> {
+    let delivery = TrackedDelivery::new(&delivery);
+    let job: CallbackJob = match serde_json::from_slice(&delivery.data) {
+        Ok(job) => job,
+        Err(e) => {
+            error!("❌ Error deserializing callback job: {}", e);
+            return crate::worker_utils::dead_letter(tenant, &delivery, "malformed_callback").await;
+        }
+    };
+
+    let span = tracing::info_span!(
+        "callback",
+        notification_id = %job.notification_id,
+        user_id = %job.user_id,
+        delivery_tag = delivery.delivery_tag,
+        attempt = job.attempt + 1,
+    );
+    deliver_callback(tenant, client, &delivery, job)
+        .instrument(span)
+        .await
+}
+
+async fn deliver_callback(
+    tenant: &TenantPool,
+    client: &reqwest::Client,
+    delivery: &TrackedDelivery<'_>,
+    mut job: CallbackJob,
+) -> Result<(), Error> {
+    match post_callback(tenant, client, &job).await {
+        Ok(_) => {
+            info!(
+                "🪝 Callback for notification {} delivered to {}",
+                job.notification_id, job.url
+            );
+            delivery.ack().await?;
+        }
+        Err(e) => {
+            let config = init_config()?;
+            job.attempt += 1;
+            if job.attempt >= config.callback_max_attempts {
+                error!(
+                    "❌ Callback for notification {} failed after {} attempts: {}",
+                    job.notification_id, job.attempt, e
+                );
+                return crate::worker_utils::dead_letter(tenant, delivery, "callback_failed").await;
+            }
+
+            let delay_ms = backoff_ms(config.callback_backoff_base_ms, job.attempt);
+            warn!(
+                "🔄 Callback for notification {} failed (attempt {}/{}), retrying in {} ms: {}",
+                job.notification_id, job.attempt, config.callback_max_attempts, delay_ms, e
+            );
+            // Without a confirmed retry job the failed delivery itself is the retry
+            if let Err(e) = enqueue_callback(tenant, &job, delay_ms).await {
+                delivery.requeue().await?;
+                return Err(e);
+            }
+            delivery.ack().await?;
+        }
+    }
+    Ok(())
+}
+
+async fn post_callback(
+    tenant: &TenantPool,
+    client: &reqwest::Client,
+    job: &CallbackJob,
+) -> Result<(), String> {
+    // Literal addresses bypass the resolver
+    let url = reqwest::Url::parse(&job.url).map_err(|e| e.to_string())?;
+    if let Some(host) = url.host_str()
+        && let Ok(ip) = host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>()
+        && !is_public_address(ip)
+        && !init_config().is_ok_and(|c| c.callback_allow_private_targets)
+    {
+        return Err(format!("Callback target {} is not a public address", ip));
+    }
+    let body = serde_json::to_vec(&CallbackPayload::from(job)).map_err(|e| e.to_string())?;
+    let timestamp = Utc::now().timestamp();
+
+    let mut request = client
+        .post(&job.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(TIMESTAMP_HEADER, timestamp.to_string());
+    if let Some(id) = &job.secret_id {
+        let secret = callback_secret(tenant.name(), id)
+            .ok_or_else(|| format!("Unknown callback secret {}", id))?;
+        request = request.header(SIGNATURE_HEADER, sign(secret, timestamp, &body));
+    }
+
+    let response = request.body(body).send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("Callback target responded with {}", response.status()));
+    }
+    Ok(())
+}
+
+/// Resolves callback hosts, dropping every address a callback must not reach. Checked on each
+/// connection, so a host that starts resolving to an internal address is refused.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|address| is_public_address(address.ip()))
+                .collect();
+            if addresses.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            let addresses: Addrs = Box::new(addresses.into_iter());
+            Ok(addresses)
+        })
+    }
+}
+
+/// Exponential backoff: base, 2×base, 4×base, … capped at one hour.
+fn backoff_ms(base_ms: u64, attempt: u32) -> i32 {
+    let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+    base_ms.saturating_mul(factor).min(60 * 60 * 1000) as i32
+}
@@ -1,26 +1,354 @@
-use lapin::{Connection, ConnectionProperties, Channel};
-use std::sync::Arc;
-use crate::config::Config;
+use lapin::{BasicProperties, Connection, ConnectionProperties, Channel, options::{BasicPublishOptions, ConfirmSelectOptions}};
+use futures_util::FutureExt;
+use lapin::types::{AMQPValue, FieldTable};
+use lapin::uri::AMQPUri;
+use rand::seq::SliceRandom;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{Instrument, error, info, warn};
+use crate::config::{Config, init_config};
+use crate::error::Error;
+use crate::metrics;
+use crate::secret::{RedactedUrl, SecretString};
+use crate::telemetry;
+use crate::tenant::TenantConfig;
+use crate::topology::Topology;
 
+/// In which order the nodes of `RABBITMQ_NODES` are tried on every connect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeOrder {
+    /// As listed, so the first reachable node is preferred.
+    Ordered,
+    /// Shuffled, to spread the connections of several processes over the cluster.
+    Random,
+}
+
+impl FromStr for NodeOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "ordered" => Ok(NodeOrder::Ordered),
+            "random" => Ok(NodeOrder::Random),
+            other => Err(format!(
+                "Unknown RABBITMQ_NODE_ORDER '{}', expected 'ordered' or 'random'",
+                other
+            )),
+        }
+    }
+}
+
+/// A tenant's own broker connection and the names of its exchanges and queues.
+/// The connection is opened, and re-established when lost, the next time a channel is needed,
+/// on the first reachable node of the cluster.
+pub struct TenantPool {
+    name: String,
+    prefetch: u16,
+    amqp_url: SecretString,
+    /// `None` until the first connect succeeded.
+    connection: RwLock<Option<Arc<Connection>>>,
+    /// `host:port` of the node `connection` is open to.
+    active_node: RwLock<String>,
+    // Serializes reconnects so concurrent callers do not open one connection each
+    reconnecting: tokio::sync::Mutex<()>,
+    topology: Topology,
+}
+
+impl TenantPool {
+    /// A pool that connects on first use, so it can be created while the broker is down.
+    pub fn new(amqp_url: &SecretString, tenant: &TenantConfig, topology: Topology) -> Self {
+        Self {
+            name: tenant.name.clone(),
+            prefetch: tenant.prefetch,
+            amqp_url: amqp_url.clone(),
+            connection: RwLock::new(None),
+            active_node: RwLock::new(url_authority(amqp_url.expose_secret()).to_string()),
+            reconnecting: tokio::sync::Mutex::new(()),
+            topology,
+        }
+    }
+
+    pub async fn get_channel(&self) -> Result<Channel, Error> {
+        let connection = self.connection();
+        if let Some(connection) = &connection
+            && connection.status().connected()
+        {
+            return Ok(connection.create_channel().await?);
+        }
+        Ok(self.reconnect(connection.as_ref()).await?.create_channel().await?)
+    }
+
+    /// A channel with publisher confirms enabled, so publishes are only `Ok` once the broker
+    /// has taken responsibility for the message.
+    pub async fn get_publish_channel(&self) -> Result<Channel, Error> {
+        let channel = self.get_channel().await?;
+        channel.confirm_select(ConfirmSelectOptions::default()).await?;
+        Ok(channel)
+    }
+
+    fn connection(&self) -> Option<Arc<Connection>> {
+        self.connection
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Opens the connection, replacing `lost` unless it has not been opened yet.
+    async fn reconnect(&self, lost: Option<&Arc<Connection>>) -> Result<Arc<Connection>, Error> {
+        let _guard = self.reconnecting.lock().await;
+        // Another caller may have connected while we waited
+        if let Some(current) = self.connection()
+            && !lost.is_some_and(|lost| Arc::ptr_eq(&current, lost))
+            && current.status().connected()
+        {
+            return Ok(current);
+        }
+
+        if lost.is_some() {
+            warn!("🔌 Connection of tenant {} lost, reconnecting", self.name);
+        }
+        let (connection, node) = connect(&self.amqp_url, &self.name).await?;
+        let connection = Arc::new(connection);
+        // The broker may have restarted without the topology
+        let channel = connection.create_channel().await?;
+        self.topology.declare(&channel).await?;
+        let _ = channel.close(200, "Topology declared").await;
+
+        *self.connection.write().unwrap_or_else(|e| e.into_inner()) = Some(connection.clone());
+        if lost.is_some() {
+            metrics::RABBITMQ_RECONNECTS.with_label_values(&[&self.name]).inc();
+            info!("🔌 Tenant {} reconnected to {}", self.name, node);
+        } else {
+            info!("🔌 Tenant {} connected to {}", self.name, node);
+        }
+        *self.active_node.write().unwrap_or_else(|e| e.into_inner()) = node;
+        Ok(connection)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn prefetch(&self) -> u16 {
+        self.prefetch
+    }
+
+    pub fn topology(&self) -> &Topology {
+        &self.topology
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection()
+            .is_some_and(|connection| connection.status().connected())
+    }
+
+    /// `host:port` of the node the tenant is connected, or was last connected, to.
+    pub fn active_node(&self) -> String {
+        self.active_node
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+/// Connects to the first reachable node and returns the connection and the node's `host:port`.
+/// Without `RABBITMQ_NODES` the URL's own host is the only node.
+async fn connect(amqp_url: &SecretString, tenant: &str) -> Result<(Connection, String), Error> {
+    let config = init_config()?;
+    let mut nodes = config.rabbitmq_nodes.clone();
+    if config.rabbitmq_node_order == NodeOrder::Random {
+        nodes.shuffle(&mut rand::rng());
+    }
+    if nodes.is_empty() {
+        let node = url_authority(amqp_url.expose_secret()).to_string();
+        return Ok((connect_node(amqp_url, tenant).await?, node));
+    }
+
+    let mut last_error = None;
+    for node in nodes {
+        let url = SecretString::new(url_with_node(amqp_url.expose_secret(), &node));
+        match connect_node(&url, tenant).await {
+            Ok(connection) => return Ok((connection, node)),
+            Err(e) => {
+                warn!("🔌 Broker node {} unreachable for tenant {}: {}", node, tenant, e);
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| Error::Connection("No broker node configured".to_string())))
+}
+
+#[allow(clippy::result_large_err)] // The TLS handshake closure returns lapin's `HandshakeResult`
+async fn connect_node(amqp_url: &SecretString, tenant: &str) -> Result<Connection, Error> {
+    let config = init_config()?;
+    let mut uri: AMQPUri = amqp_url.expose_secret().parse().map_err(|e| {
+        Error::Config(format!(
+            "Invalid broker URL {}: {}",
+            RedactedUrl(amqp_url.expose_secret()),
+            e
+        ))
+    })?;
+    tune(&mut uri, &config);
+    let properties = connection_properties(&config, tenant);
+
+    let connecting = match &config.rabbitmq_tls {
+        Some(tls) => {
+            let connector = tls.connector()?;
+            let server_name = tls.server_name(&uri).to_string();
+            Connection::connector(
+                uri,
+                Box::new(move |uri| crate::tls::handshake(uri, &connector, &server_name)),
+                properties,
+            )
+            .boxed()
+        }
+        None => Connection::connect_uri(uri, properties).boxed(),
+    };
+    // Also bounds the AMQP handshake with a broker that accepted the TCP connection
+    let timeout = Duration::from_secs(config.rabbitmq_connect_timeout_secs);
+    let result = match tokio::time::timeout(timeout, connecting).await {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(_) => Err(format!("timed out after {}s", timeout.as_secs())),
+    };
+    result.map_err(|e| {
+        Error::Connection(format!(
+            "Failed to connect tenant {} to {}: {}",
+            tenant,
+            RedactedUrl(amqp_url.expose_secret()),
+            e
+        ))
+    })
+}
+
+/// Applies the configured heartbeat, limits and TCP connect timeout to `uri`, unless its query
+/// string sets them.
+fn tune(uri: &mut AMQPUri, config: &Config) {
+    let query = &mut uri.query;
+    query.heartbeat.get_or_insert(config.rabbitmq_heartbeat_secs);
+    // lapin raises 0 to the smallest frame size instead of taking the broker's limit
+    if config.rabbitmq_channel_max != 0 {
+        query.channel_max.get_or_insert(config.rabbitmq_channel_max);
+    }
+    if config.rabbitmq_frame_max != 0 {
+        query.frame_max.get_or_insert(config.rabbitmq_frame_max);
+    }
+    query
+        .connection_timeout
+        .get_or_insert(config.rabbitmq_connect_timeout_secs * 1000);
+}
+
+/// Client properties naming the process behind a connection in the broker's management UI.
+fn connection_properties(config: &Config, tenant: &str) -> ConnectionProperties {
+    let binary = binary_name();
+    let host = hostname();
+    let name = config
+        .rabbitmq_connection_name
+        .clone()
+        .unwrap_or_else(|| format!("{}@{}", binary, host));
+    let version = env!("CARGO_PKG_VERSION");
+
+    let mut properties = ConnectionProperties::default()
+        .with_connection_name(format!("{} v{} ({})", name, version, tenant).into());
+    let client_properties: &mut FieldTable = &mut properties.client_properties;
+    // lapin only keeps its own product and version when one of them is missing
+    for (key, value) in [
+        ("product", binary.as_str()),
+        ("version", version),
+        ("hostname", host.as_str()),
+        ("tenant", tenant),
+    ] {
+        client_properties.insert(key.into(), AMQPValue::LongString(value.into()));
+    }
+    properties
+}
+
+/// File name of the running executable.
+fn binary_name() -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| env!("CARGO_PKG_NAME").to_string())
+}
+
+/// `HOSTNAME`, or the kernel's host name when the shell did not export it.
+pub(crate) fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown-host".to_string())
+}
+
+/// Publishes a notification on a channel from `get_publish_channel` and waits for the broker's
+/// confirm, counting the outcome by notification type. The message carries the trace context.
+pub async fn publish_confirmed(
+    channel: &Channel,
+    exchange: &str,
+    routing_key: &str,
+    body: &[u8],
+    properties: BasicProperties,
+    notification_type: &str,
+) -> Result<(), Error> {
+    let span = tracing::info_span!(
+        "publish",
+        otel.name = format!("publish {}", exchange),
+        otel.kind = "producer",
+        messaging.destination.name = exchange,
+        messaging.rabbitmq.destination.routing_key = routing_key,
+    );
+    // The worker continues the trace from the message's `traceparent` header
+    let mut headers = properties.headers().clone().unwrap_or_default();
+    span.in_scope(|| telemetry::inject_context(&mut headers));
+    let properties = properties.with_headers(headers);
+
+    let confirm = match channel
+        .basic_publish(exchange, routing_key, BasicPublishOptions::default(), body, properties)
+        .instrument(span.clone())
+        .await
+    {
+        Ok(confirm) => confirm,
+        Err(e) => {
+            metrics::record_publish(notification_type, false, false);
+            return Err(Error::Publish(e.to_string()));
+        }
+    };
+    let result = match confirm.instrument(span).await {
+        Ok(confirmation) if confirmation.is_nack() => {
+            Err(Error::Publish("Broker rejected the message".to_string()))
+        }
+        Ok(_) => Ok(()),
+        Err(e) => Err(Error::Publish(format!("Publish was not confirmed: {}", e))),
+    };
+    metrics::record_publish(notification_type, true, result.is_ok());
+    result
+}
+
+/// One connection per configured tenant.
 pub struct RabbitMQPool {
-    connection: Arc<Connection>,
+    tenants: Vec<TenantPool>,
+    single_tenant: bool,
 }
 
 impl RabbitMQPool {
-    pub async fn new(amqp_url: &str) -> Result<Self, lapin::Error> {
-        let connection = Connection::connect(amqp_url, ConnectionProperties::default()).await?;
-        Ok(Self {
-            connection: Arc::new(connection),
-        })
+    pub fn tenants(&self) -> &[TenantPool] {
+        &self.tenants
     }
 
-    pub async fn get_channel(&self) -> Result<Channel, lapin::Error> {
-        self.connection.create_channel().await
+    pub fn tenant(&self, name: &str) -> Option<&TenantPool> {
+        self.tenants.iter().find(|t| t.name == name)
     }
 
-    // pub fn is_connected(&self) -> bool {
-    //     self.connection.status().connected()
-    // }
+    /// Tenant serving a caller. Without `TENANTS` every caller shares the default tenant;
+    /// otherwise unauthenticated callers get the first tenant and unknown tenants get `None`.
+    pub fn tenant_for(&self, tenant: Option<&str>) -> Option<&TenantPool> {
+        match tenant {
+            Some(name) if !self.single_tenant => self.tenant(name),
+            _ => self.tenants.first(),
+        }
+    }
 }
 
 // Singleton global
@@ -28,13 +356,104 @@ lazy_static::lazy_static! {
     pub static ref RABBITMQ_POOL: tokio::sync::OnceCell<RabbitMQPool> = tokio::sync::OnceCell::new();
 }
 
-pub async fn init_rabbitmq_pool() -> Result<(), Box<dyn std::error::Error>> {
-    let config = Config::from_env()?;
-    let pool = RabbitMQPool::new(&config.rabbitmq_url).await?;
-    RABBITMQ_POOL.set(pool).map_err(|_| "Failed to set RabbitMQ pool")?;
+/// Creates every tenant's pool without connecting it. The server accepts notifications into
+/// its outbox while the broker is down; connections open when a channel is first needed.
+pub fn init_lazy_rabbitmq_pool() -> Result<&'static RabbitMQPool, Error> {
+    let config = init_config()?;
+
+    let tenants = config
+        .tenants
+        .iter()
+        .map(|tenant| {
+            let topology = Topology::for_tenant(&config, tenant);
+            TenantPool::new(&tenant.amqp_url(&config.rabbitmq_url), tenant, topology)
+        })
+        .collect();
+    let pool = RabbitMQPool {
+        tenants,
+        single_tenant: config.is_single_tenant(),
+    };
+    RABBITMQ_POOL
+        .set(pool)
+        .map_err(|_| Error::Connection("Failed to set RabbitMQ pool".to_string()))?;
+    get_rabbitmq_pool()
+}
+
+/// Creates every tenant's pool and connects it, failing when a tenant's broker is unreachable.
+pub async fn init_rabbitmq_pool() -> Result<(), Error> {
+    for tenant in init_lazy_rabbitmq_pool()?.tenants() {
+        connect_tenant(tenant).await?;
+    }
     Ok(())
 }
 
-pub fn get_rabbitmq_pool() -> Result<&'static RabbitMQPool, &'static str> {
-    RABBITMQ_POOL.get().ok_or("RabbitMQ pool not initialized")
+/// Connects a tenant, declaring its topology on the way.
+pub async fn connect_tenant(tenant: &TenantPool) -> Result<(), Error> {
+    // Both the server and the worker declare the topology so either can start first
+    let channel = tenant.get_channel().await?;
+    let _ = channel.close(200, "Topology declared").await;
+    Ok(())
+}
+
+/// Server-side task: connects every tenant once at startup. A tenant whose broker is down is
+/// connected later by whichever task needs a channel first, such as the outbox relay.
+pub async fn connect_rabbitmq_pool_task() {
+    let pool = match get_rabbitmq_pool() {
+        Ok(pool) => pool,
+        Err(e) => {
+            error!("Cannot connect to RabbitMQ: {}", e);
+            return;
+        }
+    };
+    for tenant in pool.tenants() {
+        if let Err(e) = connect_tenant(tenant).await {
+            warn!(
+                "🔌 RabbitMQ unreachable for tenant {}, notifications wait in the outbox: {}",
+                tenant.name(),
+                e
+            );
+        }
+    }
+}
+
+/// `host:port` of an AMQP URL, without the credentials.
+pub fn url_authority(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?']).next().unwrap_or_default();
+    authority.rsplit_once('@').map_or(authority, |(_, host)| host)
+}
+
+/// The URL with its host and port replaced by `node`. A node without a port keeps the URL's.
+pub fn url_with_node(url: &str, node: &str) -> String {
+    let (scheme, rest) = url.split_once("://").unwrap_or(("amqp", url));
+    let authority_len = rest.find(['/', '?']).unwrap_or(rest.len());
+    let (authority, tail) = rest.split_at(authority_len);
+    let (credentials, host) = match authority.rsplit_once('@') {
+        Some((credentials, host)) => (Some(credentials), host),
+        None => (None, authority),
+    };
+    let node = match (node_port(node), node_port(host)) {
+        (None, Some(port)) => format!("{}:{}", node, port),
+        _ => node.to_string(),
+    };
+    match credentials {
+        Some(credentials) => format!("{}://{}@{}{}", scheme, credentials, node, tail),
+        None => format!("{}://{}{}", scheme, node, tail),
+    }
+}
+
+/// The port of `host:port` or `[ipv6]:port`, if any.
+pub fn node_port(node: &str) -> Option<&str> {
+    let (host, port) = node.rsplit_once(':')?;
+    if host.starts_with('[') && !host.ends_with(']') {
+        // A bare IPv6 address
+        return None;
+    }
+    Some(port)
+}
+
+pub fn get_rabbitmq_pool() -> Result<&'static RabbitMQPool, Error> {
+    RABBITMQ_POOL
+        .get()
+        .ok_or_else(|| Error::Connection("RabbitMQ pool not initialized".to_string()))
 }
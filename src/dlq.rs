@@ -0,0 +1,716 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use lapin::message::Delivery;
+use lapin::{
+    BasicProperties, Channel,
+    options::*,
+    types::{AMQPValue, FieldTable},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::config::get_config;
+use crate::connection::TenantPool;
+use crate::error::{Error, FieldError};
+use crate::metrics;
+use crate::topology::{Topology, priority_of_value};
+
+/// Headers describing how a message failed and was dead-lettered, dropped when it is replayed.
+const DEATH_HEADERS: [&str; 6] = [
+    "x-death",
+    "x-failure-reason",
+    "x-original-routing-key",
+    "x-delay",
+    "x-delivery-count",
+    "x-retry-count",
+];
+/// Prefixes of RabbitMQ's summary headers, e.g. `x-first-death-reason`.
+const DEATH_HEADER_PREFIXES: [&str; 2] = ["x-first-death-", "x-last-death-"];
+/// Body fields shown as `***`; replays still send the original value.
+const REDACTED_FIELDS: [&str; 1] = ["secret"];
+
+/// One entry of the `x-death` header RabbitMQ adds each time it dead-letters a message.
+#[derive(Debug, Serialize, Clone, PartialEq, ToSchema)]
+pub struct XDeath {
+    /// Queue the message died in.
+    pub queue: String,
+    /// `rejected`, `expired`, `maxlen` or `delivery_limit`.
+    pub reason: String,
+    pub count: i64,
+    pub exchange: String,
+    pub routing_keys: Vec<String>,
+    pub time: Option<DateTime<Utc>>,
+}
+
+/// A message waiting in a tenant's dead letter queue.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct DlqMessage {
+    /// The notification id, or `sha256:<hash of the body>` when the body has none. Messages
+    /// with the same id are replayed and purged together.
+    pub id: String,
+    pub tenant: String,
+    /// The worker's failure reason, or the broker's reason of the latest `x-death` entry.
+    pub reason: Option<String>,
+    /// Routing key the message had before it was dead-lettered; replays publish with it.
+    pub routing_key: String,
+    pub dead_lettered_at: Option<DateTime<Utc>>,
+    /// Latest first.
+    pub x_death: Vec<XDeath>,
+    /// Body size in bytes.
+    pub size: usize,
+    /// The body, when it is JSON. Only included when a single message is shown.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub headers: Option<Map<String, Value>>,
+}
+
+impl DlqMessage {
+    pub fn new(tenant: &str, routing_key: &str, body: &[u8], properties: &BasicProperties) -> Self {
+        let headers = properties.headers().clone().unwrap_or_default();
+        let header = |name: &str| match headers.inner().get(name) {
+            Some(AMQPValue::LongString(value)) => Some(value.to_string()),
+            Some(AMQPValue::ShortString(value)) => Some(value.to_string()),
+            _ => None,
+        };
+        let x_death = parse_x_death(&headers);
+        let latest = x_death.first();
+
+        let mut body_value: Option<Value> = serde_json::from_slice(body).ok();
+        if let Some(Value::Object(fields)) = body_value.as_mut() {
+            for field in REDACTED_FIELDS {
+                if let Some(value) = fields.get_mut(field) {
+                    *value = Value::String("***".to_string());
+                }
+            }
+        }
+
+        DlqMessage {
+            id: message_id(body),
+            tenant: tenant.to_string(),
+            reason: header("x-failure-reason").or_else(|| latest.map(|d| d.reason.clone())),
+            routing_key: header("x-original-routing-key")
+                .or_else(|| latest.and_then(|d| d.routing_keys.first().cloned()))
+                .unwrap_or_else(|| routing_key.to_string()),
+            dead_lettered_at: latest.and_then(|d| d.time).or_else(|| {
+                properties
+                    .timestamp()
+                    .and_then(|t| DateTime::from_timestamp(t as i64, 0))
+            }),
+            size: body.len(),
+            body: body_value,
+            headers: match amqp_to_json(&AMQPValue::FieldTable(headers.clone())) {
+                Value::Object(map) => Some(map),
+                _ => None,
+            },
+            x_death,
+        }
+    }
+
+    /// The message without its body and headers, as listed.
+    pub fn summary(self) -> Self {
+        DlqMessage {
+            body: None,
+            headers: None,
+            ..self
+        }
+    }
+}
+
+/// Identifies a dead-lettered message across scans: the body's `id`, or a hash of the body.
+pub fn message_id(body: &[u8]) -> String {
+    let id = serde_json::from_slice::<Value>(body)
+        .ok()
+        .and_then(|v| v.get("id").and_then(|id| id.as_str()).map(str::to_string));
+    id.unwrap_or_else(|| {
+        let digest = hex::encode(Sha256::digest(body));
+        format!("sha256:{}", &digest[..16])
+    })
+}
+
+/// Reads the `x-death` header, latest death first as RabbitMQ orders it.
+pub fn parse_x_death(headers: &FieldTable) -> Vec<XDeath> {
+    let Some(AMQPValue::FieldArray(deaths)) = headers.inner().get("x-death") else {
+        return Vec::new();
+    };
+    deaths
+        .as_slice()
+        .iter()
+        .filter_map(|death| match death {
+            AMQPValue::FieldTable(death) => Some(death),
+            _ => None,
+        })
+        .map(|death| {
+            let field =
+                |name: &str| amqp_to_json(death.inner().get(name).unwrap_or(&AMQPValue::Void));
+            let text = |name: &str| field(name).as_str().unwrap_or_default().to_string();
+            XDeath {
+                queue: text("queue"),
+                reason: text("reason"),
+                count: field("count").as_i64().unwrap_or_default(),
+                exchange: text("exchange"),
+                routing_keys: match field("routing-keys") {
+                    Value::Array(keys) => keys
+                        .iter()
+                        .filter_map(|k| k.as_str().map(str::to_string))
+                        .collect(),
+                    _ => Vec::new(),
+                },
+                time: match death.inner().get("time") {
+                    Some(AMQPValue::Timestamp(t)) => DateTime::from_timestamp(*t as i64, 0),
+                    _ => None,
+                },
+            }
+        })
+        .collect()
+}
+
+/// Converts a header value to JSON. Timestamps become RFC 3339 strings and byte arrays hex.
+pub fn amqp_to_json(value: &AMQPValue) -> Value {
+    match value {
+        AMQPValue::Boolean(b) => Value::Bool(*b),
+        AMQPValue::ShortShortInt(n) => Value::from(*n),
+        AMQPValue::ShortShortUInt(n) => Value::from(*n),
+        AMQPValue::ShortInt(n) => Value::from(*n),
+        AMQPValue::ShortUInt(n) => Value::from(*n),
+        AMQPValue::LongInt(n) => Value::from(*n),
+        AMQPValue::LongUInt(n) => Value::from(*n),
+        AMQPValue::LongLongInt(n) => Value::from(*n),
+        AMQPValue::Float(n) => Value::from(*n),
+        AMQPValue::Double(n) => Value::from(*n),
+        AMQPValue::DecimalValue(d) => Value::from(d.value as f64 / 10f64.powi(d.scale as i32)),
+        AMQPValue::ShortString(s) => Value::String(s.to_string()),
+        AMQPValue::LongString(s) => Value::String(s.to_string()),
+        AMQPValue::FieldArray(values) => {
+            Value::Array(values.as_slice().iter().map(amqp_to_json).collect())
+        }
+        AMQPValue::Timestamp(t) => DateTime::from_timestamp(*t as i64, 0)
+            .map(|t| Value::String(t.to_rfc3339()))
+            .unwrap_or(Value::from(*t)),
+        AMQPValue::FieldTable(table) => Value::Object(
+            table
+                .inner()
+                .iter()
+                .map(|(k, v)| (k.to_string(), amqp_to_json(v)))
+                .collect(),
+        ),
+        AMQPValue::ByteArray(bytes) => Value::String(hex::encode(bytes.as_slice())),
+        AMQPValue::Void => Value::Null,
+    }
+}
+
+/// Applies a JSON merge patch (RFC 7386): objects are merged, `null` removes a field and
+/// anything else replaces the target.
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(fields) = target else {
+        return;
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            fields.remove(key);
+        } else {
+            merge_patch(fields.entry(key.as_str()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+/// Selects dead-lettered messages. Every given criterion must match.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, IntoParams, ToSchema)]
+#[into_params(parameter_in = Query)]
+pub struct DlqFilter {
+    /// Tenant whose dead letter queue is read; only the caller's own tenant is allowed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// Failure reason, e.g. `callback_failed` or `expired`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Queue named in the message's `x-death` entries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue: Option<String>,
+}
+
+impl DlqFilter {
+    pub fn matches(&self, message: &DlqMessage) -> bool {
+        self.reason
+            .as_ref()
+            .is_none_or(|reason| message.reason.as_ref() == Some(reason))
+            && self
+                .queue
+                .as_ref()
+                .is_none_or(|queue| message.x_death.iter().any(|d| &d.queue == queue))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.reason.is_none() && self.queue.is_none()
+    }
+}
+
+/// Messages of a dead letter queue matching a filter.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DlqListing {
+    pub tenant: String,
+    /// Messages in the queue, matching or not.
+    pub total: u32,
+    /// Messages read, at most `DLQ_SCAN_LIMIT`; later messages are not listed.
+    pub scanned: usize,
+    pub messages: Vec<DlqMessage>,
+}
+
+/// Outcome of a replay or purge.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DlqActionResult {
+    pub action: AuditAction,
+    pub tenant: String,
+    /// Ids of the messages replayed or purged.
+    pub ids: Vec<String>,
+    /// Messages replayed or purged.
+    pub count: usize,
+    /// Requested ids not found among the scanned messages.
+    pub missing: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Replay,
+    Purge,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Replay => "replay",
+            AuditAction::Purge => "purge",
+        }
+    }
+}
+
+/// Who replayed or purged which dead-lettered messages.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct AuditRecord {
+    pub at: DateTime<Utc>,
+    /// API key id or JWT subject of the caller.
+    pub actor: String,
+    pub tenant: String,
+    pub action: AuditAction,
+    pub ids: Vec<String>,
+    /// The merge patch applied to replayed bodies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub patch: Option<Value>,
+    /// The filter of a purge.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<DlqFilter>,
+}
+
+/// Audit records appended as JSON lines, so earlier records are never rewritten.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        AuditLog { path: path.into() }
+    }
+
+    pub fn append(&self, record: &AuditRecord) -> Result<(), Error> {
+        if let Some(parent) = self.path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                Error::Store(format!("Failed to create {}: {}", parent.display(), e))
+            })?;
+        }
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(&line))
+            .map_err(|e| Error::Store(format!("Failed to write {}: {}", self.path.display(), e)))
+    }
+
+    /// The latest `limit` records of a tenant, newest first.
+    pub fn recent(&self, tenant: &str, limit: usize) -> Result<Vec<AuditRecord>, Error> {
+        let contents = match std::fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(Error::Store(format!(
+                    "Failed to read {}: {}",
+                    self.path.display(),
+                    e
+                )));
+            }
+        };
+        contents
+            .lines()
+            .rev()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str::<AuditRecord>(line).map_err(|e| {
+                    Error::Store(format!("Failed to parse {}: {}", self.path.display(), e))
+                })
+            })
+            .filter(|record| record.as_ref().map_or(true, |r| r.tenant == tenant))
+            .take(limit)
+            .collect()
+    }
+}
+
+pub fn audit_log() -> Result<AuditLog, Error> {
+    Ok(AuditLog::new(&get_config()?.dlq_audit_path))
+}
+
+// Singleton global
+lazy_static::lazy_static! {
+    // One scan at a time, since a scan holds the messages it read until it finishes
+    static ref DLQ_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::new(());
+}
+
+/// Messages read from a dead letter queue and held unacknowledged on their own channel.
+/// Closing the channel puts every message not acknowledged back in the queue.
+struct Scan {
+    channel: Channel,
+    total: u32,
+    messages: Vec<(DlqMessage, Delivery)>,
+}
+
+impl Scan {
+    async fn read(tenant: &TenantPool) -> Result<Self, Error> {
+        let limit = get_config()?.dlq_scan_limit;
+        let channel = tenant.get_channel().await?;
+        let queue = &tenant.topology().dead_letter_queue;
+        let mut scan = Scan {
+            channel,
+            total: 0,
+            messages: Vec::new(),
+        };
+        while scan.messages.len() < limit {
+            let Some(message) = scan
+                .channel
+                .basic_get(queue, BasicGetOptions { no_ack: false })
+                .await?
+            else {
+                break;
+            };
+            if scan.messages.is_empty() {
+                scan.total = message.message_count + 1;
+            }
+            let delivery = message.delivery;
+            let dlq_message = DlqMessage::new(
+                tenant.name(),
+                delivery.routing_key.as_str(),
+                &delivery.data,
+                &delivery.properties,
+            );
+            scan.messages.push((dlq_message, delivery));
+        }
+        Ok(scan)
+    }
+
+    async fn finish(self) {
+        if let Err(e) = self.channel.close(200, "DLQ scan finished").await {
+            warn!("🗑️ Failed to close DLQ scan channel: {}", e);
+        }
+    }
+}
+
+/// Dead-lettered messages matching `filter`, oldest first.
+pub async fn list(
+    tenant: &TenantPool,
+    filter: &DlqFilter,
+    limit: usize,
+) -> Result<DlqListing, Error> {
+    let _guard = DLQ_LOCK.lock().await;
+    let scan = Scan::read(tenant).await?;
+    let listing = DlqListing {
+        tenant: tenant.name().to_string(),
+        total: scan.total,
+        scanned: scan.messages.len(),
+        messages: scan
+            .messages
+            .iter()
+            .filter(|(message, _)| filter.matches(message))
+            .take(limit)
+            .map(|(message, _)| message.clone().summary())
+            .collect(),
+    };
+    scan.finish().await;
+    Ok(listing)
+}
+
+/// A dead-lettered message with its body and headers.
+pub async fn show(tenant: &TenantPool, id: &str) -> Result<Option<DlqMessage>, Error> {
+    let _guard = DLQ_LOCK.lock().await;
+    let scan = Scan::read(tenant).await?;
+    let message = scan
+        .messages
+        .iter()
+        .find(|(message, _)| message.id == id)
+        .map(|(message, _)| message.clone());
+    scan.finish().await;
+    Ok(message)
+}
+
+/// Publishes the messages with the given ids back to the tenant's delayed exchange, their
+/// bodies merged with `patch` first, and removes them from the dead letter queue.
+pub async fn replay(
+    tenant: &TenantPool,
+    ids: &[String],
+    patch: Option<&Value>,
+    actor: &str,
+) -> Result<DlqActionResult, Error> {
+    if ids.is_empty() {
+        return Err(Error::Validation(vec![FieldError::new(
+            "ids",
+            "give the ids of the messages to replay",
+        )]));
+    }
+    if patch.is_some_and(|patch| !patch.is_object()) {
+        return Err(Error::Validation(vec![FieldError::new(
+            "patch",
+            "must be a JSON object",
+        )]));
+    }
+    let _guard = DLQ_LOCK.lock().await;
+    let scan = Scan::read(tenant).await?;
+    let mut replayed = Vec::new();
+    let result = replay_scanned(tenant, &scan, ids, patch, actor, &mut replayed).await;
+    scan.finish().await;
+
+    // Replayed messages are gone from the queue even if a later one failed
+    if !replayed.is_empty() {
+        record(&AuditRecord {
+            at: Utc::now(),
+            actor: actor.to_string(),
+            tenant: tenant.name().to_string(),
+            action: AuditAction::Replay,
+            ids: replayed.clone(),
+            patch: patch.cloned(),
+            filter: None,
+        })?;
+    }
+    result?;
+    Ok(action_result(AuditAction::Replay, tenant, replayed, ids))
+}
+
+async fn replay_scanned(
+    tenant: &TenantPool,
+    scan: &Scan,
+    ids: &[String],
+    patch: Option<&Value>,
+    actor: &str,
+    replayed: &mut Vec<String>,
+) -> Result<(), Error> {
+    let selected: Vec<&(DlqMessage, Delivery)> = scan
+        .messages
+        .iter()
+        .filter(|(message, _)| ids.contains(&message.id))
+        .collect();
+    // Patches only apply to JSON bodies; check them all before anything is published
+    if patch.is_some()
+        && let Some((message, _)) = selected
+            .iter()
+            .find(|(_, delivery)| serde_json::from_slice::<Value>(&delivery.data).is_err())
+    {
+        return Err(Error::Validation(vec![FieldError::new(
+            "patch",
+            format!("message {} does not have a JSON body", message.id),
+        )]));
+    }
+
+    let channel = tenant.get_publish_channel().await?;
+    for (message, delivery) in selected {
+        let (body, routing_key, priority) = match patch {
+            Some(patch) => {
+                let mut body: Value = serde_json::from_slice(&delivery.data)?;
+                merge_patch(&mut body, patch);
+                // A patched notification may now belong to another queue
+                let routing_key = if body.get("notification_type").is_some() {
+                    Topology::routing_key_for_value(&body)
+                } else {
+                    message.routing_key.clone()
+                };
+                let priority = priority_of_value(&body).amqp_priority();
+                (serde_json::to_vec(&body)?, routing_key, Some(priority))
+            }
+            None => (
+                delivery.data.clone(),
+                message.routing_key.clone(),
+                delivery.properties.priority().as_ref().copied(),
+            ),
+        };
+        let mut properties =
+            BasicProperties::default().with_headers(replay_headers(&delivery.properties, actor));
+        if let Some(priority) = priority {
+            properties = properties.with_priority(priority);
+        }
+
+        let confirm = channel
+            .basic_publish(
+                &tenant.topology().delayed_exchange,
+                &routing_key,
+                BasicPublishOptions::default(),
+                &body,
+                properties,
+            )
+            .await
+            .map_err(|e| Error::Publish(e.to_string()))?
+            .await
+            .map_err(|e| Error::Publish(format!("Publish was not confirmed: {}", e)))?;
+        if confirm.is_nack() {
+            return Err(Error::Publish("Broker rejected the message".to_string()));
+        }
+        // Removed from the dead letter queue only once the replay is safe with the broker
+        delivery.ack(BasicAckOptions::default()).await?;
+        info!(
+            "♻️ Replayed dead-lettered message {} of tenant {} to {}",
+            message.id,
+            tenant.name(),
+            routing_key
+        );
+        replayed.push(message.id.clone());
+    }
+    let _ = channel.close(200, "DLQ replay finished").await;
+    Ok(())
+}
+
+/// Headers of a replayed message: the original ones without the dead-lettering details,
+/// plus who replayed it and when.
+fn replay_headers(properties: &BasicProperties, actor: &str) -> FieldTable {
+    let headers = properties.headers().clone().unwrap_or_default();
+    let mut replay_headers: FieldTable = headers
+        .inner()
+        .iter()
+        .filter(|(key, _)| {
+            let key = key.as_str();
+            !DEATH_HEADERS.contains(&key)
+                && !DEATH_HEADER_PREFIXES
+                    .iter()
+                    .any(|prefix| key.starts_with(prefix))
+        })
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect::<BTreeMap<_, _>>()
+        .into();
+    let replays = match headers.inner().get("x-replay-count") {
+        Some(AMQPValue::LongInt(n)) => *n,
+        _ => 0,
+    };
+    replay_headers.insert("x-replay-count".into(), AMQPValue::LongInt(replays + 1));
+    replay_headers.insert("x-replayed-by".into(), AMQPValue::LongString(actor.into()));
+    replay_headers.insert(
+        "x-replayed-at".into(),
+        AMQPValue::LongString(Utc::now().to_rfc3339().into()),
+    );
+    replay_headers
+}
+
+/// Removes the messages with the given ids, or matching `filter`, from the dead letter
+/// queue. An empty filter without ids only purges when `all` is set.
+pub async fn purge(
+    tenant: &TenantPool,
+    ids: &[String],
+    filter: &DlqFilter,
+    all: bool,
+    actor: &str,
+) -> Result<DlqActionResult, Error> {
+    if ids.is_empty() && filter.is_empty() && !all {
+        return Err(Error::Validation(vec![FieldError::new(
+            "filter",
+            "give ids, a reason or a queue, or set all to purge every message",
+        )]));
+    }
+    let _guard = DLQ_LOCK.lock().await;
+    let scan = Scan::read(tenant).await?;
+    let mut purged = Vec::new();
+    let mut result = Ok(());
+    for (message, delivery) in &scan.messages {
+        if (!ids.is_empty() && !ids.contains(&message.id)) || !filter.matches(message) {
+            continue;
+        }
+        if let Err(e) = delivery.ack(BasicAckOptions::default()).await {
+            result = Err(Error::from(e));
+            break;
+        }
+        purged.push(message.id.clone());
+    }
+    scan.finish().await;
+
+    // Acknowledged messages are gone even if a later one failed, so they are recorded
+    if !purged.is_empty() {
+        warn!(
+            "🗑️ Purged {} dead-lettered messages of tenant {}",
+            purged.len(),
+            tenant.name()
+        );
+    }
+    record(&AuditRecord {
+        at: Utc::now(),
+        actor: actor.to_string(),
+        tenant: tenant.name().to_string(),
+        action: AuditAction::Purge,
+        ids: purged.clone(),
+        patch: None,
+        filter: Some(filter.clone()),
+    })?;
+    result?;
+    Ok(action_result(AuditAction::Purge, tenant, purged, ids))
+}
+
+fn action_result(
+    action: AuditAction,
+    tenant: &TenantPool,
+    done: Vec<String>,
+    requested: &[String],
+) -> DlqActionResult {
+    let missing = requested
+        .iter()
+        .filter(|id| !done.contains(id))
+        .cloned()
+        .collect();
+    let mut ids = done.clone();
+    let mut seen = HashSet::new();
+    ids.retain(|id| seen.insert(id.clone()));
+    DlqActionResult {
+        action,
+        tenant: tenant.name().to_string(),
+        count: done.len(),
+        ids,
+        missing,
+    }
+}
+
+fn record(record: &AuditRecord) -> Result<(), Error> {
+    metrics::DLQ_ACTIONS
+        .with_label_values(&[record.action.as_str()])
+        .inc_by(record.ids.len() as u64);
+    info!(
+        "🧾 {} {} {} dead-lettered messages of tenant {}: {}",
+        record.actor,
+        match record.action {
+            AuditAction::Replay => "replayed",
+            AuditAction::Purge => "purged",
+        },
+        record.ids.len(),
+        record.tenant,
+        record.ids.join(", ")
+    );
+    audit_log()?.append(record)
+}
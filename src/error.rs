@@ -0,0 +1,194 @@
+use actix_web::{
+    HttpRequest, HttpResponse, ResponseError,
+    error::{JsonPayloadError, PathError, QueryPayloadError},
+    http::StatusCode,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// A problem with one field of a request.
+#[derive(Debug, Serialize, Clone, PartialEq, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        FieldError {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Crate-wide error type. Maps onto an HTTP status for the API and tells the worker whether a
+/// failed delivery is worth retrying.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Configuration error: {0}")]
+    Config(String),
+    /// The broker is unreachable, or a connection or channel could not be used.
+    #[error("RabbitMQ connection error: {0}")]
+    Connection(String),
+    #[error("Failed to publish message: {0}")]
+    Publish(String),
+    /// The outbox holds its maximum of notifications waiting for the broker.
+    #[error("Outbox is full with {0} notifications waiting for the broker, try again later")]
+    OutboxFull(usize),
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+    /// A local store (inbox, scheduled notifications, ...) could not be read or written.
+    #[error("Store error: {0}")]
+    Store(String),
+    /// The request was well-formed but some fields are invalid.
+    #[error("Request validation failed{}", format_field_errors(.0))]
+    Validation(Vec<FieldError>),
+    #[error("Timed out after {0:?}")]
+    Timeout(std::time::Duration),
+    /// The body, query string or path could not be parsed.
+    #[error("{message}")]
+    BadRequest { code: &'static str, message: String },
+    #[error("{0}")]
+    PayloadTooLarge(String),
+    #[error("{0}")]
+    UnsupportedMediaType(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    NotFound(String),
+}
+
+impl From<lapin::Error> for Error {
+    fn from(error: lapin::Error) -> Self {
+        Error::Connection(error.to_string())
+    }
+}
+
+fn format_field_errors(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(|e| format!("; {}: {}", e.field, e.message))
+        .collect()
+}
+
+impl Error {
+    /// Stable, machine-readable error code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Config(_) => "config_error",
+            Error::Connection(_) => "broker_unavailable",
+            Error::Publish(_) => "publish_failed",
+            Error::OutboxFull(_) => "outbox_full",
+            Error::Serialization(_) => "serialization_error",
+            Error::Store(_) => "store_error",
+            Error::Validation(_) => "validation_failed",
+            Error::Timeout(_) => "timeout",
+            Error::BadRequest { code, .. } => code,
+            Error::PayloadTooLarge(_) => "payload_too_large",
+            Error::UnsupportedMediaType(_) => "unsupported_media_type",
+            Error::Unauthorized(_) => "unauthorized",
+            Error::Forbidden(_) => "forbidden",
+            Error::NotFound(_) => "not_found",
+        }
+    }
+
+    /// Whether trying again later may succeed. The worker requeues deliveries that failed with
+    /// a retryable error and dead-letters the rest, which would fail the same way every time.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Error::Connection(_)
+                | Error::Publish(_)
+                | Error::OutboxFull(_)
+                | Error::Store(_)
+                | Error::Timeout(_)
+        )
+    }
+}
+
+/// Body of every error response.
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody<'a> {
+    /// Stable, machine-readable error code, e.g. `validation_failed`.
+    pub code: &'static str,
+    pub message: String,
+    /// Per-field problems; empty unless the code is `validation_failed`.
+    pub field_errors: &'a [FieldError],
+}
+
+impl ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Error::Config(_) | Error::Serialization(_) | Error::Store(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            Error::Connection(_) | Error::Publish(_) | Error::OutboxFull(_) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            Error::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            Error::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::BadRequest { .. } => StatusCode::BAD_REQUEST,
+            Error::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Error::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Error::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Error::Forbidden(_) => StatusCode::FORBIDDEN,
+            Error::NotFound(_) => StatusCode::NOT_FOUND,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let (message, field_errors) = match self {
+            Error::Validation(errors) => {
+                ("Request validation failed".to_string(), errors.as_slice())
+            }
+            other => (other.to_string(), &[][..]),
+        };
+        HttpResponse::build(self.status_code()).json(ErrorBody {
+            code: self.code(),
+            message,
+            field_errors,
+        })
+    }
+}
+
+/// `JsonConfig` error handler: malformed or oversized bodies get the standard error body.
+pub fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    match err {
+        JsonPayloadError::OverflowKnownLength { length, limit } => Error::PayloadTooLarge(format!(
+            "Body of {} bytes exceeds the {} byte limit",
+            length, limit
+        )),
+        JsonPayloadError::Overflow { limit } => {
+            Error::PayloadTooLarge(format!("Body exceeds the {} byte limit", limit))
+        }
+        JsonPayloadError::ContentType => {
+            Error::UnsupportedMediaType("Content-Type must be application/json".to_string())
+        }
+        other => Error::BadRequest {
+            code: "invalid_json",
+            message: other.to_string(),
+        },
+    }
+    .into()
+}
+
+/// `QueryConfig` error handler.
+pub fn query_error_handler(err: QueryPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    Error::BadRequest {
+        code: "invalid_query",
+        message: err.to_string(),
+    }
+    .into()
+}
+
+/// `PathConfig` error handler, e.g. for a notification id that is not a UUID.
+pub fn path_error_handler(err: PathError, _req: &HttpRequest) -> actix_web::Error {
+    Error::BadRequest {
+        code: "invalid_path",
+        message: err.to_string(),
+    }
+    .into()
+}
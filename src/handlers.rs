@@ -1,133 +1,214 @@
-use actix_web::{post, web, HttpResponse, Result as ActixResult};
-use crate::models::{Notification, ScheduledNotification, ScheduleNotificationRequest, ScheduleAtRequest, SCHEDULED_NOTIFICATIONS};
-use crate::connection::get_rabbitmq_pool;
-use lapin::{options::*, types::{FieldTable, AMQPValue}, BasicProperties, Channel};
-use serde_json::{to_vec, json};
+use actix_web::{get, post, web, HttpResponse};
+use crate::models::{Notification, NotificationAccepted, ScheduledNotification, ScheduleNotificationRequest, ScheduleAtRequest, SCHEDULED_NOTIFICATIONS};
+use crate::connection::{TenantPool, get_rabbitmq_pool};
+use crate::outbox::{self, OutboxEntry};
+use crate::auth::AuthContext;
+use crate::tenant::TenantConfig;
+use crate::status::{self, DeliveryState, DeliveryStatus, StatusEvent};
 use uuid::Uuid;
-use chrono::{Utc};
-use tracing::{info, error};
-
-// Reusable helper function to publish notifications
-async fn publish_notification(
-    channel: &Channel,
-    notification: &Notification,
-    delay_ms: i32
-) -> Result<(), String> {
-    let body = to_vec(notification).map_err(|e| format!("Serialization error: {}", e))?;
-
-    let properties = if delay_ms > 0 {
-        BasicProperties::default().with_headers({
-            let mut table = FieldTable::default();
-            table.insert("x-delay".into(), AMQPValue::LongInt(delay_ms));
-            table
-        })
-    } else {
-        BasicProperties::default()
-    };
-
-    channel
-        .basic_publish(
-            "delayed_exchange",
-            "main",
-            BasicPublishOptions::default(),
-            &body,
-            properties,
-        )
-        .await
-        .map_err(|e| format!("Failed to publish message: {}", e))?
-        .await
-        .map_err(|e| format!("Failed to confirm message: {}", e))?;
+use chrono::{Duration as ChronoDuration, Utc};
+use crate::config::get_config;
+use crate::error::{Error, ErrorBody};
+use crate::metrics;
+use crate::telemetry::record_notification;
+use crate::topology::priority_of_value;
+use crate::validation::{validate_callback_secret, validate_callback_target, validate_delayed, validate_notification, validate_schedule_at, validate_schedule_request};
+use tracing::{Instrument, info, error, warn};
+
+// Fills in per-client defaults, such as the API key's webhook, the request did not set
+fn apply_client_defaults(notification: &mut Notification, auth: Option<&AuthContext>) {
+    if let Some(auth) = auth
+        && notification.callback_url.is_none()
+    {
+        notification.callback_url = auth.callback_url.clone();
+        notification.callback_secret_id = auth.callback_secret_id.clone();
+    }
+}
 
-    Ok(())
+// Rejects callers that may not target this user (e.g. a gateway token for another tenant)
+pub(crate) fn ensure_user_permitted(auth: Option<&AuthContext>, user_id: &str) -> Result<(), Error> {
+    let Some(auth) = auth else {
+        return Ok(());
+    };
+    if auth.permits_user(user_id) {
+        return Ok(());
+    }
+    warn!("🔒 {} (tenant {}) may not notify user {}", auth.key_id, auth.tenant, user_id);
+    Err(Error::Forbidden(format!("User {} is outside the caller's tenant", user_id)))
 }
 
-#[post("/notify")]
-pub async fn send_notification(payload: web::Json<Notification>) -> ActixResult<HttpResponse> {
-    let pool = get_rabbitmq_pool().map_err(|e| {
-        error!("RabbitMQ pool error: {}", e);
-        actix_web::error::ErrorInternalServerError(e)
-    })?;
+// Picks the caller's tenant. Accepting a notification only needs its name: the outbox relay
+// publishes it on the tenant's connection later.
+pub(crate) fn resolve_tenant(auth: Option<&AuthContext>) -> Result<TenantConfig, Error> {
+    let tenant = auth.map(|a| a.tenant.as_str());
+    get_config()?.tenant_for(tenant).cloned().ok_or_else(|| {
+        warn!("🔒 Tenant {:?} is not served by this deployment", tenant);
+        Error::Forbidden(format!("Tenant {} is not served by this deployment", tenant.unwrap_or_default()))
+    })
+}
 
-    let channel = pool.get_channel().await.map_err(|e| {
-        error!("Failed to get channel: {}", e);
-        actix_web::error::ErrorInternalServerError(format!("Failed to get channel: {}", e))
-    })?;
 
-    let mut notification = payload.into_inner();
-    notification.notification_type = "immediate".to_string();
+/// Tenant an authenticated caller is confined to; `None` for admins, unauthenticated callers
+/// and single-tenant deployments.
+pub fn caller_tenant(auth: Option<&AuthContext>) -> Option<String> {
+    let auth = auth?;
+    let single_tenant = get_config().map(|c| c.is_single_tenant()).unwrap_or(true);
+    if single_tenant || auth.has_scope(crate::auth::Scope::Admin) {
+        return None;
+    }
+    Some(auth.tenant.clone())
+}
 
-    info!("📨 Sending immediate notification to user: {}", notification.user_id);
+// Stores an accepted notification in the outbox, whose relay publishes it once the broker is
+// reachable. Only a full or broken outbox fails the request.
+async fn enqueue(tenant: &TenantConfig, entry: OutboxEntry) -> Result<(), Error> {
+    let (id, user_id) = (entry.id, entry.user_id.clone());
+    outbox::enqueue(entry).await.inspect_err(|e| {
+        track(tenant.name(), id, &user_id, DeliveryState::Failed, Some(&e.to_string()));
+    })
+}
 
-    if let Err(e) = publish_notification(&channel, &notification, 0).await {
-        error!("Failed to publish immediate notification: {}", e);
-        return Ok(HttpResponse::InternalServerError().json(json!({
-            "error": "Failed to send notification",
-            "details": e.to_string()
-        })));
+// Records a status change in the local status store
+fn track(tenant: &str, notification_id: Uuid, user_id: &str, state: DeliveryState, error: Option<&str>) {
+    let mut event = StatusEvent::new(notification_id, user_id, state).with_tenant(tenant);
+    if let Some(error) = error {
+        event = event.with_error(error);
     }
+    status::record(&event);
+}
 
-    info!("✅ Immediate notification sent successfully");
-    Ok(HttpResponse::Ok().json(json!({
-        "status": "sent",
-        "type": "immediate",
-        "user_id": notification.user_id
-    })))
+/// Accepts a notification for immediate delivery. It is published from the outbox, so a
+/// broker outage delays it instead of failing the request.
+#[utoipa::path(
+    tag = "notifications",
+    request_body = Notification,
+    responses(
+        (status = 202, description = "Notification accepted", body = NotificationAccepted),
+        (status = 422, description = "Invalid notification", body = ErrorBody),
+        (status = 503, description = "Outbox full", body = ErrorBody),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+#[post("/notify")]
+pub async fn send_notification(
+    payload: web::Json<Notification>,
+    auth: Option<web::ReqData<AuthContext>>,
+) -> Result<HttpResponse, Error> {
+    let config = get_config()?;
+    validate_notification(&payload, Utc::now(), &config)?;
+    ensure_user_permitted(auth.as_deref(), &payload.user_id)?;
+
+    let tenant = resolve_tenant(auth.as_deref())?;
+    validate_callback_secret("", payload.callback_secret_id.as_deref(), tenant.name())?;
+    validate_callback_target("", payload.callback_url.as_deref(), &config).await?;
+
+    let mut notification = payload.into_inner();
+    notification.id = Uuid::new_v4();
+    notification.created_at = Some(Utc::now());
+    notification.notification_type = "immediate".to_string();
+    record_notification(&notification.id, &notification.user_id);
+    apply_client_defaults(&mut notification, auth.as_deref());
+    notification.resolve_expiry(Utc::now())?;
+    track(tenant.name(), notification.id, &notification.user_id, DeliveryState::Accepted, None);
+
+    info!("📨 Accepting immediate notification to user: {} (priority: {})",
+          notification.user_id, notification.priority.as_str());
+
+    enqueue(&tenant, OutboxEntry::new(tenant.name(), &notification, Utc::now())?).await?;
+
+    info!("✅ Immediate notification accepted");
+    Ok(HttpResponse::Accepted().json(NotificationAccepted {
+        id: notification.id,
+        status: "accepted".to_string(),
+        notification_type: Some(notification.notification_type),
+        user_id: notification.user_id,
+        priority: Some(notification.priority),
+        delay_seconds: None,
+        scheduled_at: None,
+    }))
 }
 
+/// Accepts a notification delivered after `delay_secs`, counted from acceptance.
+#[utoipa::path(
+    tag = "notifications",
+    request_body = Notification,
+    responses(
+        (status = 202, description = "Notification accepted with a delay", body = NotificationAccepted),
+        (status = 422, description = "Invalid notification or delay", body = ErrorBody),
+        (status = 503, description = "Outbox full", body = ErrorBody),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
 #[post("/notify-delayed")]
-pub async fn send_notification_delayed(payload: web::Json<Notification>) -> ActixResult<HttpResponse> {
-    let pool = get_rabbitmq_pool().map_err(|e| {
-        error!("RabbitMQ pool error: {}", e);
-        actix_web::error::ErrorInternalServerError(e)
-    })?;
-
-    let channel = pool.get_channel().await.map_err(|e| {
-        error!("Failed to get channel: {}", e);
-        actix_web::error::ErrorInternalServerError(format!("Failed to get channel: {}", e))
-    })?;
+pub async fn send_notification_delayed(
+    payload: web::Json<Notification>,
+    auth: Option<web::ReqData<AuthContext>>,
+) -> Result<HttpResponse, Error> {
+    let config = get_config()?;
+    validate_delayed(&payload, &config)?;
+    ensure_user_permitted(auth.as_deref(), &payload.user_id)?;
+
+    let tenant = resolve_tenant(auth.as_deref())?;
+    validate_callback_secret("", payload.callback_secret_id.as_deref(), tenant.name())?;
+    validate_callback_target("", payload.callback_url.as_deref(), &config).await?;
 
     let mut notification = payload.into_inner();
+    notification.id = Uuid::new_v4();
+    notification.created_at = Some(Utc::now());
     notification.notification_type = "delayed".to_string();
-    let delay_ms = (notification.delay_secs * 1000) as i32;
+    record_notification(&notification.id, &notification.user_id);
+    apply_client_defaults(&mut notification, auth.as_deref());
+    let release_at = Utc::now() + ChronoDuration::seconds(notification.delay_secs as i64);
+    notification.resolve_expiry(release_at)?;
+    track(tenant.name(), notification.id, &notification.user_id, DeliveryState::Accepted, None);
 
-    info!("🕐 Sending delayed notification to user: {} with {}s delay",
+    info!("🕐 Accepting delayed notification to user: {} with {}s delay",
           notification.user_id, notification.delay_secs);
 
-    if let Err(e) = publish_notification(&channel, &notification, delay_ms).await {
-        error!("Failed to publish delayed notification: {}", e);
-        return Ok(HttpResponse::InternalServerError().json(json!({
-            "error": "Failed to send delayed notification",
-            "details": e.to_string()
-        })));
-    }
-
-    info!("✅ Delayed notification scheduled successfully");
-    Ok(HttpResponse::Ok().json(json!({
-        "status": "scheduled",
-        "type": "delayed",
-        "user_id": notification.user_id,
-        "delay_seconds": notification.delay_secs
-    })))
+    enqueue(&tenant, OutboxEntry::new(tenant.name(), &notification, release_at)?).await?;
+
+    info!("✅ Delayed notification accepted");
+    Ok(HttpResponse::Accepted().json(NotificationAccepted {
+        id: notification.id,
+        status: "accepted".to_string(),
+        notification_type: Some(notification.notification_type),
+        user_id: notification.user_id,
+        priority: Some(notification.priority),
+        delay_seconds: Some(notification.delay_secs),
+        scheduled_at: None,
+    }))
 }
 
+/// Accepts a notification delivered at `scheduled_at`, through the broker's delayed exchange.
+#[utoipa::path(
+    tag = "notifications",
+    request_body = ScheduleAtRequest,
+    responses(
+        (status = 202, description = "Notification accepted", body = NotificationAccepted),
+        (status = 422, description = "Invalid request", body = ErrorBody),
+        (status = 503, description = "Outbox full", body = ErrorBody),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
 #[post("/notify-at")]
-pub async fn send_notification_at(payload: web::Json<ScheduleAtRequest>) -> ActixResult<HttpResponse> {
-    let pool = get_rabbitmq_pool().map_err(|e| {
-        error!("RabbitMQ pool error: {}", e);
-        actix_web::error::ErrorInternalServerError(e)
-    })?;
-
-    let channel = pool.get_channel().await.map_err(|e| {
-        error!("Failed to get channel: {}", e);
-        actix_web::error::ErrorInternalServerError(format!("Failed to get channel: {}", e))
-    })?;
+pub async fn send_notification_at(
+    payload: web::Json<ScheduleAtRequest>,
+    auth: Option<web::ReqData<AuthContext>>,
+) -> Result<HttpResponse, Error> {
+    let config = get_config()?;
+    validate_schedule_at(&payload, &config)?;
+    ensure_user_permitted(auth.as_deref(), &payload.user_id)?;
+
+    let tenant = resolve_tenant(auth.as_deref())?;
+    validate_callback_secret("", payload.callback_secret_id.as_deref(), tenant.name())?;
+    validate_callback_target("", payload.callback_url.as_deref(), &config).await?;
 
     let now = Utc::now();
     let scheduled_at = payload.scheduled_at;
-    let max_delay_ms = 7 * 24 * 60 * 60 * 1000; // 7 days in ms
-    let delay_ms = (scheduled_at.timestamp_millis() - now.timestamp_millis()).max(0) as i64;
+    let max_delay_ms = config.max_delay_hop_secs as i64 * 1000;
+    let delay_ms = (scheduled_at.timestamp_millis() - now.timestamp_millis()).max(0);
 
-    // If the delay is greater than the maximum, only schedule 7 days and save the real date
+    // If the delay is greater than the maximum, only schedule one hop and save the real date
     let (final_delay_ms, real_scheduled_at) = if delay_ms > max_delay_ms {
         (max_delay_ms, Some(scheduled_at))
     } else {
@@ -135,12 +216,23 @@ pub async fn send_notification_at(payload: web::Json<ScheduleAtRequest>) -> Acti
     };
 
     // Notification with real scheduled_at if applicable
-    let notification = Notification {
+    let mut notification = Notification {
+        id: Uuid::new_v4(),
         user_id: payload.user_id.clone(),
         message: payload.message.clone(),
         delay_secs: 0,
         notification_type: "scheduled".to_string(),
+        channel: payload.channel.clone(),
+        priority: payload.priority,
+        expires_at: payload.expires_at,
+        ttl_secs: payload.ttl_secs,
+        callback_url: payload.callback_url.clone(),
+        callback_secret_id: payload.callback_secret_id.clone(),
+        created_at: Some(now),
     };
+    notification.resolve_expiry(scheduled_at)?;
+    apply_client_defaults(&mut notification, auth.as_deref());
+    record_notification(&notification.id, &notification.user_id);
 
     // Pack the payload with the real date if applicable
     let mut json_payload = serde_json::to_value(&notification).unwrap();
@@ -153,54 +245,67 @@ pub async fn send_notification_at(payload: web::Json<ScheduleAtRequest>) -> Acti
     info!("🕐 Scheduling notification for user: {} at {} (delay {} ms, real_scheduled_at: {:?})",
           notification.user_id, scheduled_at, final_delay_ms, real_scheduled_at);
 
-    // Use publish_notification but serializing the payload manually
-    let body = serde_json::to_vec(&json_payload).map_err(|e| {
-        error!("Serialization error: {}", e);
-        actix_web::error::ErrorInternalServerError("Serialization error")
-    })?;
-
-    let properties = if final_delay_ms > 0 {
-        BasicProperties::default().with_headers({
-            let mut table = FieldTable::default();
-            table.insert("x-delay".into(), AMQPValue::LongInt(final_delay_ms as i32));
-            table
-        })
-    } else {
-        BasicProperties::default()
-    };
+    let mut entry = OutboxEntry::new(
+        tenant.name(),
+        &notification,
+        now + ChronoDuration::milliseconds(final_delay_ms),
+    )?;
+    entry.payload = json_payload;
+    // Long schedules must reach the worker to be requeued, so only the last hop gets a TTL
+    if real_scheduled_at.is_some() {
+        entry.expires_at = None;
+    }
 
-    channel
-        .basic_publish(
-            "delayed_exchange",
-            "main",
-            BasicPublishOptions::default(),
-            &body,
-            properties,
-        )
-        .await
-        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to publish message: {}", e)))?
-        .await
-        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to confirm message: {}", e)))?;
-
-    Ok(HttpResponse::Ok().json(json!({
-        "status": "scheduled",
-        "type": "scheduled",
-        "user_id": notification.user_id,
-        "scheduled_at": scheduled_at
-    })))
+    track(tenant.name(), notification.id, &notification.user_id, DeliveryState::Accepted, None);
+    enqueue(&tenant, entry).await?;
+
+    Ok(HttpResponse::Accepted().json(NotificationAccepted {
+        id: notification.id,
+        status: "accepted".to_string(),
+        notification_type: Some(notification.notification_type),
+        user_id: notification.user_id,
+        priority: Some(notification.priority),
+        delay_seconds: None,
+        scheduled_at: Some(scheduled_at),
+    }))
 }
 
+/// Stores a notification that the server's scheduler publishes when due.
+#[utoipa::path(
+    tag = "notifications",
+    request_body = ScheduleNotificationRequest,
+    responses(
+        (status = 200, description = "Notification scheduled", body = NotificationAccepted),
+        (status = 422, description = "Invalid request", body = ErrorBody),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
 #[post("/schedule-notification")]
-pub async fn schedule_notification(payload: web::Json<ScheduleNotificationRequest>) -> ActixResult<HttpResponse> {
+pub async fn schedule_notification(
+    payload: web::Json<ScheduleNotificationRequest>,
+    auth: Option<web::ReqData<AuthContext>>,
+) -> Result<HttpResponse, Error> {
+    let config = get_config()?;
+    validate_schedule_request(&payload, &config)?;
+    ensure_user_permitted(auth.as_deref(), &payload.user_id)?;
+
+    let tenant = resolve_tenant(auth.as_deref())?;
+    let secret_id = payload.payload.get("callback_secret_id").and_then(|v| v.as_str());
+    validate_callback_secret("payload.", secret_id, tenant.name())?;
+    let callback_url = payload.payload.get("callback_url").and_then(|v| v.as_str());
+    validate_callback_target("payload.", callback_url, &config).await?;
+
     let mut db = SCHEDULED_NOTIFICATIONS.lock().map_err(|e| {
         error!("Failed to lock scheduled notifications: {}", e);
-        actix_web::error::ErrorInternalServerError("Database lock error")
+        Error::Store("Database lock error".to_string())
     })?;
 
     let id = Uuid::new_v4();
+    record_notification(&id, &payload.user_id);
     let notification = ScheduledNotification {
         id,
         user_id: payload.user_id.clone(),
+        tenant: tenant.name().to_string(),
         scheduled_at: payload.scheduled_at,
         payload: payload.payload.clone(),
         status: "pending".to_string(),
@@ -210,72 +315,148 @@ pub async fn schedule_notification(payload: web::Json<ScheduleNotificationReques
           id, payload.user_id, payload.scheduled_at);
 
     db.insert(id, notification.clone());
+    track(tenant.name(), id, &payload.user_id, DeliveryState::Accepted, None);
 
     info!("✅ Notification scheduled successfully with ID: {}", id);
-    Ok(HttpResponse::Ok().json(json!({
-        "id": id,
-        "status": "scheduled",
-        "scheduled_at": payload.scheduled_at,
-        "user_id": payload.user_id
-    })))
+    // What the scheduler will route it with
+    let priority = priority_of_value(&payload.payload);
+    Ok(HttpResponse::Ok().json(NotificationAccepted {
+        id,
+        status: "scheduled".to_string(),
+        notification_type: None,
+        user_id: payload.into_inner().user_id,
+        priority: Some(priority),
+        delay_seconds: None,
+        scheduled_at: Some(notification.scheduled_at),
+    }))
+}
+
+/// Current delivery status of a notification and its history.
+#[utoipa::path(
+    tag = "status",
+    params(("id" = Uuid, Path, description = "Notification id returned when it was accepted")),
+    responses(
+        (status = 200, description = "Delivery status", body = DeliveryStatus),
+        (status = 404, description = "Unknown notification", body = ErrorBody),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+#[get("/notifications/{id}")]
+pub async fn get_notification_status(
+    path: web::Path<Uuid>,
+    auth: Option<web::ReqData<AuthContext>>,
+) -> Result<HttpResponse, Error> {
+    let id = path.into_inner();
+    // Other tenants' notifications are reported as missing
+    let visible_tenant = caller_tenant(auth.as_deref());
+    match status::get_status(&id)
+        .filter(|s| visible_tenant.is_none() || s.tenant.as_deref() == visible_tenant.as_deref())
+    {
+        Some(delivery_status) => Ok(HttpResponse::Ok().json(delivery_status)),
+        None => Err(Error::NotFound(format!("Notification {} not found", id))),
+    }
 }
 
 pub async fn notification_scheduler_task() {
     info!("🕐 Starting notification scheduler task");
 
     loop {
-        if let Err(e) = run_scheduler_cycle().await {
+        // Read every cycle so a configuration reload takes effect
+        let (interval_secs, error_backoff_secs) = get_config()
+            .map(|c| (c.scheduler_interval_secs, c.scheduler_error_backoff_secs))
+            .unwrap_or((1, 5));
+        let timer = metrics::SCHEDULER_CYCLE_DURATION.start_timer();
+        let result = run_scheduler_cycle().await;
+        timer.observe_duration();
+        if let Err(e) = result {
             error!("Scheduler cycle failed: {}", e);
-            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+            tokio::time::sleep(tokio::time::Duration::from_secs(error_backoff_secs)).await;
             continue;
         }
+        crate::health::SCHEDULER_HEARTBEAT.beat();
 
-        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        tokio::time::sleep(tokio::time::Duration::from_secs(interval_secs)).await;
     }
 }
 
-async fn run_scheduler_cycle() -> Result<(), String> {
-    let pool = get_rabbitmq_pool().map_err(|e| format!("Failed to get pool: {}", e))?;
-    let channel = pool.get_channel().await.map_err(|e| format!("Failed to get channel: {}", e))?;
+async fn run_scheduler_cycle() -> Result<(), Error> {
+    let pool = get_rabbitmq_pool()?;
+
+    let grace_period = ChronoDuration::seconds(
+        get_config().map(|c| c.scheduler_grace_period_secs).unwrap_or(300) as i64,
+    );
 
     let mut notifications_to_send = Vec::new();
 
     // Collect pending notifications
     {
         let mut db = SCHEDULED_NOTIFICATIONS.lock().map_err(|e| {
-            format!("Failed to lock scheduled notifications: {}", e)
+            Error::Store(format!("Failed to lock scheduled notifications: {}", e))
         })?;
 
         let now = Utc::now();
 
         for (id, notification) in db.iter_mut() {
             if notification.status == "pending" && notification.scheduled_at <= now {
+                if is_scheduled_notification_expired(notification, now, grace_period) {
+                    notification.status = "expired".to_string();
+                    track(&notification.tenant, *id, &notification.user_id, DeliveryState::Expired, None);
+                    warn!("⌛ Scheduled notification {} for user {} expired (was due at {}), discarding",
+                          id, notification.user_id, notification.scheduled_at);
+                    continue;
+                }
                 notification.status = "processing".to_string();
                 notifications_to_send.push((*id, notification.clone()));
             }
         }
     }
 
+    metrics::SCHEDULER_DUE_BACKLOG.set(notifications_to_send.len() as i64);
+
     // Process notifications
     for (id, scheduled_notification) in notifications_to_send {
-        match process_scheduled_notification(&channel, &scheduled_notification).await {
+        let span = tracing::info_span!(
+            "scheduled_notification",
+            notification_id = %id,
+            user_id = %scheduled_notification.user_id,
+        );
+        let result = match pool.tenant(&scheduled_notification.tenant) {
+            Some(tenant) => {
+                process_scheduled_notification(tenant, &scheduled_notification)
+                    .instrument(span.clone())
+                    .await
+            }
+            None => Err(Error::Config(format!("Unknown tenant {}", scheduled_notification.tenant))),
+        };
+        let _entered = span.enter();
+        match result {
             Ok(_) => {
-                // Mark as sent
-                if let Ok(mut db) = SCHEDULED_NOTIFICATIONS.lock() {
-                    if let Some(notification) = db.get_mut(&id) {
-                        notification.status = "sent".to_string();
-                    }
+                // Mark as sent; the outbox relay reports it queued once the broker confirms
+                if let Ok(mut db) = SCHEDULED_NOTIFICATIONS.lock()
+                    && let Some(notification) = db.get_mut(&id)
+                {
+                    notification.status = "sent".to_string();
+                }
+                info!("✅ Scheduled notification {} handed to the outbox", id);
+            }
+            Err(Error::OutboxFull(waiting)) => {
+                // Retried on the next cycle, within the grace period
+                warn!("📤 Outbox full ({} waiting), scheduled notification {} stays pending", waiting, id);
+                if let Ok(mut db) = SCHEDULED_NOTIFICATIONS.lock()
+                    && let Some(notification) = db.get_mut(&id)
+                {
+                    notification.status = "pending".to_string();
                 }
-                info!("✅ Scheduled notification {} sent successfully", id);
             }
             Err(e) => {
                 error!("Failed to send scheduled notification {}: {}", id, e);
                 // Mark as failed
-                if let Ok(mut db) = SCHEDULED_NOTIFICATIONS.lock() {
-                    if let Some(notification) = db.get_mut(&id) {
-                        notification.status = "failed".to_string();
-                    }
+                if let Ok(mut db) = SCHEDULED_NOTIFICATIONS.lock()
+                    && let Some(notification) = db.get_mut(&id)
+                {
+                    notification.status = "failed".to_string();
                 }
+                track(&scheduled_notification.tenant, id, &scheduled_notification.user_id, DeliveryState::Failed, Some(&e.to_string()));
             }
         }
     }
@@ -284,17 +465,38 @@ async fn run_scheduler_cycle() -> Result<(), String> {
 }
 
 async fn process_scheduled_notification(
-    channel: &Channel,
+    tenant: &TenantPool,
     scheduled_notification: &ScheduledNotification
-) -> Result<(), String> {
+) -> Result<(), Error> {
     // Convert payload to Notification
-    let mut notification: Notification = serde_json::from_value(scheduled_notification.payload.clone())
-        .map_err(|e| format!("Failed to deserialize notification: {}", e))?;
+    let mut notification: Notification = serde_json::from_value(scheduled_notification.payload.clone())?;
+    // The row id doubles as the notification id so its status can be looked up
+    notification.id = scheduled_notification.id;
+    notification.created_at = Some(Utc::now());
     notification.notification_type = "scheduled".to_string();
+    notification.resolve_expiry(scheduled_notification.scheduled_at)?;
 
     info!("📨 Processing scheduled notification for user: {}", notification.user_id);
 
-    publish_notification(channel, &notification, 0).await?;
+    outbox::enqueue(OutboxEntry::new(tenant.name(), &notification, Utc::now())?).await
+}
 
-    Ok(())
+/// A row is expired when it is overdue by more than the grace period (e.g. the server was down)
+/// or when its payload carries an expiry that has already passed.
+fn is_scheduled_notification_expired(
+    scheduled_notification: &ScheduledNotification,
+    now: chrono::DateTime<Utc>,
+    grace_period: ChronoDuration,
+) -> bool {
+    if now - scheduled_notification.scheduled_at > grace_period {
+        return true;
+    }
+    match serde_json::from_value::<Notification>(scheduled_notification.payload.clone()) {
+        // An expiry out of range is reported as a failure when processed
+        Ok(mut notification) => notification
+            .resolve_expiry(scheduled_notification.scheduled_at)
+            .is_ok_and(|()| notification.is_expired(now)),
+        // Malformed payloads are reported as failures when processed
+        Err(_) => false,
+    }
 }
@@ -0,0 +1,346 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use actix_web::{App, HttpResponse, HttpServer, dev::Server, get};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::config::get_config;
+use crate::connection::get_rabbitmq_pool;
+use crate::error::Error;
+
+/// Time of the last occurrence of something, readable from any thread without locking.
+pub struct Heartbeat(AtomicI64);
+
+impl Heartbeat {
+    pub const fn new() -> Self {
+        Heartbeat(AtomicI64::new(0))
+    }
+
+    pub fn beat(&self) {
+        self.0
+            .store(Utc::now().timestamp_millis(), Ordering::Relaxed);
+    }
+
+    pub fn last(&self) -> Option<DateTime<Utc>> {
+        match self.0.load(Ordering::Relaxed) {
+            0 => None,
+            millis => DateTime::from_timestamp_millis(millis),
+        }
+    }
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Completed scheduler cycles on the server.
+pub static SCHEDULER_HEARTBEAT: Heartbeat = Heartbeat::new();
+/// Deliveries received by the worker's consumers.
+pub static LAST_MESSAGE: Heartbeat = Heartbeat::new();
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ConsumerState {
+    Consuming,
+    Stopped,
+}
+
+/// A queue consumer of the worker.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct ConsumerStatus {
+    pub tenant: String,
+    pub queue: String,
+    pub state: ConsumerState,
+    pub since: DateTime<Utc>,
+}
+
+// Worker consumers by consumer tag
+lazy_static::lazy_static! {
+    static ref CONSUMERS: Mutex<BTreeMap<String, ConsumerStatus>> = Mutex::new(BTreeMap::new());
+}
+
+/// Records that a worker consumer started or stopped consuming.
+pub fn set_consumer_state(tag: &str, tenant: &str, queue: &str, state: ConsumerState) {
+    if let Ok(mut consumers) = CONSUMERS.lock() {
+        consumers.insert(
+            tag.to_string(),
+            ConsumerStatus {
+                tenant: tenant.to_string(),
+                queue: queue.to_string(),
+                state,
+                since: Utc::now(),
+            },
+        );
+    }
+}
+
+pub fn consumers() -> Vec<ConsumerStatus> {
+    CONSUMERS
+        .lock()
+        .map(|consumers| consumers.values().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Outcome of one readiness check.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Check {
+    pub name: &'static str,
+    pub ok: bool,
+    /// Whether a failure makes the service unready; other failures only degrade it.
+    pub critical: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Check {
+    pub fn new(name: &'static str, result: Result<(), String>) -> Self {
+        let (ok, detail) = match result {
+            Ok(()) => (true, None),
+            Err(detail) => (false, Some(detail)),
+        };
+        Check {
+            name,
+            ok,
+            critical: true,
+            detail,
+        }
+    }
+
+    /// A check whose failure is reported without taking the service out of rotation.
+    pub fn degrading(name: &'static str, result: Result<(), String>) -> Self {
+        Check {
+            critical: false,
+            ..Check::new(name, result)
+        }
+    }
+}
+
+/// The broker node a tenant is connected to.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BrokerNode {
+    pub tenant: String,
+    /// `host:port` of the active node, or of the last one when the connection is closed.
+    pub node: String,
+    pub connected: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Readiness {
+    /// `ready` when every check passed, `degraded` when only non-critical checks failed,
+    /// `not_ready` otherwise.
+    pub status: &'static str,
+    pub checks: Vec<Check>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub brokers: Vec<BrokerNode>,
+}
+
+impl Readiness {
+    /// `not_ready` (answered with `503`) only when a critical check failed.
+    pub fn status_of(checks: &[Check]) -> &'static str {
+        if checks.iter().all(|c| c.ok) {
+            "ready"
+        } else if checks.iter().all(|c| c.ok || !c.critical) {
+            "degraded"
+        } else {
+            "not_ready"
+        }
+    }
+
+    fn from_checks(checks: Vec<Check>) -> HttpResponse {
+        let status = Readiness::status_of(&checks);
+        let body = Readiness {
+            status,
+            checks,
+            brokers: broker_nodes(),
+        };
+        if status == "not_ready" {
+            HttpResponse::ServiceUnavailable().json(body)
+        } else {
+            HttpResponse::Ok().json(body)
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Liveness {
+    pub status: &'static str,
+}
+
+fn broker_nodes() -> Vec<BrokerNode> {
+    let Ok(pool) = get_rabbitmq_pool() else {
+        return Vec::new();
+    };
+    pool.tenants()
+        .iter()
+        .map(|t| BrokerNode {
+            tenant: t.name().to_string(),
+            node: t.active_node(),
+            connected: t.is_connected(),
+        })
+        .collect()
+}
+
+/// Every tenant's broker connection is open.
+fn check_broker() -> Result<(), String> {
+    let pool = get_rabbitmq_pool().map_err(|e| e.to_string())?;
+    let down: Vec<&str> = pool
+        .tenants()
+        .iter()
+        .filter(|t| !t.is_connected())
+        .map(|t| t.name())
+        .collect();
+    if down.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Connection closed for tenants: {}",
+            down.join(", ")
+        ))
+    }
+}
+
+/// A channel can be opened on every tenant's connection.
+async fn check_channel() -> Result<(), String> {
+    let pool = get_rabbitmq_pool().map_err(|e| e.to_string())?;
+    for tenant in pool.tenants() {
+        let channel = tenant
+            .get_channel()
+            .await
+            .map_err(|e| format!("Tenant {}: {}", tenant.name(), e))?;
+        let _ = channel.close(200, "Readiness check").await;
+    }
+    Ok(())
+}
+
+fn check_store() -> Result<(), String> {
+    crate::inbox::lock_inbox()
+        .map(drop)
+        .map_err(|e| e.to_string())?;
+    crate::models::SCHEDULED_NOTIFICATIONS
+        .lock()
+        .map(drop)
+        .map_err(|_| "Scheduled notifications store is poisoned".to_string())
+}
+
+/// The outbox has room for new notifications.
+fn check_outbox() -> Result<(), String> {
+    let outbox = crate::outbox::lock_outbox().map_err(|e| e.to_string())?;
+    let max_entries = get_config()
+        .map(|c| c.outbox_max_entries)
+        .unwrap_or(usize::MAX);
+    if outbox.len() < max_entries {
+        Ok(())
+    } else {
+        Err(format!("Outbox is full with {} notifications", outbox.len()))
+    }
+}
+
+fn check_scheduler() -> Result<(), String> {
+    let stale_after = get_config()
+        .map(|c| c.scheduler_stale_after_secs)
+        .unwrap_or(30);
+    match SCHEDULER_HEARTBEAT.last() {
+        Some(last) if (Utc::now() - last).num_seconds() <= stale_after as i64 => Ok(()),
+        Some(last) => Err(format!("Last scheduler cycle completed at {}", last)),
+        None => Err("Scheduler has not completed a cycle yet".to_string()),
+    }
+}
+
+/// Liveness probe: the process is up and serving requests.
+#[utoipa::path(
+    tag = "health",
+    responses((status = 200, description = "Process is alive", body = Liveness))
+)]
+#[get("/healthz")]
+pub async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().json(Liveness { status: "ok" })
+}
+
+/// Readiness probe: the stores and the scheduler are usable and the outbox has room. Accepted
+/// notifications wait in the outbox while the broker is down, so a broker outage only degrades
+/// the server.
+#[utoipa::path(
+    tag = "health",
+    responses(
+        (status = 200, description = "Ready, or degraded while the broker is unreachable", body = Readiness),
+        (status = 503, description = "A critical check failed", body = Readiness),
+    )
+)]
+#[get("/readyz")]
+pub async fn readyz() -> HttpResponse {
+    Readiness::from_checks(vec![
+        Check::degrading("broker", check_broker()),
+        Check::degrading("channel", check_channel().await),
+        Check::new("store", check_store()),
+        Check::new("outbox", check_outbox()),
+        Check::new("scheduler", check_scheduler()),
+    ])
+}
+
+#[derive(Debug, Serialize)]
+struct WorkerHealth {
+    status: &'static str,
+    consumers: Vec<ConsumerStatus>,
+    last_message_at: Option<DateTime<Utc>>,
+}
+
+/// Worker liveness, with the state of every consumer and when the last delivery arrived.
+#[get("/healthz")]
+async fn worker_healthz() -> HttpResponse {
+    HttpResponse::Ok().json(WorkerHealth {
+        status: "ok",
+        consumers: consumers(),
+        last_message_at: LAST_MESSAGE.last(),
+    })
+}
+
+/// Worker readiness: broker connections are open and every consumer is consuming.
+#[get("/readyz")]
+async fn worker_readyz() -> HttpResponse {
+    let consumers = consumers();
+    let stopped: Vec<&str> = consumers
+        .iter()
+        .filter(|c| c.state != ConsumerState::Consuming)
+        .map(|c| c.queue.as_str())
+        .collect();
+    let consumer_check = if consumers.is_empty() {
+        Err("No consumer started yet".to_string())
+    } else if stopped.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Not consuming from: {}", stopped.join(", ")))
+    };
+    Readiness::from_checks(vec![
+        Check::new("broker", check_broker()),
+        Check::new("consumers", consumer_check),
+    ])
+}
+
+/// Binds the worker's listener for probes and metrics; the returned server runs once spawned.
+pub fn worker_health_server() -> Result<Server, Error> {
+    let config = get_config()?;
+    let address = (config.worker_health_host.clone(), config.worker_health_port);
+    let server = HttpServer::new(|| {
+        App::new()
+            .service(worker_healthz)
+            .service(worker_readyz)
+            .service(crate::metrics::metrics)
+    })
+    .workers(1)
+    // The worker handles shutdown signals itself
+    .disable_signals()
+    .bind(&address)
+    .map_err(|e| Error::Config(format!("Failed to bind worker health listener: {}", e)))?
+    .run();
+    info!(
+        "🩺 Worker health listener at http://{}:{}",
+        address.0, address.1
+    );
+    Ok(server)
+}
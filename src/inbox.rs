@@ -0,0 +1,717 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use actix_web::{HttpResponse, get, post, web};
+use chrono::{DateTime, Utc};
+use futures_util::stream::StreamExt;
+use lapin::{BasicProperties, options::*, types::FieldTable};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::auth::AuthContext;
+use crate::config::init_config;
+use crate::connection::{TenantPool, get_rabbitmq_pool};
+use crate::error::{Error, ErrorBody};
+use crate::handlers::{ensure_user_permitted, resolve_tenant};
+use crate::models::{Notification, Priority};
+use crate::store::JsonLog;
+
+/// A delivered notification as shown in the user's in-app inbox.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct InboxEntry {
+    pub notification_id: Uuid,
+    pub user_id: String,
+    pub message: String,
+    pub notification_type: String,
+    pub channel: String,
+    pub priority: Priority,
+    pub delivered_at: DateTime<Utc>,
+    #[serde(default)]
+    pub read_at: Option<DateTime<Utc>>,
+}
+
+impl InboxEntry {
+    pub fn from_notification(notification: &Notification, delivered_at: DateTime<Utc>) -> Self {
+        InboxEntry {
+            notification_id: notification.id,
+            user_id: notification.user_id.clone(),
+            message: notification.message.clone(),
+            notification_type: notification.notification_type.clone(),
+            channel: notification.channel.clone(),
+            priority: notification.priority,
+            delivered_at,
+            read_at: None,
+        }
+    }
+}
+
+/// What goes over a tenant's inbox exchange. Every server keeps its own copy of the inboxes
+/// and applies every event, so replicas show the same entries and read state.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum InboxEvent {
+    /// A notification the worker delivered.
+    Delivered(InboxEntry),
+    /// The user read one entry, or every entry delivered until `read_at` when
+    /// `notification_id` is absent.
+    Read {
+        user_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        notification_id: Option<Uuid>,
+        read_at: DateTime<Utc>,
+    },
+}
+
+/// Inboxes by tenant, then user.
+type TenantInboxes = HashMap<String, HashMap<String, Vec<InboxEntry>>>;
+
+/// Inbox file contents written before the log; files from before inboxes were kept per tenant
+/// map users directly.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum InboxFile {
+    Tenants(TenantInboxes),
+    Users(HashMap<String, Vec<InboxEntry>>),
+}
+
+/// One change to the inboxes, appended to their log as a JSON line.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum LogRecord<E> {
+    Add {
+        tenant: String,
+        entry: E,
+    },
+    /// One entry read, or every entry delivered until `read_at` when `notification_id` is absent.
+    Read {
+        tenant: String,
+        user_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        notification_id: Option<Uuid>,
+        read_at: DateTime<Utc>,
+    },
+}
+
+/// Stale log records tolerated before the log is rewritten with only the kept entries.
+const COMPACT_AFTER_STALE_RECORDS: usize = 1000;
+
+/// Per-user inboxes of each tenant, newest entry last. The same user id in two tenants is two
+/// different inboxes. Every change is appended to a log of JSON lines and synced to disk, so a
+/// change costs the same however large the inboxes grow. The changing methods do disk I/O;
+/// async code goes through `with_inbox`, which runs them on the blocking pool.
+pub struct InboxStore {
+    log: JsonLog,
+    max_per_user: usize,
+    inboxes: TenantInboxes,
+    /// Records in the log that a rewrite would leave out: reads and trimmed entries.
+    stale_records: usize,
+}
+
+impl InboxStore {
+    /// Replays the log and rewrites it with only the kept entries. A file written before the
+    /// log is converted; its inboxes go to `default_tenant` if it has no tenants.
+    pub fn open(
+        path: impl Into<PathBuf>,
+        max_per_user: usize,
+        default_tenant: &str,
+    ) -> Result<Self, Error> {
+        let path = path.into();
+        let contents = JsonLog::read(&path)?;
+        let mut inboxes = TenantInboxes::new();
+        match serde_json::from_str::<InboxFile>(&contents) {
+            Ok(InboxFile::Tenants(tenants)) => inboxes = tenants,
+            Ok(InboxFile::Users(users)) => {
+                inboxes.insert(default_tenant.to_string(), users);
+            }
+            Err(_) => {
+                for record in JsonLog::parse(&path, &contents)? {
+                    apply_record(&mut inboxes, max_per_user, record);
+                }
+            }
+        }
+        Ok(InboxStore {
+            log: write_log(path, &inboxes)?,
+            max_per_user,
+            inboxes,
+            stale_records: 0,
+        })
+    }
+
+    fn inbox(&self, tenant: &str, user_id: &str) -> Option<&Vec<InboxEntry>> {
+        self.inboxes.get(tenant)?.get(user_id)
+    }
+
+    /// Adds an entry to the tenant's inbox of its user, ignoring redeliveries of the same
+    /// notification.
+    pub fn add(&mut self, tenant: &str, entry: InboxEntry) -> Result<bool, Error> {
+        let duplicate = self
+            .inbox(tenant, &entry.user_id)
+            .is_some_and(|inbox| inbox.iter().any(|e| e.notification_id == entry.notification_id));
+        if duplicate {
+            return Ok(false);
+        }
+        let record = LogRecord::Add {
+            tenant: tenant.to_string(),
+            entry,
+        };
+        self.log.append(&record)?;
+        let trimmed = apply_record(&mut self.inboxes, self.max_per_user, record);
+        self.record_stale(trimmed)?;
+        Ok(true)
+    }
+
+    /// Newest first, `per_page` entries of page `page` (1-based).
+    pub fn page(
+        &self,
+        tenant: &str,
+        user_id: &str,
+        page: usize,
+        per_page: usize,
+        unread_only: bool,
+    ) -> (Vec<InboxEntry>, usize) {
+        let entries: Vec<&InboxEntry> = self
+            .inbox(tenant, user_id)
+            .map(|inbox| {
+                inbox
+                    .iter()
+                    .rev()
+                    .filter(|e| !unread_only || e.read_at.is_none())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let total = entries.len();
+        let items = entries
+            .into_iter()
+            // `page` comes from the query string and may be anything
+            .skip(page.saturating_sub(1).saturating_mul(per_page))
+            .take(per_page)
+            .cloned()
+            .collect();
+        (items, total)
+    }
+
+    pub fn unread_count(&self, tenant: &str, user_id: &str) -> usize {
+        self.inbox(tenant, user_id)
+            .map(|inbox| inbox.iter().filter(|e| e.read_at.is_none()).count())
+            .unwrap_or(0)
+    }
+
+    /// Marks one entry as read at `at`, keeping an earlier read time. Returns `None` when the
+    /// entry does not exist.
+    pub fn mark_read(
+        &mut self,
+        tenant: &str,
+        user_id: &str,
+        notification_id: &Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<Option<InboxEntry>, Error> {
+        let Some(entry) = self
+            .inbox(tenant, user_id)
+            .and_then(|inbox| inbox.iter().find(|e| &e.notification_id == notification_id))
+        else {
+            return Ok(None);
+        };
+        if entry.read_at.is_some() {
+            return Ok(Some(entry.clone()));
+        }
+        self.read(tenant, user_id, Some(*notification_id), at)?;
+        Ok(self
+            .inbox(tenant, user_id)
+            .and_then(|inbox| inbox.iter().find(|e| &e.notification_id == notification_id))
+            .cloned())
+    }
+
+    /// Marks every entry delivered until `at` as read and returns how many changed. Entries
+    /// that reach this server after a marker from another one stay unread if they are newer.
+    pub fn mark_all_read(
+        &mut self,
+        tenant: &str,
+        user_id: &str,
+        at: DateTime<Utc>,
+    ) -> Result<usize, Error> {
+        let marked = self
+            .inbox(tenant, user_id)
+            .map(|inbox| {
+                inbox
+                    .iter()
+                    .filter(|e| e.read_at.is_none() && e.delivered_at <= at)
+                    .count()
+            })
+            .unwrap_or(0);
+        if marked > 0 {
+            self.read(tenant, user_id, None, at)?;
+        }
+        Ok(marked)
+    }
+
+    fn read(
+        &mut self,
+        tenant: &str,
+        user_id: &str,
+        notification_id: Option<Uuid>,
+        read_at: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let record = LogRecord::Read {
+            tenant: tenant.to_string(),
+            user_id: user_id.to_string(),
+            notification_id,
+            read_at,
+        };
+        self.log.append(&record)?;
+        apply_record(&mut self.inboxes, self.max_per_user, record);
+        // A rewrite keeps the read time in the entry itself
+        self.record_stale(1)
+    }
+
+    /// Applies an event from the inbox exchange. Events this server published itself come
+    /// back too and change nothing the second time.
+    pub fn apply(&mut self, tenant: &str, event: InboxEvent) -> Result<(), Error> {
+        match event {
+            InboxEvent::Delivered(entry) => self.add(tenant, entry).map(|_| ()),
+            InboxEvent::Read {
+                user_id,
+                notification_id: Some(id),
+                read_at,
+            } => self.mark_read(tenant, &user_id, &id, read_at).map(|_| ()),
+            InboxEvent::Read {
+                user_id,
+                notification_id: None,
+                read_at,
+            } => self.mark_all_read(tenant, &user_id, read_at).map(|_| ()),
+        }
+    }
+
+    /// Counts records the log no longer needs and rewrites it once they outnumber the entries.
+    fn record_stale(&mut self, records: usize) -> Result<(), Error> {
+        self.stale_records += records;
+        if self.stale_records >= COMPACT_AFTER_STALE_RECORDS
+            && self.stale_records >= self.len()
+        {
+            self.log = write_log(self.log.path().to_path_buf(), &self.inboxes)?;
+            self.stale_records = 0;
+        }
+        Ok(())
+    }
+
+    /// Entries in all inboxes.
+    pub fn len(&self) -> usize {
+        self.inboxes
+            .values()
+            .flat_map(HashMap::values)
+            .map(Vec::len)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Applies a change to the inboxes and returns how many old entries it trimmed.
+fn apply_record(
+    inboxes: &mut TenantInboxes,
+    max_per_user: usize,
+    record: LogRecord<InboxEntry>,
+) -> usize {
+    match record {
+        LogRecord::Add { tenant, entry } => {
+            let inbox = inboxes
+                .entry(tenant)
+                .or_default()
+                .entry(entry.user_id.clone())
+                .or_default();
+            inbox.push(entry);
+            let excess = inbox.len().saturating_sub(max_per_user);
+            inbox.drain(..excess);
+            excess
+        }
+        LogRecord::Read {
+            tenant,
+            user_id,
+            notification_id,
+            read_at,
+        } => {
+            let Some(inbox) = inboxes
+                .get_mut(&tenant)
+                .and_then(|users| users.get_mut(&user_id))
+            else {
+                return 0;
+            };
+            for entry in inbox.iter_mut().filter(|e| match notification_id {
+                Some(id) => e.notification_id == id,
+                None => e.delivered_at <= read_at,
+            }) {
+                entry.read_at.get_or_insert(read_at);
+            }
+            0
+        }
+    }
+}
+
+/// Replaces the log at `path` with one addition per entry and returns it opened for appending.
+fn write_log(path: PathBuf, inboxes: &TenantInboxes) -> Result<JsonLog, Error> {
+    let records = inboxes.iter().flat_map(|(tenant, users)| {
+        users.values().flatten().map(|entry| LogRecord::Add {
+            tenant: tenant.clone(),
+            entry,
+        })
+    });
+    JsonLog::rewrite(path, records)
+}
+
+// Singleton global
+lazy_static::lazy_static! {
+    static ref INBOX_STORE: tokio::sync::OnceCell<Mutex<InboxStore>> = tokio::sync::OnceCell::new();
+}
+
+pub fn init_inbox_store() -> Result<(), Error> {
+    let config = init_config()?;
+    let default_tenant = config.tenants.first().map(|t| t.name.as_str()).unwrap_or_default();
+    let store = InboxStore::open(&config.inbox_path, config.inbox_max_per_user, default_tenant)?;
+    INBOX_STORE
+        .set(Mutex::new(store))
+        .map_err(|_| Error::Store("Failed to set inbox store".to_string()))?;
+    Ok(())
+}
+
+pub fn get_inbox_store() -> Result<&'static Mutex<InboxStore>, Error> {
+    INBOX_STORE
+        .get()
+        .ok_or_else(|| Error::Store("Inbox store not initialized".to_string()))
+}
+
+pub(crate) fn lock_inbox() -> Result<std::sync::MutexGuard<'static, InboxStore>, Error> {
+    let store = get_inbox_store().inspect_err(|e| error!("Inbox store error: {}", e))?;
+    store.lock().map_err(|e| {
+        error!("Failed to lock inbox store: {}", e);
+        Error::Store("Database lock error".to_string())
+    })
+}
+
+/// Runs a change to the inboxes on the blocking pool, since it is written and synced to disk.
+async fn with_inbox<T, F>(change: F) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut InboxStore) -> Result<T, Error> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || change(&mut *lock_inbox()?))
+        .await
+        .map_err(|e| Error::Store(format!("Inbox write failed: {}", e)))?
+}
+
+/// Publishes an inbox event to every server's inbox queue and waits for the broker's confirm.
+/// The worker sends delivered notifications before it acknowledges the delivery; servers send
+/// read markers.
+pub async fn publish_inbox_event(
+    tenant: &TenantPool,
+    event: &InboxEvent,
+) -> Result<(), Error> {
+    let channel = tenant.get_publish_channel().await?;
+    let body = serde_json::to_vec(event)?;
+
+    let confirmation = channel
+        .basic_publish(
+            &tenant.topology().inbox_exchange,
+            "",
+            BasicPublishOptions::default(),
+            &body,
+            BasicProperties::default().with_delivery_mode(2),
+        )
+        .await
+        .map_err(|e| Error::Publish(e.to_string()))?
+        .await
+        .map_err(|e| Error::Publish(format!("Publish was not confirmed: {}", e)))?;
+    if confirmation.is_nack() {
+        return Err(Error::Publish("Broker rejected the inbox event".to_string()));
+    }
+    Ok(())
+}
+
+/// Server-side task: stores every tenant's delivered notifications in the users' inboxes.
+pub async fn inbox_consumer_task() {
+    info!("📥 Starting inbox consumer");
+
+    let pool = match get_rabbitmq_pool() {
+        Ok(pool) => pool,
+        Err(e) => {
+            error!("Inbox consumer cannot start: {}", e);
+            return;
+        }
+    };
+    futures_util::future::join_all(pool.tenants().iter().map(tenant_inbox_consumer)).await;
+}
+
+async fn tenant_inbox_consumer(tenant: &'static TenantPool) {
+    loop {
+        if let Err(e) = consume_inbox_entries(tenant).await {
+            error!("Inbox consumer for tenant {} failed: {}", tenant.name(), e);
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+    }
+}
+
+async fn consume_inbox_entries(tenant: &TenantPool) -> Result<(), Error> {
+    // Consuming without a store would only requeue every event
+    get_inbox_store()?;
+    let config = init_config()?;
+    let channel = tenant.get_channel().await?;
+    let queue = tenant
+        .topology()
+        .declare_inbox_queue(&channel, &config.instance_id)
+        .await?;
+
+    let mut consumer = channel
+        .basic_consume(
+            &queue,
+            "inbox_consumer",
+            BasicConsumeOptions::default(),
+            FieldTable::default(),
+        )
+        .await?;
+
+    while let Some(delivery) = consumer.next().await {
+        let delivery = delivery?;
+        let event = match serde_json::from_slice::<InboxEvent>(&delivery.data) {
+            Ok(event) => event,
+            Err(e) => {
+                error!("❌ Error deserializing inbox event: {}", e);
+                delivery
+                    .nack(BasicNackOptions {
+                        requeue: false,
+                        ..Default::default()
+                    })
+                    .await?;
+                continue;
+            }
+        };
+
+        // The queue, not the payload, decides which tenant an event belongs to
+        let tenant_name = tenant.name().to_string();
+        let result = with_inbox(move |store| store.apply(&tenant_name, event)).await;
+        match result {
+            Ok(_) => delivery.ack(BasicAckOptions::default()).await?,
+            Err(e) => {
+                // Keep the event in the queue until the store is writable again
+                warn!("Failed to store inbox event, requeueing: {}", e);
+                delivery
+                    .nack(BasicNackOptions {
+                        requeue: true,
+                        ..Default::default()
+                    })
+                    .await?;
+                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+            }
+        }
+    }
+
+    warn!("Inbox consumer stream for tenant {} ended", tenant.name());
+    Ok(())
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct InboxQuery {
+    /// 1-based page number.
+    #[serde(default = "default_page")]
+    pub page: usize,
+    /// Entries per page, at most 100.
+    #[serde(default = "default_per_page")]
+    pub per_page: usize,
+    #[serde(default)]
+    pub unread_only: bool,
+}
+
+/// A page of a user's inbox, newest entries first.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct InboxPage {
+    pub user_id: String,
+    pub items: Vec<InboxEntry>,
+    pub page: usize,
+    pub per_page: usize,
+    /// Entries matching the query across all pages.
+    pub total: usize,
+    pub unread: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UnreadCount {
+    pub user_id: String,
+    pub unread: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MarkedRead {
+    pub user_id: String,
+    pub marked_read: usize,
+    pub unread: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EntryRead {
+    pub entry: InboxEntry,
+    pub unread: usize,
+}
+
+fn default_page() -> usize {
+    1
+}
+
+fn default_per_page() -> usize {
+    20
+}
+
+const MAX_PER_PAGE: usize = 100;
+
+// Tenant whose inbox of the user the caller works on: its own, and only for users it may target
+fn inbox_tenant(auth: Option<&AuthContext>, user_id: &str) -> Result<String, Error> {
+    ensure_user_permitted(auth, user_id)?;
+    Ok(resolve_tenant(auth)?.name)
+}
+
+/// A page of the user's in-app inbox.
+#[utoipa::path(
+    tag = "inbox",
+    params(("user_id" = String, Path), InboxQuery),
+    responses(
+        (status = 200, description = "Inbox page", body = InboxPage),
+        (status = 400, description = "Invalid query", body = ErrorBody),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+#[get("/users/{user_id}/inbox")]
+pub async fn get_inbox(
+    path: web::Path<String>,
+    query: web::Query<InboxQuery>,
+    auth: Option<web::ReqData<AuthContext>>,
+) -> Result<HttpResponse, Error> {
+    let user_id = path.into_inner();
+    let tenant = &inbox_tenant(auth.as_deref(), &user_id)?;
+    let page = query.page.max(1);
+    let per_page = query.per_page.clamp(1, MAX_PER_PAGE);
+
+    let store = lock_inbox()?;
+    let (items, total) = store.page(tenant, &user_id, page, per_page, query.unread_only);
+    let unread = store.unread_count(tenant, &user_id);
+    Ok(HttpResponse::Ok().json(InboxPage {
+        user_id,
+        items,
+        page,
+        per_page,
+        total,
+        unread,
+    }))
+}
+
+#[utoipa::path(
+    tag = "inbox",
+    params(("user_id" = String, Path)),
+    responses((status = 200, description = "Unread inbox entries", body = UnreadCount)),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+#[get("/users/{user_id}/inbox/unread-count")]
+pub async fn get_inbox_unread_count(
+    path: web::Path<String>,
+    auth: Option<web::ReqData<AuthContext>>,
+) -> Result<HttpResponse, Error> {
+    let user_id = path.into_inner();
+    let tenant = &inbox_tenant(auth.as_deref(), &user_id)?;
+    let store = lock_inbox()?;
+    let unread = store.unread_count(tenant, &user_id);
+    Ok(HttpResponse::Ok().json(UnreadCount { user_id, unread }))
+}
+
+#[utoipa::path(
+    tag = "inbox",
+    params(("user_id" = String, Path)),
+    responses((status = 200, description = "Every entry marked as read", body = MarkedRead)),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+#[post("/users/{user_id}/inbox/read-all")]
+pub async fn mark_inbox_read(
+    path: web::Path<String>,
+    auth: Option<web::ReqData<AuthContext>>,
+) -> Result<HttpResponse, Error> {
+    let user_id = path.into_inner();
+    let tenant = &inbox_tenant(auth.as_deref(), &user_id)?;
+    let read_at = Utc::now();
+    let marked = {
+        let (tenant, user_id) = (tenant.clone(), user_id.clone());
+        with_inbox(move |store| store.mark_all_read(&tenant, &user_id, read_at)).await?
+    };
+    share_read_marker(
+        tenant,
+        InboxEvent::Read {
+            user_id: user_id.clone(),
+            notification_id: None,
+            read_at,
+        },
+    )
+    .await;
+
+    info!(
+        "📬 Marked {} inbox entries as read for user: {}",
+        marked, user_id
+    );
+    Ok(HttpResponse::Ok().json(MarkedRead {
+        user_id,
+        marked_read: marked,
+        unread: 0,
+    }))
+}
+
+#[utoipa::path(
+    tag = "inbox",
+    params(("user_id" = String, Path), ("notification_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Entry marked as read", body = EntryRead),
+        (status = 404, description = "No such entry in the user's inbox", body = ErrorBody),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+#[post("/users/{user_id}/inbox/{notification_id}/read")]
+pub async fn mark_inbox_entry_read(
+    path: web::Path<(String, Uuid)>,
+    auth: Option<web::ReqData<AuthContext>>,
+) -> Result<HttpResponse, Error> {
+    let (user_id, notification_id) = path.into_inner();
+    let tenant = &inbox_tenant(auth.as_deref(), &user_id)?;
+    let read_at = Utc::now();
+    let (entry, unread) = {
+        let (tenant, user_id) = (tenant.clone(), user_id.clone());
+        with_inbox(move |store| {
+            let entry = store.mark_read(&tenant, &user_id, &notification_id, read_at)?;
+            Ok((entry, store.unread_count(&tenant, &user_id)))
+        })
+        .await?
+    };
+    let Some(entry) = entry else {
+        return Err(Error::NotFound(format!(
+            "Inbox entry {} not found for user {}",
+            notification_id, user_id
+        )));
+    };
+    share_read_marker(
+        tenant,
+        InboxEvent::Read {
+            user_id,
+            notification_id: Some(notification_id),
+            read_at: entry.read_at.unwrap_or(read_at),
+        },
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(EntryRead { entry, unread }))
+}
+
+// Tells the other servers about a read so every replica shows the same unread count. The
+// local store already has it; while the broker is down the other replicas miss the read.
+async fn share_read_marker(tenant: &str, event: InboxEvent) {
+    let Some(pool) = get_rabbitmq_pool().ok().and_then(|pool| pool.tenant(tenant)) else {
+        return;
+    };
+    if let Err(e) = publish_inbox_event(pool, &event).await {
+        warn!("Failed to share inbox read marker with the other servers: {}", e);
+    }
+}
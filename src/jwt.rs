@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use jsonwebtoken::jwk::{AlgorithmParameters, JwkSet};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::{error, info, warn};
+
+use crate::auth::{AuthContext, AuthMethod, Scope};
+use crate::config::{Config, init_config};
+use crate::secret::SecretString;
+
+/// Where verification keys come from. The files are re-read on every reload; a shared secret
+/// is set at startup.
+#[derive(Debug, Clone)]
+enum KeySource {
+    Jwks(String),
+    Pem(String),
+    Secret(SecretString),
+}
+
+struct VerificationKey {
+    kid: Option<String>,
+    key: DecodingKey,
+    algorithms: Vec<Algorithm>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    /// OAuth style, space separated.
+    #[serde(default)]
+    scope: Option<String>,
+    #[serde(default)]
+    scopes: Option<Vec<String>>,
+    #[serde(flatten)]
+    extra: HashMap<String, Value>,
+}
+
+/// Validates bearer tokens issued by the gateway against a local JWKS file, PEM key or shared
+/// secret.
+pub struct JwtVerifier {
+    source: KeySource,
+    keys: RwLock<Vec<VerificationKey>>,
+    issuer: Option<String>,
+    audience: Option<String>,
+    tenant_claim: String,
+}
+
+impl JwtVerifier {
+    /// Builds a verifier from the configuration; `None` when JWT auth is not configured.
+    pub fn from_config(config: &Config) -> Result<Option<Self>, String> {
+        let source = match (&config.jwt_jwks_path, &config.jwt_public_key_path) {
+            (Some(path), _) => KeySource::Jwks(path.clone()),
+            (None, Some(path)) => KeySource::Pem(path.clone()),
+            (None, None) => match &config.jwt_hmac_secret {
+                Some(secret) => KeySource::Secret(secret.clone()),
+                None => return Ok(None),
+            },
+        };
+        let keys = load_keys(&source)?;
+        Ok(Some(JwtVerifier {
+            source,
+            keys: RwLock::new(keys),
+            issuer: config.jwt_issuer.clone(),
+            audience: config.jwt_audience.clone(),
+            tenant_claim: config.jwt_tenant_claim.clone(),
+        }))
+    }
+
+    /// Re-reads the key file. The previous keys stay active if the file is unreadable.
+    pub fn reload(&self) -> Result<usize, String> {
+        let keys = load_keys(&self.source)?;
+        let count = keys.len();
+        let mut current = self
+            .keys
+            .write()
+            .map_err(|e| format!("Failed to lock JWT keys: {}", e))?;
+        *current = keys;
+        Ok(count)
+    }
+
+    pub fn verify(&self, token: &str) -> Result<AuthContext, String> {
+        let header = decode_header(token).map_err(|e| format!("Malformed token: {}", e))?;
+        let keys = self
+            .keys
+            .read()
+            .map_err(|e| format!("Failed to lock JWT keys: {}", e))?;
+
+        let mut validation = Validation::new(header.alg);
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        let candidates = keys.iter().filter(|k| {
+            k.algorithms.contains(&header.alg)
+                && (header.kid.is_none() || k.kid.is_none() || k.kid == header.kid)
+        });
+
+        let mut last_error = "No key matches the token's kid and algorithm".to_string();
+        for candidate in candidates {
+            match decode::<Claims>(token, &candidate.key, &validation) {
+                Ok(data) => return self.context_from_claims(data.claims),
+                Err(e) => last_error = format!("Invalid token: {}", e),
+            }
+        }
+        Err(last_error)
+    }
+
+    fn context_from_claims(&self, claims: Claims) -> Result<AuthContext, String> {
+        let tenant = claims
+            .extra
+            .get(&self.tenant_claim)
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| format!("Token has no '{}' claim", self.tenant_claim))?
+            .to_string();
+
+        let names: Vec<String> = match (claims.scope, claims.scopes) {
+            (_, Some(scopes)) => scopes,
+            (Some(scope), None) => scope.split_whitespace().map(str::to_string).collect(),
+            (None, None) => Vec::new(),
+        };
+        // Scopes meant for other services are ignored
+        let scopes = names
+            .iter()
+            .filter_map(|name| serde_json::from_value::<Scope>(Value::String(name.clone())).ok())
+            .collect();
+
+        Ok(AuthContext {
+            key_id: format!("jwt:{}", claims.sub),
+            tenant,
+            scopes,
+            callback_url: None,
+            callback_secret_id: None,
+            method: AuthMethod::Jwt,
+        })
+    }
+}
+
+fn load_keys(source: &KeySource) -> Result<Vec<VerificationKey>, String> {
+    match source {
+        KeySource::Jwks(path) => {
+            let bytes =
+                std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+            let set: JwkSet = serde_json::from_slice(&bytes)
+                .map_err(|e| format!("Failed to parse JWKS {}: {}", path, e))?;
+            let mut keys = Vec::new();
+            for jwk in &set.keys {
+                let key = match DecodingKey::from_jwk(jwk) {
+                    Ok(key) => key,
+                    Err(e) => {
+                        warn!("Skipping unusable JWK {:?}: {}", jwk.common.key_id, e);
+                        continue;
+                    }
+                };
+                let algorithms = match jwk
+                    .common
+                    .key_algorithm
+                    .and_then(|alg| Algorithm::from_str(&alg.to_string()).ok())
+                {
+                    Some(alg) => vec![alg],
+                    None => algorithms_for(&jwk.algorithm),
+                };
+                keys.push(VerificationKey {
+                    kid: jwk.common.key_id.clone(),
+                    key,
+                    algorithms,
+                });
+            }
+            if keys.is_empty() {
+                return Err(format!("No usable keys in JWKS {}", path));
+            }
+            Ok(keys)
+        }
+        KeySource::Secret(secret) => Ok(vec![VerificationKey {
+            kid: None,
+            key: DecodingKey::from_secret(secret.expose_secret().as_bytes()),
+            algorithms: vec![Algorithm::HS256, Algorithm::HS384, Algorithm::HS512],
+        }]),
+        KeySource::Pem(path) => {
+            let pem = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+            let (key, algorithms) = if let Ok(key) = DecodingKey::from_rsa_pem(&pem) {
+                let algorithms = vec![
+                    Algorithm::RS256,
+                    Algorithm::RS384,
+                    Algorithm::RS512,
+                    Algorithm::PS256,
+                    Algorithm::PS384,
+                    Algorithm::PS512,
+                ];
+                (key, algorithms)
+            } else if let Ok(key) = DecodingKey::from_ec_pem(&pem) {
+                (key, vec![Algorithm::ES256, Algorithm::ES384])
+            } else if let Ok(key) = DecodingKey::from_ed_pem(&pem) {
+                (key, vec![Algorithm::EdDSA])
+            } else {
+                return Err(format!("{} is not an RSA, EC or Ed25519 public key", path));
+            };
+            Ok(vec![VerificationKey {
+                kid: None,
+                key,
+                algorithms,
+            }])
+        }
+    }
+}
+
+fn algorithms_for(parameters: &AlgorithmParameters) -> Vec<Algorithm> {
+    match parameters {
+        AlgorithmParameters::RSA(_) => vec![
+            Algorithm::RS256,
+            Algorithm::RS384,
+            Algorithm::RS512,
+            Algorithm::PS256,
+            Algorithm::PS384,
+            Algorithm::PS512,
+        ],
+        AlgorithmParameters::EllipticCurve(_) => vec![Algorithm::ES256, Algorithm::ES384],
+        AlgorithmParameters::OctetKeyPair(_) => vec![Algorithm::EdDSA],
+        // Shared secrets are not accepted from a public key set
+        AlgorithmParameters::OctetKey(_) => Vec::new(),
+    }
+}
+
+// Singleton global; `None` when JWT authentication is not configured
+lazy_static::lazy_static! {
+    static ref JWT_VERIFIER: tokio::sync::OnceCell<Option<JwtVerifier>> = tokio::sync::OnceCell::new();
+}
+
+pub fn init_jwt_verifier() -> Result<(), Box<dyn std::error::Error>> {
+    let config = init_config()?;
+    let verifier = JwtVerifier::from_config(&config)?;
+    if let Some(verifier) = &verifier {
+        info!(
+            "🔑 Loaded {} JWT verification keys",
+            verifier.keys.read().map(|k| k.len()).unwrap_or(0)
+        );
+    }
+    JWT_VERIFIER
+        .set(verifier)
+        .map_err(|_| "Failed to set JWT verifier")?;
+    Ok(())
+}
+
+pub fn get_jwt_verifier() -> Option<&'static JwtVerifier> {
+    JWT_VERIFIER.get().and_then(Option::as_ref)
+}
+
+/// Periodically reloads the JWKS/PEM file so rotated keys are picked up without a restart.
+pub async fn jwt_key_reload_task() {
+    let Some(verifier) = get_jwt_verifier() else {
+        return;
+    };
+    if let KeySource::Secret(_) = verifier.source {
+        return;
+    }
+    let interval_secs = init_config()
+        .map(|c| c.jwt_reload_interval_secs)
+        .unwrap_or(300);
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        match verifier.reload() {
+            Ok(count) => info!("🔄 Reloaded {} JWT verification keys", count),
+            Err(e) => error!(
+                "Failed to reload JWT keys, keeping the previous ones: {}",
+                e
+            ),
+        }
+    }
+}
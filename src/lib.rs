@@ -3,3 +3,24 @@ pub mod connection;
 pub mod handlers;
 pub mod worker_utils;
 pub mod config;
+pub mod topology;
+pub mod tenant;
+pub mod status;
+pub mod callbacks;
+pub mod stream;
+pub mod store;
+pub mod inbox;
+pub mod outbox;
+pub mod dlq;
+pub mod auth;
+pub mod jwt;
+pub mod error;
+pub mod validation;
+pub mod openapi;
+pub mod health;
+pub mod metrics;
+pub mod telemetry;
+pub mod admin;
+pub mod secret;
+pub mod settings;
+pub mod tls;
@@ -1,32 +1,75 @@
-mod connection;
-mod handlers;
-mod models;
-mod config;
-
-use actix_web::{App, HttpServer, middleware::Logger};
-use connection::init_rabbitmq_pool;
-use config::Config;
-use handlers::{
-    notification_scheduler_task, schedule_notification, send_notification, send_notification_at,
-    send_notification_delayed,
+use actix_web::{App, HttpServer, middleware::{Logger, from_fn}, web};
+use integration_rust_rabbitmq::admin::{
+    get_dlq_audit, get_dlq_message, get_log_level, list_dlq, purge_dlq, replay_dlq, set_log_level,
 };
+use integration_rust_rabbitmq::auth::{authenticate, check_auth_configured, init_api_keys};
+use integration_rust_rabbitmq::callbacks::init_callback_secrets;
+use integration_rust_rabbitmq::jwt::{init_jwt_verifier, jwt_key_reload_task};
+use integration_rust_rabbitmq::connection::{connect_rabbitmq_pool_task, init_lazy_rabbitmq_pool};
+use integration_rust_rabbitmq::config::{config_reload_task, init_config_with, print_config};
+use integration_rust_rabbitmq::error::{json_error_handler, path_error_handler, query_error_handler};
+use integration_rust_rabbitmq::handlers::{
+    get_notification_status, notification_scheduler_task, schedule_notification, send_notification,
+    send_notification_at, send_notification_delayed,
+};
+use integration_rust_rabbitmq::inbox::{
+    get_inbox, get_inbox_unread_count, inbox_consumer_task, init_inbox_store, mark_inbox_entry_read,
+    mark_inbox_read,
+};
+use integration_rust_rabbitmq::outbox::{init_outbox_store, outbox_relay_task};
+use integration_rust_rabbitmq::health::{healthz, readyz};
+use integration_rust_rabbitmq::metrics::metrics;
+use integration_rust_rabbitmq::openapi::{docs, openapi_json};
+use integration_rust_rabbitmq::secret::RedactedUrl;
+use integration_rust_rabbitmq::settings::Sources;
+use integration_rust_rabbitmq::status::status_consumer_task;
+use integration_rust_rabbitmq::telemetry::{init_tracing, shutdown as shutdown_tracing, trace_request};
+use integration_rust_rabbitmq::stream::{stream_notification_status, stream_notification_status_ws};
 use tokio::task;
 use tracing::{error, info};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    let sources = Sources::from_args(
+        "notification-server",
+        "HTTP API that publishes notifications to RabbitMQ",
+    );
+    if sources.print_config {
+        match print_config(&sources) {
+            Ok(toml) => print!("{}", toml),
+            Err(e) => {
+                eprintln!("❌ Invalid configuration: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    // Load configuration; tracing needs it to know where spans go
+    let config = init_config_with(&sources);
+
     // Configure tracing
-    tracing_subscriber::fmt()
-        .with_env_filter("info,integration_rust_rabbitmq=debug,actix_web=info")
-        .init();
+    let tracer_provider = match init_tracing(
+        config.as_deref().ok(),
+        "notification-server",
+        "info,integration_rust_rabbitmq=debug,actix_web=info",
+    ) {
+        Ok(provider) => provider,
+        Err(e) => {
+            eprintln!("❌ Failed to set up tracing: {}", e);
+            std::process::exit(1);
+        }
+    };
 
     info!("🚀 Starting notification service...");
 
-    // Load configuration
-    let config = match Config::from_env() {
+    let config = match config {
         Ok(config) => {
             info!("✅ Configuration loaded successfully");
-            info!("📊 RabbitMQ URL: {}", config.rabbitmq_url);
+            info!("📊 RabbitMQ URL: {}", RedactedUrl(config.rabbitmq_url.expose_secret()));
+            if !config.rabbitmq_nodes.is_empty() {
+                info!("📊 RabbitMQ nodes ({:?}): {}", config.rabbitmq_node_order, config.rabbitmq_nodes.join(", "));
+            }
             info!("🌐 Server will bind to {}:{}", config.server_host, config.server_port);
             config
         }
@@ -36,29 +79,108 @@ async fn main() -> std::io::Result<()> {
         }
     };
 
-    // Initialize connection pool
-    if let Err(e) = init_rabbitmq_pool().await {
-        error!("❌ Failed to initialize RabbitMQ pool: {}", e);
+    if let Err(e) = init_api_keys() {
+        error!("❌ Failed to load API keys: {}", e);
         std::process::exit(1);
     }
 
-    info!("✅ RabbitMQ connection pool initialized");
+    if let Err(e) = init_jwt_verifier() {
+        error!("❌ Failed to load JWT verification keys: {}", e);
+        std::process::exit(1);
+    }
+
+    if let Err(e) = check_auth_configured() {
+        error!("❌ Refusing to start without authentication: {}", e);
+        std::process::exit(1);
+    }
+
+    if let Err(e) = init_callback_secrets() {
+        error!("❌ Failed to load callback secrets: {}", e);
+        std::process::exit(1);
+    }
+
+    if let Err(e) = init_inbox_store() {
+        error!("❌ Failed to open inbox store: {}", e);
+        std::process::exit(1);
+    }
+
+    if let Err(e) = init_outbox_store() {
+        error!("❌ Failed to open outbox store: {}", e);
+        std::process::exit(1);
+    }
+
+    // Connections open in the background, so the API accepts notifications during an outage
+    if let Err(e) = init_lazy_rabbitmq_pool() {
+        error!("❌ Failed to initialize RabbitMQ pool: {}", e);
+        std::process::exit(1);
+    }
+    task::spawn(connect_rabbitmq_pool_task());
 
     // Launch background scheduler
     info!("📅 Starting notification scheduler task");
     task::spawn(notification_scheduler_task());
 
+    // Consume delivery status events from the worker
+    task::spawn(status_consumer_task());
+
+    // Store delivered notifications in the users' inboxes
+    task::spawn(inbox_consumer_task());
+
+    // Publish accepted notifications, retrying while the broker is down
+    task::spawn(outbox_relay_task());
+
+    // Pick up rotated JWT keys
+    task::spawn(jwt_key_reload_task());
+
+    // Apply safe configuration changes on SIGHUP
+    task::spawn(config_reload_task());
+
     info!("🌐 Starting HTTP server at http://{}:{}", config.server_host, config.server_port);
 
-    HttpServer::new(|| {
+    let max_body_bytes = config.max_body_bytes;
+    HttpServer::new(move || {
         App::new()
+            // Malformed requests get the same JSON error body as validation failures
+            .app_data(
+                web::JsonConfig::default()
+                    .limit(max_body_bytes)
+                    .error_handler(json_error_handler),
+            )
+            .app_data(web::QueryConfig::default().error_handler(query_error_handler))
+            .app_data(web::PathConfig::default().error_handler(path_error_handler))
+            .wrap(from_fn(authenticate))
             .wrap(Logger::default())
+            .wrap(from_fn(trace_request))
             .service(send_notification_delayed)
             .service(send_notification)
             .service(schedule_notification)
             .service(send_notification_at)
+            // Stream routes first so "stream" is not taken for a notification id
+            .service(stream_notification_status_ws)
+            .service(stream_notification_status)
+            .service(get_notification_status)
+            .service(get_inbox)
+            .service(get_inbox_unread_count)
+            .service(mark_inbox_read)
+            .service(mark_inbox_entry_read)
+            .service(healthz)
+            .service(readyz)
+            .service(metrics)
+            .service(openapi_json)
+            .service(docs())
+            .service(get_log_level)
+            .service(set_log_level)
+            .service(list_dlq)
+            // Before `/admin/dlq/{id}`, which would match it
+            .service(get_dlq_audit)
+            .service(get_dlq_message)
+            .service(replay_dlq)
+            .service(purge_dlq)
     })
     .bind((config.server_host.as_str(), config.server_port))?
     .run()
-    .await
+    .await?;
+
+    shutdown_tracing(tracer_provider);
+    Ok(())
 }
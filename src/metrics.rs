@@ -0,0 +1,140 @@
+use actix_web::{HttpResponse, get};
+use prometheus::{
+    Encoder, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, TextEncoder,
+    exponential_buckets, register_histogram, register_histogram_vec, register_int_counter,
+    register_int_counter_vec, register_int_gauge,
+};
+
+// Each metric joins the default registry the first time it is used, so a binary only exposes
+// the metrics it records.
+lazy_static::lazy_static! {
+    /// Notifications handed to the broker, by notification type.
+    pub static ref MESSAGES_PUBLISHED: IntCounterVec = register_int_counter_vec!(
+        "notify_messages_published_total",
+        "Notifications published to the broker",
+        &["type"]
+    )
+    .expect("metric can be registered");
+    /// Notifications the broker confirmed, by notification type.
+    pub static ref MESSAGES_CONFIRMED: IntCounterVec = register_int_counter_vec!(
+        "notify_messages_confirmed_total",
+        "Notifications confirmed by the broker",
+        &["type"]
+    )
+    .expect("metric can be registered");
+    /// Notifications that could not be published or were not confirmed, by notification type.
+    pub static ref MESSAGES_FAILED: IntCounterVec = register_int_counter_vec!(
+        "notify_messages_failed_total",
+        "Notifications that failed to publish",
+        &["type"]
+    )
+    .expect("metric can be registered");
+
+    pub static ref SCHEDULER_CYCLE_DURATION: Histogram = register_histogram!(
+        "notify_scheduler_cycle_duration_seconds",
+        "Duration of a scheduler cycle",
+        exponential_buckets(0.001, 2.0, 14).expect("valid buckets")
+    )
+    .expect("metric can be registered");
+    /// Scheduled notifications found due in the last cycle.
+    pub static ref SCHEDULER_DUE_BACKLOG: IntGauge = register_int_gauge!(
+        "notify_scheduler_due_backlog",
+        "Scheduled notifications due in the last scheduler cycle"
+    )
+    .expect("metric can be registered");
+
+    /// Settled deliveries by outcome: `ack`, `rescheduled`, `requeue`, `dead_letter` or `timeout`.
+    pub static ref WORKER_DELIVERIES: IntCounterVec = register_int_counter_vec!(
+        "notify_worker_deliveries_total",
+        "Deliveries settled by the worker, by outcome",
+        &["outcome"]
+    )
+    .expect("metric can be registered");
+    pub static ref WORKER_PROCESSING_DURATION: HistogramVec = register_histogram_vec!(
+        "notify_worker_processing_duration_seconds",
+        "Time spent delivering a notification, by notification type",
+        &["type"],
+        exponential_buckets(0.005, 2.0, 14).expect("valid buckets")
+    )
+    .expect("metric can be registered");
+    /// From `created_at` to delivery, so it includes the requested delay of delayed and
+    /// scheduled notifications.
+    pub static ref DELIVERY_LAG: HistogramVec = register_histogram_vec!(
+        "notify_delivery_lag_seconds",
+        "Time from acceptance to delivery, by notification type",
+        &["type"],
+        exponential_buckets(0.01, 2.0, 20).expect("valid buckets")
+    )
+    .expect("metric can be registered");
+
+    /// Accepted notifications not yet confirmed by the broker.
+    pub static ref OUTBOX_SIZE: IntGauge = register_int_gauge!(
+        "notify_outbox_size",
+        "Notifications in the outbox waiting for the broker"
+    )
+    .expect("metric can be registered");
+    /// Notifications refused because the outbox was full.
+    pub static ref OUTBOX_REJECTED: IntCounter = register_int_counter!(
+        "notify_outbox_rejected_total",
+        "Notifications refused because the outbox was full"
+    )
+    .expect("metric can be registered");
+    /// Dead-lettered messages replayed or purged through the admin API, by action.
+    pub static ref DLQ_ACTIONS: IntCounterVec = register_int_counter_vec!(
+        "notify_dlq_messages_total",
+        "Dead-lettered messages replayed or purged",
+        &["action"]
+    )
+    .expect("metric can be registered");
+
+    pub static ref RABBITMQ_RECONNECTS: IntCounterVec = register_int_counter_vec!(
+        "notify_rabbitmq_reconnects_total",
+        "Broker connections re-established after being lost, by tenant",
+        &["tenant"]
+    )
+    .expect("metric can be registered");
+}
+
+/// Counts one publish of a notification. `published` is false when `basic_publish` itself
+/// failed; anything not `confirmed` counts as failed.
+pub fn record_publish(notification_type: &str, published: bool, confirmed: bool) {
+    if published {
+        MESSAGES_PUBLISHED
+            .with_label_values(&[notification_type])
+            .inc();
+    }
+    if confirmed {
+        MESSAGES_CONFIRMED
+            .with_label_values(&[notification_type])
+            .inc();
+    } else {
+        MESSAGES_FAILED
+            .with_label_values(&[notification_type])
+            .inc();
+    }
+}
+
+pub fn record_delivery(outcome: &str) {
+    WORKER_DELIVERIES.with_label_values(&[outcome]).inc();
+}
+
+/// Prometheus text format of every registered metric.
+pub fn render() -> String {
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&prometheus::gather(), &mut buffer) {
+        tracing::error!("Failed to encode metrics: {}", e);
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+/// Prometheus scrape endpoint.
+#[utoipa::path(
+    tag = "health",
+    responses((status = 200, description = "Metrics in the Prometheus text format", content_type = "text/plain", body = String))
+)]
+#[get("/metrics")]
+pub async fn metrics() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(TextEncoder::new().format_type())
+        .body(render())
+}
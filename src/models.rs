@@ -3,21 +3,121 @@ use std::sync::Mutex;
 use uuid::Uuid;
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::error::{Error, FieldError};
+
+pub const DEFAULT_CHANNEL: &str = "push";
+
+fn default_channel() -> String {
+    DEFAULT_CHANNEL.to_string()
+}
+
+/// Delivery priority. Maps onto the AMQP `priority` property of priority-enabled queues.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// Highest AMQP priority used; queues are declared with this `x-max-priority`.
+pub const MAX_PRIORITY: u8 = 9;
+
+impl Priority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+        }
+    }
+
+    pub fn amqp_priority(&self) -> u8 {
+        match self {
+            Priority::Low => 1,
+            Priority::Normal => 5,
+            Priority::High => MAX_PRIORITY,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct Notification {
+    /// Assigned by the server when the notification is accepted.
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
     pub user_id: String,
     pub message: String,
     #[serde(default)]
     pub delay_secs: u64,
     #[serde(default)]
     pub notification_type: String, // "immediate", "delayed", "scheduled"
+    #[serde(default = "default_channel")]
+    pub channel: String, // "push", "email", "sms", ...
+    #[serde(default)]
+    pub priority: Priority,
+    /// Absolute expiry; the notification is discarded instead of delivered after it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Relative expiry, counted from the moment the notification becomes due.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_secs: Option<u64>,
+    /// Receives a signed POST once the notification is delivered, failed or expired.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
+    /// Id of the tenant's callback secret (see `CALLBACK_SECRETS_PATH`) that signs the callback.
+    /// The secret itself stays on the servers and never travels with the notification.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callback_secret_id: Option<String>,
+    /// Set by the server when the notification is accepted, or published by the scheduler.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl Notification {
+    /// Turns `ttl_secs` into an absolute `expires_at` for a notification due at `due_at`.
+    /// An explicit `expires_at` wins over `ttl_secs`.
+    pub fn resolve_expiry(&mut self, due_at: DateTime<Utc>) -> Result<(), Error> {
+        if self.expires_at.is_none()
+            && let Some(ttl) = self.ttl_secs
+        {
+            let expires_at = expiry_after(due_at, ttl).ok_or_else(|| {
+                Error::Validation(vec![FieldError::new("ttl_secs", "is out of range")])
+            })?;
+            self.expires_at = Some(expires_at);
+        }
+        Ok(())
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// `ttl_secs` after `due_at`, or `None` when that is beyond the representable dates.
+pub fn expiry_after(due_at: DateTime<Utc>, ttl_secs: u64) -> Option<DateTime<Utc>> {
+    let ttl = chrono::Duration::try_seconds(i64::try_from(ttl_secs).ok()?)?;
+    due_at.checked_add_signed(ttl)
+}
+
+impl Notification {
+    /// Value for the AMQP `expiration` property of a message that reaches its queue at `due_at`.
+    /// RabbitMQ only starts the TTL once the delayed exchange hands the message over.
+    pub fn amqp_expiration(&self, due_at: DateTime<Utc>) -> Option<String> {
+        self.expires_at
+            .map(|expires_at| (expires_at - due_at).num_milliseconds().max(0).to_string())
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ScheduledNotification {
     pub id: Uuid,
     pub user_id: String,
+    /// Tenant whose exchange the notification is published on when due.
+    pub tenant: String,
     pub scheduled_at: DateTime<Utc>,
     pub payload: serde_json::Value,
     pub status: String,
@@ -28,16 +128,46 @@ lazy_static::lazy_static! {
     pub static ref SCHEDULED_NOTIFICATIONS: Mutex<HashMap<Uuid, ScheduledNotification>> = Mutex::new(HashMap::new());
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ScheduleNotificationRequest {
     pub user_id: String,
     pub scheduled_at: DateTime<Utc>,
+    /// A notification, delivered as is when due.
+    #[schema(value_type = Notification)]
     pub payload: serde_json::Value,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ScheduleAtRequest {
     pub user_id: String,
     pub message: String,
     pub scheduled_at: DateTime<Utc>,
+    #[serde(default = "default_channel")]
+    pub channel: String,
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub ttl_secs: Option<u64>,
+    #[serde(default)]
+    pub callback_url: Option<String>,
+    #[serde(default)]
+    pub callback_secret_id: Option<String>,
+}
+
+/// Response of the endpoints that accept a notification for delivery.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NotificationAccepted {
+    pub id: Uuid,
+    pub status: String,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub notification_type: Option<String>,
+    pub user_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delay_seconds: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheduled_at: Option<DateTime<Utc>>,
 }
@@ -0,0 +1,86 @@
+use actix_web::{HttpResponse, get};
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+use utoipa_redoc::{Redoc, Servable};
+
+use crate::{admin, handlers, health, inbox, metrics, stream};
+
+/// OpenAPI document for every route registered in `main.rs`.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Notification service",
+        description = "Publishes notifications to RabbitMQ and reports their delivery status."
+    ),
+    paths(
+        handlers::send_notification,
+        handlers::send_notification_delayed,
+        handlers::send_notification_at,
+        handlers::schedule_notification,
+        handlers::get_notification_status,
+        stream::stream_notification_status,
+        stream::stream_notification_status_ws,
+        inbox::get_inbox,
+        inbox::get_inbox_unread_count,
+        inbox::mark_inbox_read,
+        inbox::mark_inbox_entry_read,
+        health::healthz,
+        health::readyz,
+        metrics::metrics,
+        admin::get_log_level,
+        admin::set_log_level,
+        admin::list_dlq,
+        admin::get_dlq_audit,
+        admin::get_dlq_message,
+        admin::replay_dlq,
+        admin::purge_dlq,
+    ),
+    modifiers(&SecuritySchemes),
+    tags(
+        (name = "notifications", description = "Sending and scheduling notifications"),
+        (name = "status", description = "Delivery status lookups and streams"),
+        (name = "inbox", description = "Users' in-app inboxes"),
+        (name = "health", description = "Probes and metrics"),
+        (name = "admin", description = "Operating the service; requires the admin scope"),
+    )
+)]
+pub struct ApiDoc;
+
+/// API keys (plain or HMAC-signed, see the README) and gateway JWTs.
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(
+                crate::auth::API_KEY_HEADER,
+            ))),
+        );
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
+
+// Built once; the document only depends on the code
+lazy_static::lazy_static! {
+    static ref OPENAPI: utoipa::openapi::OpenApi = ApiDoc::openapi();
+}
+
+#[get("/openapi.json")]
+pub async fn openapi_json() -> HttpResponse {
+    HttpResponse::Ok().json(&*OPENAPI)
+}
+
+/// Redoc UI for the spec at `/docs`.
+pub fn docs() -> Redoc<utoipa::openapi::OpenApi> {
+    Redoc::with_url("/docs", OPENAPI.clone())
+}
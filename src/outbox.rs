@@ -0,0 +1,392 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use lapin::{
+    BasicProperties, Channel,
+    types::{AMQPValue, FieldTable},
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use tracing::{Instrument, Span, error, info, warn};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use uuid::Uuid;
+
+use crate::config::{get_config, init_config};
+use crate::connection::{TenantPool, get_rabbitmq_pool, publish_confirmed};
+use crate::error::Error;
+use crate::metrics;
+use crate::models::Notification;
+use crate::status::{self, DeliveryState, StatusEvent};
+use crate::store::JsonLog;
+use crate::telemetry;
+use crate::topology::Topology;
+
+/// A notification the API accepted and that waits to be published on its tenant's delayed
+/// exchange.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OutboxEntry {
+    pub id: Uuid,
+    pub tenant: String,
+    pub user_id: String,
+    pub notification_type: String,
+    pub routing_key: String,
+    /// The message body.
+    pub payload: serde_json::Value,
+    pub priority: u8,
+    /// When the delayed exchange should hand the message to its queue.
+    pub release_at: DateTime<Utc>,
+    /// Sets the message TTL, counted from `release_at`.
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    pub accepted_at: DateTime<Utc>,
+    /// `traceparent` of the request that accepted the notification, so the published message
+    /// continues its trace.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub trace_context: HashMap<String, String>,
+}
+
+impl OutboxEntry {
+    pub fn new(
+        tenant: &str,
+        notification: &Notification,
+        release_at: DateTime<Utc>,
+    ) -> Result<Self, Error> {
+        Ok(OutboxEntry {
+            id: notification.id,
+            tenant: tenant.to_string(),
+            user_id: notification.user_id.clone(),
+            notification_type: notification.notification_type.clone(),
+            routing_key: Topology::routing_key(notification),
+            payload: serde_json::to_value(notification)?,
+            priority: notification.priority.amqp_priority(),
+            release_at,
+            expires_at: notification.expires_at,
+            accepted_at: Utc::now(),
+            trace_context: telemetry::current_trace_context(),
+        })
+    }
+
+    /// Span the relay publishes the entry in, a child of the request that accepted it.
+    pub fn relay_span(&self) -> Span {
+        let span = tracing::info_span!(
+            "outbox_relay",
+            notification_id = %self.id,
+            user_id = %self.user_id,
+        );
+        let _ = span.set_parent(telemetry::stored_context(&self.trace_context));
+        span
+    }
+
+    /// The entry can no longer be delivered in time.
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    async fn publish(&self, channel: &Channel, topology: &Topology) -> Result<(), Error> {
+        let body = serde_json::to_vec(&self.payload)?;
+        let now = Utc::now();
+
+        let mut properties = BasicProperties::default().with_priority(self.priority);
+        // The queue TTL only starts once the delayed exchange releases the message
+        if let Some(expires_at) = self.expires_at {
+            let ttl = (expires_at - self.release_at.max(now))
+                .num_milliseconds()
+                .max(0);
+            properties = properties.with_expiration(ttl.to_string().into());
+        }
+        // Time spent in the outbox counts towards the delay
+        let delay_ms = (self.release_at - now)
+            .num_milliseconds()
+            .clamp(0, i32::MAX as i64) as i32;
+        if delay_ms > 0 {
+            let mut headers = FieldTable::default();
+            headers.insert("x-delay".into(), AMQPValue::LongInt(delay_ms));
+            properties = properties.with_headers(headers);
+        }
+
+        publish_confirmed(
+            channel,
+            &topology.delayed_exchange,
+            &self.routing_key,
+            &body,
+            properties,
+            &self.notification_type,
+        )
+        .await
+    }
+}
+
+/// One change to the outbox, appended to its log as a JSON line.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum LogRecord<E> {
+    Push { entry: E },
+    Remove { id: Uuid },
+}
+
+/// Stale log records tolerated before the log is rewritten with only the waiting entries.
+const COMPACT_AFTER_STALE_RECORDS: usize = 1000;
+
+/// Accepted notifications, oldest first, kept in an append-only log of JSON lines. Every push
+/// and removal is appended and synced to disk before it takes effect, and an entry is removed
+/// once the broker confirmed it, so a crash in between publishes it again. The methods do disk
+/// I/O; async code goes through `enqueue` and the relay, which run them on the blocking pool.
+pub struct OutboxStore {
+    log: JsonLog,
+    entries: Vec<OutboxEntry>,
+    /// Records in the log that no longer describe a waiting entry.
+    stale_records: usize,
+}
+
+impl OutboxStore {
+    /// Replays the log and rewrites it with only the waiting entries.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+        let entries = read_log(&path)?;
+        let log = write_log(path, &entries)?;
+        metrics::OUTBOX_SIZE.set(entries.len() as i64);
+        Ok(OutboxStore {
+            log,
+            entries,
+            stale_records: 0,
+        })
+    }
+
+    /// Appends an entry, refusing it when `max_entries` are already waiting.
+    pub fn push(&mut self, entry: OutboxEntry, max_entries: usize) -> Result<(), Error> {
+        if self.entries.len() >= max_entries {
+            metrics::OUTBOX_REJECTED.inc();
+            return Err(Error::OutboxFull(self.entries.len()));
+        }
+        self.log.append(&LogRecord::Push { entry: &entry })?;
+        self.entries.push(entry);
+        metrics::OUTBOX_SIZE.set(self.entries.len() as i64);
+        Ok(())
+    }
+
+    /// Removes an entry. Returns whether it was there.
+    pub fn remove(&mut self, id: &Uuid) -> Result<bool, Error> {
+        let Some(index) = self.entries.iter().position(|e| &e.id == id) else {
+            return Ok(false);
+        };
+        self.log.append(&LogRecord::<&OutboxEntry>::Remove { id: *id })?;
+        self.entries.remove(index);
+        metrics::OUTBOX_SIZE.set(self.entries.len() as i64);
+
+        // The entry's push and its removal
+        self.stale_records += 2;
+        if self.stale_records >= COMPACT_AFTER_STALE_RECORDS
+            && self.stale_records >= self.entries.len()
+        {
+            self.log = write_log(self.log.path().to_path_buf(), &self.entries)?;
+            self.stale_records = 0;
+        }
+        Ok(true)
+    }
+
+    pub fn entries(&self) -> &[OutboxEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Waiting entries recorded in the log at `path`.
+fn read_log(path: &Path) -> Result<Vec<OutboxEntry>, Error> {
+    let contents = JsonLog::read(path)?;
+    // Outboxes written before the log was introduced are a single JSON array
+    if contents.trim_start().starts_with('[') {
+        return serde_json::from_str(&contents)
+            .map_err(|e| Error::Store(format!("Failed to parse {}: {}", path.display(), e)));
+    }
+
+    let mut entries: Vec<OutboxEntry> = Vec::new();
+    for record in JsonLog::parse(path, &contents)? {
+        match record {
+            LogRecord::Push { entry } => entries.push(entry),
+            LogRecord::Remove { id } => entries.retain(|e| e.id != id),
+        }
+    }
+    Ok(entries)
+}
+
+/// Replaces the log at `path` with one push per entry and returns it opened for appending.
+fn write_log(path: PathBuf, entries: &[OutboxEntry]) -> Result<JsonLog, Error> {
+    JsonLog::rewrite(path, entries.iter().map(|entry| LogRecord::Push { entry }))
+}
+
+// Singleton global
+lazy_static::lazy_static! {
+    static ref OUTBOX_STORE: tokio::sync::OnceCell<Mutex<OutboxStore>> = tokio::sync::OnceCell::new();
+    // Wakes the relay when an entry is added
+    static ref RELAY_WAKEUP: Notify = Notify::new();
+}
+
+pub fn init_outbox_store() -> Result<(), Error> {
+    let config = init_config()?;
+    let store = OutboxStore::open(&config.outbox_path)?;
+    if !store.is_empty() {
+        info!(
+            "📤 {} notifications left in the outbox will be published",
+            store.len()
+        );
+    }
+    OUTBOX_STORE
+        .set(Mutex::new(store))
+        .map_err(|_| Error::Store("Failed to set outbox store".to_string()))?;
+    Ok(())
+}
+
+pub fn get_outbox_store() -> Result<&'static Mutex<OutboxStore>, Error> {
+    OUTBOX_STORE
+        .get()
+        .ok_or_else(|| Error::Store("Outbox store not initialized".to_string()))
+}
+
+pub(crate) fn lock_outbox() -> Result<std::sync::MutexGuard<'static, OutboxStore>, Error> {
+    let store = get_outbox_store().inspect_err(|e| error!("Outbox store error: {}", e))?;
+    store.lock().map_err(|e| {
+        error!("Failed to lock outbox store: {}", e);
+        Error::Store("Database lock error".to_string())
+    })
+}
+
+/// Runs a change to the outbox on the blocking pool, since it is written and synced to disk.
+async fn with_outbox<T, F>(change: F) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut OutboxStore) -> Result<T, Error> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || change(&mut *lock_outbox()?))
+        .await
+        .map_err(|e| Error::Store(format!("Outbox write failed: {}", e)))?
+}
+
+/// Stores an accepted notification for the relay to publish. Returns once it is on disk.
+pub async fn enqueue(entry: OutboxEntry) -> Result<(), Error> {
+    let max_entries = get_config()?.outbox_max_entries;
+    with_outbox(move |store| store.push(entry, max_entries))
+        .await
+        .inspect_err(|e| warn!("📤 Notification not accepted: {}", e))?;
+    RELAY_WAKEUP.notify_one();
+    Ok(())
+}
+
+async fn remove(id: Uuid) -> Result<bool, Error> {
+    with_outbox(move |store| store.remove(&id)).await
+}
+
+fn track(entry: &OutboxEntry, state: DeliveryState, error: Option<&str>) {
+    let mut event = StatusEvent::new(entry.id, &entry.user_id, state).with_tenant(&entry.tenant);
+    if let Some(error) = error {
+        event = event.with_error(error);
+    }
+    status::record(&event);
+}
+
+/// Server-side task: publishes the outbox in order and removes what the broker confirmed.
+/// After a failure the tenant's remaining entries wait `OUTBOX_RETRY_SECS`.
+pub async fn outbox_relay_task() {
+    info!("📤 Starting outbox relay");
+
+    loop {
+        let retry_secs = get_config().map(|c| c.outbox_retry_secs).unwrap_or(5);
+        let retry = tokio::time::Duration::from_secs(retry_secs);
+        match relay_pending().await {
+            // Wait for new entries; the timeout also picks up anything missed
+            Ok(()) => {
+                let _ = tokio::time::timeout(retry, RELAY_WAKEUP.notified()).await;
+            }
+            Err(e) => {
+                warn!("📤 Outbox relay paused for {}s: {}", retry_secs, e);
+                tokio::time::sleep(retry).await;
+            }
+        }
+    }
+}
+
+/// Publishes every entry waiting now. Entries of a tenant whose publish failed are left for
+/// the next round, so each tenant's notifications keep their order.
+async fn relay_pending() -> Result<(), Error> {
+    let pending = lock_outbox()?.entries().to_vec();
+    if pending.is_empty() {
+        return Ok(());
+    }
+    let pool = get_rabbitmq_pool()?;
+
+    let mut channels: HashMap<String, Channel> = HashMap::new();
+    let mut failed_tenants = HashSet::new();
+    let mut last_error = None;
+    for entry in pending {
+        if failed_tenants.contains(&entry.tenant) {
+            continue;
+        }
+        let Some(tenant) = pool.tenant(&entry.tenant) else {
+            error!(
+                "📤 Dropping notification {} for tenant {}, which is not served any more",
+                entry.id, entry.tenant
+            );
+            track(&entry, DeliveryState::Failed, Some("Tenant is not served"));
+            remove(entry.id).await?;
+            continue;
+        };
+        if entry.is_expired(Utc::now()) {
+            warn!(
+                "⌛ Notification {} for user {} expired in the outbox, discarding",
+                entry.id, entry.user_id
+            );
+            track(&entry, DeliveryState::Expired, None);
+            remove(entry.id).await?;
+            continue;
+        }
+
+        match relay_entry(tenant, &mut channels, &entry).await {
+            Ok(()) => {
+                remove(entry.id).await?;
+                track(&entry, DeliveryState::Queued, None);
+                info!(
+                    "✅ Notification {} published from the outbox (waited {}ms)",
+                    entry.id,
+                    (Utc::now() - entry.accepted_at).num_milliseconds()
+                );
+            }
+            Err(e) => {
+                // A new channel is opened on the next attempt
+                channels.remove(&entry.tenant);
+                failed_tenants.insert(entry.tenant.clone());
+                last_error = Some(Error::Publish(format!(
+                    "notification {} of tenant {}: {}",
+                    entry.id, entry.tenant, e
+                )));
+            }
+        }
+    }
+    last_error.map_or(Ok(()), Err)
+}
+
+async fn relay_entry(
+    tenant: &TenantPool,
+    channels: &mut HashMap<String, Channel>,
+    entry: &OutboxEntry,
+) -> Result<(), Error> {
+    let channel = match channels.get(tenant.name()) {
+        Some(channel) => channel.clone(),
+        None => {
+            let channel = tenant.get_publish_channel().await?;
+            channels.insert(tenant.name().to_string(), channel.clone());
+            channel
+        }
+    };
+    entry
+        .publish(&channel, tenant.topology())
+        .instrument(entry.relay_span())
+        .await
+}
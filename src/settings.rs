@@ -0,0 +1,837 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+use crate::config::DEFAULT_ALLOWED_CHANNELS;
+use crate::error::Error;
+use crate::secret::RedactedUrl;
+use crate::topology::DEFAULT_ROUTING_TABLE;
+
+/// A configuration key. Its name is the environment variable; the TOML file uses it in
+/// lowercase (optionally split into sections, `[rabbitmq] host` for `RABBITMQ_HOST`) and the
+/// command line as a flag (`--rabbitmq-host`).
+pub struct Setting {
+    pub key: &'static str,
+    pub default: Option<&'static str>,
+    pub help: &'static str,
+}
+
+const fn setting(key: &'static str, default: Option<&'static str>, help: &'static str) -> Setting {
+    Setting { key, default, help }
+}
+
+/// Every setting both binaries understand.
+pub const SETTINGS: &[Setting] = &[
+    setting("RABBITMQ_HOST", Some("localhost"), "Broker host"),
+    setting("RABBITMQ_PORT", Some("5672"), "Broker port"),
+    setting("RABBITMQ_USER", Some("guest"), "Broker user"),
+    setting("RABBITMQ_PASSWORD", Some("guest"), "Broker password"),
+    setting("RABBITMQ_VHOST", Some("%2f"), "Broker vhost, URL encoded"),
+    setting(
+        "RABBITMQ_URL",
+        None,
+        "Complete AMQP URL; built from the settings above when unset",
+    ),
+    setting(
+        "RABBITMQ_NODES",
+        None,
+        "Cluster nodes as host[:port], comma separated; they replace the URL host",
+    ),
+    setting(
+        "RABBITMQ_NODE_ORDER",
+        Some("ordered"),
+        "Order nodes are tried in on connect: ordered or random",
+    ),
+    setting(
+        "RABBITMQ_HEARTBEAT_SECS",
+        Some("60"),
+        "Heartbeat interval asked of the broker; 0 to take the broker's",
+    ),
+    setting(
+        "RABBITMQ_CHANNEL_MAX",
+        Some("0"),
+        "Most channels per connection; 0 to take the broker's limit",
+    ),
+    setting(
+        "RABBITMQ_FRAME_MAX",
+        Some("0"),
+        "Largest frame in bytes, at least 4096; 0 to take the broker's limit",
+    ),
+    setting(
+        "RABBITMQ_CONNECT_TIMEOUT_SECS",
+        Some("10"),
+        "Time allowed to open a broker connection, handshake included",
+    ),
+    setting(
+        "RABBITMQ_CONNECTION_NAME",
+        None,
+        "Connection name shown by the broker (default: binary@hostname)",
+    ),
+    setting(
+        "RABBITMQ_TLS",
+        Some("false"),
+        "Connect with TLS (amqps) when the URL is built from the settings above",
+    ),
+    setting(
+        "RABBITMQ_TLS_CA_FILE",
+        None,
+        "PEM CA bundle trusted for the broker (default: system roots)",
+    ),
+    setting(
+        "RABBITMQ_TLS_CERT_FILE",
+        None,
+        "PEM client certificate chain for mutual TLS",
+    ),
+    setting(
+        "RABBITMQ_TLS_KEY_FILE",
+        None,
+        "PEM private key of the client certificate",
+    ),
+    setting(
+        "RABBITMQ_TLS_KEY_PASSWORD",
+        None,
+        "Passphrase of an encrypted PKCS#8 client key",
+    ),
+    setting(
+        "RABBITMQ_TLS_KEY_PASSWORD_FILE",
+        None,
+        "File holding the client key passphrase",
+    ),
+    setting(
+        "RABBITMQ_TLS_SERVER_NAME",
+        None,
+        "Name sent as SNI and matched against the broker certificate (default: URL host)",
+    ),
+    setting(
+        "RABBITMQ_TLS_VERIFY_HOSTNAME",
+        Some("true"),
+        "Check that the broker certificate is issued for the server name",
+    ),
+    setting(
+        "RABBITMQ_PASSWORD_FILE",
+        None,
+        "File holding the broker password",
+    ),
+    setting(
+        "RABBITMQ_URL_FILE",
+        None,
+        "File holding the complete AMQP URL",
+    ),
+    setting("SERVER_HOST", Some("127.0.0.1"), "HTTP server bind address"),
+    setting("SERVER_PORT", Some("8081"), "HTTP server port"),
+    setting(
+        "ROUTING_TABLE",
+        Some(DEFAULT_ROUTING_TABLE),
+        "Queues and their bindings: queue=pattern,pattern;queue=pattern",
+    ),
+    setting(
+        "WORKER_QUEUES",
+        None,
+        "Queues the worker consumes, comma separated (default: all)",
+    ),
+    setting(
+        "WORKER_PREFETCH",
+        Some("1"),
+        "Unacknowledged deliveries per tenant",
+    ),
+    setting(
+        "TENANTS",
+        None,
+        "Tenants: name=vhost:<vhost>,prefetch:<n>;name=prefix:<prefix>",
+    ),
+    setting(
+        "DELAYED_EXCHANGE",
+        Some("delayed_topic_exchange"),
+        "Exchange notifications are published to",
+    ),
+    setting("DLX_EXCHANGE", Some("dlx_exchange"), "Dead letter exchange"),
+    setting(
+        "DEAD_LETTER_QUEUE",
+        Some("dead_letter_queue"),
+        "Dead letter queue",
+    ),
+    setting(
+        "STATUS_EXCHANGE",
+        Some("notification_status_events"),
+        "Fanout exchange of delivery status events; each server consumes all of them",
+    ),
+    setting(
+        "INBOX_EXCHANGE",
+        Some("inbox_entries"),
+        "Fanout exchange of inbox entries and read markers; each server keeps a queue on it",
+    ),
+    setting(
+        "INSTANCE_ID",
+        None,
+        "Name of this server's inbox queue, stable across restarts (default: host name)",
+    ),
+    setting(
+        "CALLBACK_QUEUE",
+        Some("callback_queue"),
+        "Queue of webhook jobs",
+    ),
+    setting(
+        "MAX_DELAY_HOP_SECS",
+        Some("604800"),
+        "Longest delay of one pass through the delayed exchange",
+    ),
+    setting(
+        "SCHEDULER_INTERVAL_SECS",
+        Some("1"),
+        "Pause between scheduler cycles",
+    ),
+    setting(
+        "SCHEDULER_ERROR_BACKOFF_SECS",
+        Some("5"),
+        "Pause after a failed scheduler cycle",
+    ),
+    setting(
+        "SCHEDULER_GRACE_PERIOD_SECS",
+        Some("300"),
+        "Overdue scheduled notifications older than this expire",
+    ),
+    setting(
+        "SCHEDULER_STALE_AFTER_SECS",
+        Some("30"),
+        "Readiness fails when no scheduler cycle completed for this long",
+    ),
+    setting(
+        "PROCESSING_TIMEOUT_SECS",
+        Some("30"),
+        "Longest time the worker spends on one delivery",
+    ),
+    setting(
+        "WORKER_MAX_RETRIES",
+        Some("3"),
+        "Consumer restarts in a row, and retries of a failed delivery, before giving up",
+    ),
+    setting(
+        "WORKER_RETRY_DELAY_SECS",
+        Some("5"),
+        "Pause before a failed consumer restarts; a failed delivery waits this times its attempt",
+    ),
+    setting(
+        "WORKER_HEALTH_HOST",
+        None,
+        "Worker health listener address (default: SERVER_HOST)",
+    ),
+    setting(
+        "WORKER_HEALTH_PORT",
+        Some("8082"),
+        "Worker health listener port, 0 disables it",
+    ),
+    setting(
+        "MAX_BODY_BYTES",
+        Some("65536"),
+        "Largest accepted JSON body",
+    ),
+    setting(
+        "MAX_MESSAGE_LENGTH",
+        Some("4096"),
+        "Longest notification message, in bytes",
+    ),
+    setting(
+        "ALLOWED_CHANNELS",
+        Some(DEFAULT_ALLOWED_CHANNELS),
+        "Accepted channels, comma separated",
+    ),
+    setting(
+        "MAX_SCHEDULE_AHEAD_DAYS",
+        Some("365"),
+        "How far in the future scheduled_at may be",
+    ),
+    setting(
+        "CALLBACK_CONCURRENCY",
+        Some("10"),
+        "Webhook callbacks in flight per tenant",
+    ),
+    setting(
+        "CALLBACK_TIMEOUT_SECS",
+        Some("10"),
+        "Timeout of one webhook request",
+    ),
+    setting(
+        "CALLBACK_MAX_ATTEMPTS",
+        Some("5"),
+        "Webhook attempts before the job is dead-lettered",
+    ),
+    setting(
+        "CALLBACK_BACKOFF_BASE_MS",
+        Some("1000"),
+        "First webhook retry delay, doubled per attempt",
+    ),
+    setting(
+        "CALLBACK_SECRETS_PATH",
+        None,
+        "JSON file of callback signing secrets by tenant and id",
+    ),
+    setting(
+        "CALLBACK_ALLOW_PRIVATE_TARGETS",
+        Some("false"),
+        "Allow callbacks to loopback, private and link-local addresses (development only)",
+    ),
+    setting(
+        "INBOX_PATH",
+        Some("data/inbox.jsonl"),
+        "Append-only log the inbox store is persisted to",
+    ),
+    setting(
+        "INBOX_MAX_PER_USER",
+        Some("500"),
+        "Inbox entries kept per user",
+    ),
+    setting(
+        "STATUS_MAX_ENTRIES",
+        Some("100000"),
+        "Delivery statuses kept in memory; the least recently updated are dropped first",
+    ),
+    setting(
+        "STATUS_RETENTION_SECS",
+        Some("86400"),
+        "Delivery statuses not updated for this long are dropped",
+    ),
+    setting(
+        "OUTBOX_PATH",
+        Some("data/outbox.jsonl"),
+        "Append-only log accepted notifications wait in until the broker confirms them",
+    ),
+    setting(
+        "OUTBOX_MAX_ENTRIES",
+        Some("10000"),
+        "Outbox size at which new notifications are refused with 503",
+    ),
+    setting(
+        "OUTBOX_RETRY_SECS",
+        Some("5"),
+        "Pause before the relay retries after a failed publish",
+    ),
+    setting(
+        "DLQ_SCAN_LIMIT",
+        Some("1000"),
+        "Dead-lettered messages read per admin list, replay or purge",
+    ),
+    setting(
+        "DLQ_AUDIT_PATH",
+        Some("data/dlq-audit.jsonl"),
+        "File replays and purges of dead-lettered messages are recorded in",
+    ),
+    setting(
+        "API_KEYS_PATH",
+        None,
+        "JSON file of API keys",
+    ),
+    setting(
+        "API_KEYS",
+        None,
+        "API keys as JSON, in the format of the API_KEYS_PATH file",
+    ),
+    setting(
+        "API_KEYS_FILE",
+        None,
+        "File holding the API keys JSON, read like the other secret files",
+    ),
+    setting(
+        "AUTH_DISABLED",
+        Some("false"),
+        "Serve the API without credentials when no API keys or JWT keys are set",
+    ),
+    setting(
+        "JWT_JWKS_PATH",
+        None,
+        "JWKS file of the JWT verification keys",
+    ),
+    setting(
+        "JWT_PUBLIC_KEY_PATH",
+        None,
+        "PEM public key of the JWT issuer",
+    ),
+    setting(
+        "JWT_HMAC_SECRET",
+        None,
+        "Shared secret of HS256/HS384/HS512 tokens",
+    ),
+    setting(
+        "JWT_HMAC_SECRET_FILE",
+        None,
+        "File holding the JWT shared secret",
+    ),
+    setting("JWT_ISSUER", None, "Required JWT iss claim"),
+    setting("JWT_AUDIENCE", None, "Required JWT aud claim"),
+    setting(
+        "JWT_TENANT_CLAIM",
+        Some("tenant"),
+        "JWT claim naming the tenant",
+    ),
+    setting(
+        "JWT_RELOAD_INTERVAL_SECS",
+        Some("300"),
+        "How often the JWT key files are re-read",
+    ),
+    setting(
+        "OTEL_EXPORTER_OTLP_ENDPOINT",
+        None,
+        "OTLP/HTTP collector traces are exported to",
+    ),
+    setting(
+        "OTEL_TRACES_FILE",
+        None,
+        "File finished spans are written to as JSON lines",
+    ),
+    setting(
+        "OTEL_SERVICE_NAME",
+        None,
+        "Service name reported with the traces",
+    ),
+    setting(
+        "RUST_LOG",
+        None,
+        "Log filter directives (default: per binary)",
+    ),
+    setting("LOG_FORMAT", Some("text"), "Log format: text or json"),
+];
+
+/// Printed as `***` by `--print-config`.
+const SECRET_SETTINGS: [&str; 5] = [
+    "RABBITMQ_PASSWORD",
+    "RABBITMQ_URL",
+    "RABBITMQ_TLS_KEY_PASSWORD",
+    "API_KEYS",
+    "JWT_HMAC_SECRET",
+];
+
+/// Secrets and the settings naming a file to read them from, for credentials mounted as files.
+const SECRET_FILES: [(&str, &str); 5] = [
+    ("RABBITMQ_PASSWORD", "RABBITMQ_PASSWORD_FILE"),
+    ("RABBITMQ_URL", "RABBITMQ_URL_FILE"),
+    ("RABBITMQ_TLS_KEY_PASSWORD", "RABBITMQ_TLS_KEY_PASSWORD_FILE"),
+    ("API_KEYS", "API_KEYS_FILE"),
+    ("JWT_HMAC_SECRET", "JWT_HMAC_SECRET_FILE"),
+];
+
+/// Settings a running process picks up on reload. Everything else is used to open broker
+/// connections, listeners or stores at startup, so changing it needs a restart.
+pub const RELOADABLE_SETTINGS: &[&str] = &[
+    "RUST_LOG",
+    "MAX_DELAY_HOP_SECS",
+    "SCHEDULER_INTERVAL_SECS",
+    "SCHEDULER_ERROR_BACKOFF_SECS",
+    "SCHEDULER_GRACE_PERIOD_SECS",
+    "SCHEDULER_STALE_AFTER_SECS",
+    "PROCESSING_TIMEOUT_SECS",
+    "WORKER_MAX_RETRIES",
+    "WORKER_RETRY_DELAY_SECS",
+    "MAX_MESSAGE_LENGTH",
+    "ALLOWED_CHANNELS",
+    "MAX_SCHEDULE_AHEAD_DAYS",
+    "CALLBACK_MAX_ATTEMPTS",
+    "CALLBACK_BACKOFF_BASE_MS",
+    "OUTBOX_MAX_ENTRIES",
+    "OUTBOX_RETRY_SECS",
+    "DLQ_SCAN_LIMIT",
+    "STATUS_MAX_ENTRIES",
+    "STATUS_RETENTION_SECS",
+];
+
+/// Where a setting's value came from, lowest precedence first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Source {
+    Default,
+    /// Computed from other settings, e.g. `RABBITMQ_URL` from the host, port and credentials.
+    Derived,
+    File,
+    Env,
+    Flag,
+    /// Read from the file named by the secret's `*_FILE` setting.
+    SecretFile,
+}
+
+impl Source {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Source::Default => "default",
+            Source::Derived => "derived",
+            Source::File => "file",
+            Source::Env => "env",
+            Source::Flag => "flag",
+            Source::SecretFile => "secret file",
+        }
+    }
+}
+
+/// What the command line adds to the environment: a TOML file and settings given as flags.
+#[derive(Debug, Clone, Default)]
+pub struct Sources {
+    /// Falls back to `CONFIG_FILE` when not given with `--config`.
+    pub file: Option<PathBuf>,
+    pub flags: BTreeMap<&'static str, String>,
+    /// `--print-config`: print the effective configuration and exit.
+    pub print_config: bool,
+}
+
+impl Sources {
+    /// Parses the process arguments; prints usage and exits on `--help` or invalid flags.
+    pub fn from_args(name: &'static str, about: &'static str) -> Self {
+        Self::from_matches(&command(name, about).get_matches())
+    }
+
+    pub fn try_from_args<I, T>(
+        name: &'static str,
+        about: &'static str,
+        args: I,
+    ) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        Ok(Self::from_matches(
+            &command(name, about).try_get_matches_from(args)?,
+        ))
+    }
+
+    fn from_matches(matches: &ArgMatches) -> Self {
+        Sources {
+            file: matches.get_one::<PathBuf>("config").cloned(),
+            flags: SETTINGS
+                .iter()
+                .filter_map(|s| matches.get_one::<String>(s.key).map(|v| (s.key, v.clone())))
+                .collect(),
+            print_config: matches.get_flag("print-config"),
+        }
+    }
+}
+
+/// Command line of a binary: `--config`, `--print-config` and one flag per setting.
+pub fn command(name: &'static str, about: &'static str) -> Command {
+    let command = Command::new(name)
+        .about(about)
+        .version(env!("CARGO_PKG_VERSION"))
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("FILE")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("TOML configuration file [env: CONFIG_FILE]"),
+        )
+        .arg(
+            Arg::new("print-config")
+                .long("print-config")
+                .action(ArgAction::SetTrue)
+                .help("Print the effective configuration as TOML and exit"),
+        );
+    SETTINGS.iter().fold(command, |command, setting| {
+        command.arg(
+            Arg::new(setting.key)
+                .long(setting.key.to_lowercase().replace('_', "-"))
+                .value_name("VALUE")
+                .help(format!("{} [env: {}]", setting.help, setting.key))
+                .help_heading("Settings"),
+        )
+    })
+}
+
+/// Every setting's value and where it came from: defaults, then the TOML file, then
+/// environment variables, then flags. Empty values are ignored, so they keep the lower layer's.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    values: BTreeMap<&'static str, (String, Source)>,
+}
+
+impl Settings {
+    pub fn resolve(sources: &Sources) -> Result<Self, Error> {
+        // Load .env file if it exists
+        dotenv::dotenv().ok();
+
+        let mut settings = Settings::default();
+        for setting in SETTINGS {
+            if let Some(default) = setting.default {
+                settings.set(setting.key, default.to_string(), Source::Default);
+            }
+        }
+
+        let file = match &sources.file {
+            Some(file) => Some(file.clone()),
+            None => env_value("CONFIG_FILE")?.map(PathBuf::from),
+        };
+        if let Some(file) = file {
+            for (key, value) in read_file(&file)? {
+                settings.set(key, value, Source::File);
+            }
+        }
+
+        for setting in SETTINGS {
+            if let Some(value) = env_value(setting.key)? {
+                settings.set(setting.key, value, Source::Env);
+            }
+        }
+        for (key, value) in &sources.flags {
+            settings.set(key, value.clone(), Source::Flag);
+        }
+        for (key, file_key) in SECRET_FILES {
+            settings.read_secret_file(key, file_key)?;
+        }
+
+        if settings.get("WORKER_HEALTH_HOST").is_none() {
+            let host = settings.string("SERVER_HOST");
+            settings.set("WORKER_HEALTH_HOST", host, Source::Derived);
+        }
+        if settings.get("RABBITMQ_URL").is_none() {
+            let scheme = if settings.parse::<bool>("RABBITMQ_TLS")? {
+                "amqps"
+            } else {
+                "amqp"
+            };
+            let url = format!(
+                "{}://{}:{}@{}:{}/{}",
+                scheme,
+                settings.string("RABBITMQ_USER"),
+                settings.string("RABBITMQ_PASSWORD"),
+                settings.string("RABBITMQ_HOST"),
+                settings.parse::<u16>("RABBITMQ_PORT")?,
+                settings.string("RABBITMQ_VHOST")
+            );
+            settings.set("RABBITMQ_URL", url, Source::Derived);
+        }
+        Ok(settings)
+    }
+
+    /// Replaces a secret with the contents of the file named by `file_key`, unless the secret
+    /// itself comes from a later layer. Both in the same layer is ambiguous and rejected.
+    fn read_secret_file(&mut self, key: &'static str, file_key: &str) -> Result<(), Error> {
+        let Some((path, file_source)) = self.values.get(file_key).cloned() else {
+            return Ok(());
+        };
+        match self.source(key) {
+            Some(source) if source == file_source => {
+                return Err(Error::Config(format!(
+                    "Set either {} or {} (from {}), not both",
+                    key,
+                    file_key,
+                    source.as_str()
+                )));
+            }
+            Some(source) if source > file_source => return Ok(()),
+            _ => {}
+        }
+        let secret = std::fs::read_to_string(path.trim())
+            .map_err(|e| self.invalid(file_key, &format!("cannot read file: {}", e)))?;
+        // Mounted secrets usually end with a newline that is not part of the value
+        let secret = secret.trim_end_matches(['\r', '\n']).to_string();
+        if secret.is_empty() {
+            return Err(self.invalid(file_key, "file is empty"));
+        }
+        self.set(key, secret, Source::SecretFile);
+        Ok(())
+    }
+
+    /// Settings whose value differs in `other`, ignoring where the values came from.
+    pub fn changed(&self, other: &Settings) -> Vec<&'static str> {
+        SETTINGS
+            .iter()
+            .map(|s| s.key)
+            .filter(|key| self.get(key) != other.get(key))
+            .collect()
+    }
+
+    fn set(&mut self, key: &'static str, value: String, source: Source) {
+        if !value.trim().is_empty() {
+            self.values.insert(key, (value, source));
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|(value, _)| value.as_str())
+    }
+
+    pub fn source(&self, key: &str) -> Option<Source> {
+        self.values.get(key).map(|(_, source)| *source)
+    }
+
+    /// A setting with a default, or an empty string.
+    pub fn string(&self, key: &str) -> String {
+        self.get(key).unwrap_or_default().to_string()
+    }
+
+    pub fn optional(&self, key: &str) -> Option<String> {
+        self.get(key).map(str::to_string)
+    }
+
+    /// A comma separated list; empty when unset.
+    pub fn list(&self, key: &str) -> Vec<String> {
+        self.get(key)
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// A setting with a default, parsed; a malformed value fails instead of falling back.
+    pub fn parse<T>(&self, key: &str) -> Result<T, Error>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.get(key)
+            .ok_or_else(|| Error::Config(format!("{} is not set", key)))?
+            .trim()
+            .parse()
+            .map_err(|e: T::Err| self.invalid(key, &e.to_string()))
+    }
+
+    /// Like `parse`, for values that must lie in `range`.
+    pub fn parse_in<T>(&self, key: &str, range: std::ops::RangeInclusive<T>) -> Result<T, Error>
+    where
+        T: FromStr + PartialOrd + std::fmt::Display,
+        T::Err: std::fmt::Display,
+    {
+        let value = self.parse(key)?;
+        if range.contains(&value) {
+            Ok(value)
+        } else {
+            Err(self.invalid(
+                key,
+                &format!("must be between {} and {}", range.start(), range.end()),
+            ))
+        }
+    }
+
+    /// A configuration error naming the setting, its value and where the value came from.
+    pub fn invalid(&self, key: &str, reason: &str) -> Error {
+        let shown = match self.values.get(key) {
+            Some((value, source)) => format!(
+                " '{}' (from {})",
+                display_value(key, value),
+                source.as_str()
+            ),
+            None => String::new(),
+        };
+        Error::Config(format!("Invalid {}{}: {}", key, shown, reason))
+    }
+
+    /// The effective settings as a TOML file that can be loaded again with `--config`, each
+    /// annotated with its source. Passwords are masked, so they have to be filled back in.
+    pub fn to_toml(&self) -> String {
+        let mut out = String::new();
+        for setting in SETTINGS {
+            let key = setting.key.to_lowercase();
+            let _ = match self.values.get(setting.key) {
+                // Derived values and secrets read from files stay commented so the output can
+                // be loaded back as a file
+                Some((value, source)) => writeln!(
+                    out,
+                    "{}{} = {} # {}",
+                    if matches!(source, Source::Derived | Source::SecretFile) {
+                        "# "
+                    } else {
+                        ""
+                    },
+                    key,
+                    toml_value(&display_value(setting.key, value)),
+                    source.as_str()
+                ),
+                None => writeln!(out, "# {} = (unset)", key),
+            };
+        }
+        out
+    }
+}
+
+fn env_value(key: &str) -> Result<Option<String>, Error> {
+    match env::var(key) {
+        Ok(value) => Ok(Some(value)),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(env::VarError::NotUnicode(_)) => {
+            Err(Error::Config(format!("{} is not valid UTF-8", key)))
+        }
+    }
+}
+
+fn display_value(key: &str, value: &str) -> String {
+    match key {
+        "RABBITMQ_URL" => RedactedUrl(value).to_string(),
+        _ if SECRET_SETTINGS.contains(&key) => "***".to_string(),
+        _ => value.to_string(),
+    }
+}
+
+/// Numbers and booleans unquoted, everything else as a TOML string.
+fn toml_value(value: &str) -> String {
+    if value.parse::<i64>().is_ok() || value.parse::<bool>().is_ok() {
+        value.to_string()
+    } else {
+        toml::Value::String(value.to_string()).to_string()
+    }
+}
+
+/// Reads a TOML file into settings. Tables are flattened into their keys' prefixes and arrays
+/// into comma separated lists; unknown keys are rejected.
+fn read_file(path: &Path) -> Result<Vec<(&'static str, String)>, Error> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| Error::Config(format!("Cannot read config file {}: {}", path.display(), e)))?;
+    let table: toml::Table = text
+        .parse()
+        .map_err(|e| Error::Config(format!("Invalid config file {}: {}", path.display(), e)))?;
+    let mut values = Vec::new();
+    flatten(path, "", &table, &mut values)?;
+    Ok(values)
+}
+
+fn flatten(
+    path: &Path,
+    prefix: &str,
+    table: &toml::Table,
+    values: &mut Vec<(&'static str, String)>,
+) -> Result<(), Error> {
+    for (name, value) in table {
+        let name = name.to_uppercase().replace('-', "_");
+        let key = if prefix.is_empty() {
+            name
+        } else {
+            format!("{}_{}", prefix, name)
+        };
+        if let toml::Value::Table(inner) = value {
+            flatten(path, &key, inner, values)?;
+            continue;
+        }
+        let setting = SETTINGS.iter().find(|s| s.key == key).ok_or_else(|| {
+            Error::Config(format!(
+                "Unknown setting '{}' in {}",
+                key.to_lowercase(),
+                path.display()
+            ))
+        })?;
+        let value = scalar(value).ok_or_else(|| {
+            Error::Config(format!(
+                "Setting '{}' in {} must be a string, number, boolean or list of them",
+                key.to_lowercase(),
+                path.display()
+            ))
+        })?;
+        values.push((setting.key, value));
+    }
+    Ok(())
+}
+
+fn scalar(value: &toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(s) => Some(s.clone()),
+        toml::Value::Integer(i) => Some(i.to_string()),
+        toml::Value::Float(f) => Some(f.to_string()),
+        toml::Value::Boolean(b) => Some(b.to_string()),
+        toml::Value::Datetime(d) => Some(d.to_string()),
+        toml::Value::Array(items) => items
+            .iter()
+            .map(|item| match item {
+                toml::Value::Array(_) | toml::Value::Table(_) => None,
+                other => scalar(other),
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(|items| items.join(",")),
+        toml::Value::Table(_) => None,
+    }
+}
@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, Utc};
+use futures_util::stream::StreamExt;
+use lapin::{BasicProperties, options::*, types::FieldTable};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::config::get_config;
+use crate::error::Error;
+use crate::connection::{TenantPool, get_rabbitmq_pool};
+
+/// Lifecycle of a notification: accepted → queued → delivering → delivered/failed/expired.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryState {
+    Accepted,
+    Queued,
+    Delivering,
+    Delivered,
+    Failed,
+    Expired,
+}
+
+impl DeliveryState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryState::Accepted => "accepted",
+            DeliveryState::Queued => "queued",
+            DeliveryState::Delivering => "delivering",
+            DeliveryState::Delivered => "delivered",
+            DeliveryState::Failed => "failed",
+            DeliveryState::Expired => "expired",
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            DeliveryState::Delivered | DeliveryState::Failed | DeliveryState::Expired
+        )
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            DeliveryState::Accepted => 0,
+            DeliveryState::Queued => 1,
+            DeliveryState::Delivering => 2,
+            DeliveryState::Delivered | DeliveryState::Failed | DeliveryState::Expired => 3,
+        }
+    }
+}
+
+/// A state change reported by the server or the worker.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct StatusEvent {
+    pub notification_id: Uuid,
+    pub user_id: String,
+    pub state: DeliveryState,
+    pub at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+}
+
+impl StatusEvent {
+    pub fn new(notification_id: Uuid, user_id: &str, state: DeliveryState) -> Self {
+        StatusEvent {
+            notification_id,
+            user_id: user_id.to_string(),
+            state,
+            at: Utc::now(),
+            error: None,
+            tenant: None,
+        }
+    }
+
+    pub fn with_error(mut self, error: impl Into<String>) -> Self {
+        self.error = Some(error.into());
+        self
+    }
+
+    pub fn with_tenant(mut self, tenant: impl Into<String>) -> Self {
+        self.tenant = Some(tenant.into());
+        self
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct StatusTransition {
+    pub state: DeliveryState,
+    pub at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Current delivery status of a notification and how it got there.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct DeliveryStatus {
+    pub id: Uuid,
+    pub user_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    pub state: DeliveryState,
+    pub updated_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub history: Vec<StatusTransition>,
+}
+
+/// Delivery statuses by notification id, bounded in age and size so a long-running server
+/// does not keep every notification it ever saw.
+#[derive(Debug, Default)]
+pub struct StatusStore {
+    statuses: HashMap<Uuid, DeliveryStatus>,
+    swept_at: Option<DateTime<Utc>>,
+}
+
+/// How often statuses past their retention are looked for while the store is not full.
+const SWEEP_INTERVAL_SECS: i64 = 60;
+
+impl StatusStore {
+    /// Applies an event. Returns `false` when the event was ignored.
+    ///
+    /// Events from the server and the worker travel different paths and can arrive out of
+    /// order, so a status never moves backwards, except for a retry (`queued` with an error),
+    /// and terminal states are final.
+    pub fn apply(&mut self, event: &StatusEvent) -> bool {
+        let transition = StatusTransition {
+            state: event.state,
+            at: event.at,
+            error: event.error.clone(),
+        };
+
+        match self.statuses.get_mut(&event.notification_id) {
+            Some(status) => {
+                let is_retry = event.state == DeliveryState::Queued && event.error.is_some();
+                if status.state.is_terminal()
+                    || (event.state.rank() < status.state.rank() && !is_retry)
+                {
+                    return false;
+                }
+                status.state = event.state;
+                status.updated_at = event.at;
+                status.error = event.error.clone();
+                status.history.push(transition);
+            }
+            None => {
+                self.statuses.insert(
+                    event.notification_id,
+                    DeliveryStatus {
+                        id: event.notification_id,
+                        user_id: event.user_id.clone(),
+                        tenant: event.tenant.clone(),
+                        state: event.state,
+                        updated_at: event.at,
+                        error: event.error.clone(),
+                        history: vec![transition],
+                    },
+                );
+            }
+        }
+        true
+    }
+
+    pub fn get(&self, id: &Uuid) -> Option<&DeliveryStatus> {
+        self.statuses.get(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.statuses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.statuses.is_empty()
+    }
+
+    /// Drops statuses not updated within `retention`, then, when more than `max_entries`
+    /// remain, the least recently updated ones down to nine tenths of the limit so the next
+    /// eviction is some inserts away. Sweeps for old statuses at most once a minute unless
+    /// the store is full.
+    pub fn evict(&mut self, now: DateTime<Utc>, max_entries: usize, retention: Duration) {
+        let full = self.statuses.len() > max_entries;
+        let sweep_due = self
+            .swept_at
+            .is_none_or(|at| now - at >= Duration::seconds(SWEEP_INTERVAL_SECS));
+        if !full && !sweep_due {
+            return;
+        }
+        self.swept_at = Some(now);
+        let cutoff = now - retention;
+        self.statuses.retain(|_, status| status.updated_at >= cutoff);
+
+        if self.statuses.len() > max_entries {
+            let keep = max_entries - max_entries / 10;
+            let mut by_age: Vec<(DateTime<Utc>, Uuid)> = self
+                .statuses
+                .values()
+                .map(|status| (status.updated_at, status.id))
+                .collect();
+            by_age.sort_unstable();
+            let excess = by_age.len() - keep;
+            for (_, id) in &by_age[..excess] {
+                self.statuses.remove(id);
+            }
+            warn!(
+                "Status store full, dropped the {} least recently updated statuses",
+                excess
+            );
+        }
+    }
+}
+
+// In-memory status store, fed by the handlers and the worker's status events
+lazy_static::lazy_static! {
+    pub static ref DELIVERY_STATUSES: Mutex<StatusStore> = Mutex::new(StatusStore::default());
+}
+
+/// Applies an event to the status store and forwards it to stream clients.
+/// Returns `false` when the event was ignored.
+pub fn record(event: &StatusEvent) -> bool {
+    let mut db = match DELIVERY_STATUSES.lock() {
+        Ok(db) => db,
+        Err(e) => {
+            error!("Failed to lock delivery statuses: {}", e);
+            return false;
+        }
+    };
+    if !db.apply(event) {
+        return false;
+    }
+    let (max_entries, retention_secs) = get_config()
+        .map(|c| (c.status_max_entries, c.status_retention_secs))
+        .unwrap_or((100_000, 86_400));
+    db.evict(Utc::now(), max_entries, Duration::seconds(retention_secs as i64));
+    drop(db);
+
+    crate::stream::publish(event);
+    true
+}
+
+pub fn get_status(id: &Uuid) -> Option<DeliveryStatus> {
+    DELIVERY_STATUSES
+        .lock()
+        .ok()
+        .and_then(|db| db.get(id).cloned())
+}
+
+/// Publishes a status event on the tenant's status exchange, which fans it out to every
+/// server. Used by the worker.
+pub async fn publish_status_event(
+    tenant: &TenantPool,
+    event: &StatusEvent,
+) -> Result<(), Error> {
+    let channel = tenant.get_channel().await?;
+    let body = serde_json::to_vec(event)?;
+
+    channel
+        .basic_publish(
+            &tenant.topology().status_exchange,
+            "",
+            BasicPublishOptions::default(),
+            &body,
+            BasicProperties::default(),
+        )
+        .await
+        .map_err(|e| Error::Publish(e.to_string()))?
+        .await
+        .map_err(|e| Error::Publish(format!("Publish was not confirmed: {}", e)))?;
+    Ok(())
+}
+
+/// Server-side task: consumes every tenant's status events into the status store.
+pub async fn status_consumer_task() {
+    info!("📡 Starting delivery status consumer");
+
+    let pool = match get_rabbitmq_pool() {
+        Ok(pool) => pool,
+        Err(e) => {
+            error!("Status consumer cannot start: {}", e);
+            return;
+        }
+    };
+    futures_util::future::join_all(pool.tenants().iter().map(tenant_status_consumer)).await;
+}
+
+async fn tenant_status_consumer(tenant: &'static TenantPool) {
+    loop {
+        if let Err(e) = consume_status_events(tenant).await {
+            error!("Status consumer for tenant {} failed: {}", tenant.name(), e);
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+    }
+}
+
+async fn consume_status_events(tenant: &TenantPool) -> Result<(), Error> {
+    let channel = tenant.get_channel().await?;
+    // Each server needs every event, so it consumes its own queue rather than a shared one
+    let queue = tenant.topology().declare_status_queue(&channel).await?;
+
+    let mut consumer = channel
+        .basic_consume(
+            &queue,
+            "status_consumer",
+            BasicConsumeOptions::default(),
+            FieldTable::default(),
+        )
+        .await?;
+
+    while let Some(delivery) = consumer.next().await {
+        let delivery = delivery?;
+        match serde_json::from_slice::<StatusEvent>(&delivery.data) {
+            // The queue, not the payload, decides which tenant an event belongs to
+            Ok(event) => {
+                let event = event.with_tenant(tenant.name());
+                if !record(&event) {
+                    warn!(
+                        "Ignored out-of-order status {:?} for notification {}",
+                        event.state, event.notification_id
+                    );
+                }
+            }
+            Err(e) => error!("❌ Error deserializing status event: {}", e),
+        }
+        delivery.ack(BasicAckOptions::default()).await?;
+    }
+
+    warn!("Status consumer stream for tenant {} ended", tenant.name());
+    Ok(())
+}
@@ -0,0 +1,154 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Serialize, de::DeserializeOwned};
+use tracing::warn;
+
+use crate::error::Error;
+
+/// A value persisted as a JSON file. Writes go to a temporary file that is renamed over the
+/// original, so a crash never leaves a half-written store behind.
+#[derive(Debug, Clone)]
+pub struct JsonFile {
+    path: PathBuf,
+}
+
+impl JsonFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        JsonFile { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Loads the stored value, or the default when the file does not exist yet.
+    pub fn load<T: DeserializeOwned + Default>(&self) -> Result<T, Error> {
+        match fs::read(&self.path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| {
+                Error::Store(format!("Failed to parse {}: {}", self.path.display(), e))
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+            Err(e) => Err(Error::Store(format!(
+                "Failed to read {}: {}",
+                self.path.display(),
+                e
+            ))),
+        }
+    }
+
+    /// Writes the value, durably as `replace_file` does.
+    pub fn save<T: Serialize>(&self, value: &T) -> Result<(), Error> {
+        replace_file(&self.path, &serde_json::to_vec(value)?)
+    }
+}
+
+/// Replaces the file at `path` with `contents`. The temporary file is synced before it is
+/// renamed over the original, and the directory after, so the new contents survive a power
+/// loss too.
+pub fn replace_file(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    let parent = path.parent().filter(|p| !p.as_os_str().is_empty());
+    if let Some(parent) = parent {
+        fs::create_dir_all(parent)
+            .map_err(|e| Error::Store(format!("Failed to create {}: {}", parent.display(), e)))?;
+    }
+    let tmp = path.with_extension("tmp");
+    File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .map_err(|e| Error::Store(format!("Failed to write {}: {}", tmp.display(), e)))?;
+    fs::rename(&tmp, path)
+        .map_err(|e| Error::Store(format!("Failed to replace {}: {}", path.display(), e)))?;
+    // Makes the rename itself durable
+    if let Some(parent) = parent
+        && let Ok(dir) = File::open(parent)
+    {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// An append-only log of JSON lines. Each record is synced to disk before `append` returns.
+#[derive(Debug)]
+pub struct JsonLog {
+    path: PathBuf,
+    file: File,
+}
+
+impl JsonLog {
+    /// The file at `path`, or an empty string when it does not exist yet.
+    pub fn read(path: &Path) -> Result<String, Error> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(contents),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+            Err(e) => Err(Error::Store(format!(
+                "Failed to read {}: {}",
+                path.display(),
+                e
+            ))),
+        }
+    }
+
+    /// Parses the records of a log. A crash can cut the last line short; that write never
+    /// returned, so the line is skipped with a warning.
+    pub fn parse<R: DeserializeOwned>(path: &Path, contents: &str) -> Result<Vec<R>, Error> {
+        let lines: Vec<&str> = contents.lines().collect();
+        let mut records = Vec::with_capacity(lines.len());
+        for (index, line) in lines.iter().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(line) {
+                Ok(record) => records.push(record),
+                Err(e) if index + 1 == lines.len() => {
+                    warn!("Ignoring the incomplete last record of {}: {}", path.display(), e);
+                }
+                Err(e) => {
+                    return Err(Error::Store(format!(
+                        "Failed to parse line {} of {}: {}",
+                        index + 1,
+                        path.display(),
+                        e
+                    )));
+                }
+            }
+        }
+        Ok(records)
+    }
+
+    /// Replaces the log at `path` with `records`, durably as `replace_file` does, and opens it
+    /// for appending.
+    pub fn rewrite<R: Serialize>(
+        path: impl Into<PathBuf>,
+        records: impl IntoIterator<Item = R>,
+    ) -> Result<Self, Error> {
+        let path = path.into();
+        let mut contents = Vec::new();
+        for record in records {
+            serde_json::to_writer(&mut contents, &record)?;
+            contents.push(b'\n');
+        }
+        replace_file(&path, &contents)?;
+        let file = OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .map_err(|e| Error::Store(format!("Failed to open {}: {}", path.display(), e)))?;
+        Ok(JsonLog { path, file })
+    }
+
+    pub fn append<R: Serialize>(&mut self, record: &R) -> Result<(), Error> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        self.file
+            .write_all(&line)
+            .and_then(|()| self.file.sync_data())
+            .map_err(|e| Error::Store(format!("Failed to write {}: {}", self.path.display(), e)))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use actix_web::{HttpRequest, HttpResponse, Result as ActixResult, get, web};
+use futures_util::stream::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::auth::AuthContext;
+use crate::handlers::caller_tenant;
+use crate::status::StatusEvent;
+
+/// Events kept for clients resuming with `Last-Event-ID`.
+const REPLAY_BUFFER_SIZE: usize = 1024;
+const KEEP_ALIVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// A status event with its position in the stream.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct StreamEvent {
+    pub id: u64,
+    #[serde(flatten)]
+    pub event: StatusEvent,
+}
+
+struct StatusStream {
+    sender: broadcast::Sender<StreamEvent>,
+    // Recent events and the next id, guarded together so subscribers never miss or repeat one
+    recent: Mutex<(VecDeque<StreamEvent>, u64)>,
+}
+
+lazy_static::lazy_static! {
+    static ref STATUS_STREAM: StatusStream = StatusStream {
+        sender: broadcast::channel(REPLAY_BUFFER_SIZE).0,
+        recent: Mutex::new((VecDeque::with_capacity(REPLAY_BUFFER_SIZE), 1)),
+    };
+}
+
+/// Fans a recorded status transition out to every connected stream client.
+pub fn publish(event: &StatusEvent) {
+    let Ok(mut recent) = STATUS_STREAM.recent.lock() else {
+        return;
+    };
+    let (buffer, next_id) = &mut *recent;
+    let stream_event = StreamEvent {
+        id: *next_id,
+        event: event.clone(),
+    };
+    *next_id += 1;
+
+    if buffer.len() == REPLAY_BUFFER_SIZE {
+        buffer.pop_front();
+    }
+    buffer.push_back(stream_event.clone());
+    // No receivers is fine: nobody is watching
+    let _ = STATUS_STREAM.sender.send(stream_event);
+}
+
+/// Subscribes to new events, replaying buffered ones after `last_event_id` first.
+fn subscribe(last_event_id: Option<u64>) -> (Vec<StreamEvent>, broadcast::Receiver<StreamEvent>) {
+    let recent = STATUS_STREAM
+        .recent
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let receiver = STATUS_STREAM.sender.subscribe();
+    let backlog = match last_event_id {
+        Some(last_id) => recent
+            .0
+            .iter()
+            .filter(|e| e.id > last_id)
+            .cloned()
+            .collect(),
+        None => Vec::new(),
+    };
+    (backlog, receiver)
+}
+
+#[derive(Debug, Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StreamFilter {
+    pub user_id: Option<String>,
+    /// Ignored for callers confined to their own tenant.
+    pub tenant: Option<String>,
+    /// Replays buffered events after this id; `Last-Event-ID` takes precedence.
+    pub last_event_id: Option<u64>,
+}
+
+impl StreamFilter {
+    fn matches(&self, event: &StreamEvent) -> bool {
+        self.user_id
+            .as_ref()
+            .is_none_or(|user_id| &event.event.user_id == user_id)
+            && self
+                .tenant
+                .as_ref()
+                .is_none_or(|tenant| event.event.tenant.as_ref() == Some(tenant))
+    }
+}
+
+/// Next event for this filter, `None` on keep-alive timeout, and stop when the hub is gone.
+async fn next_event(
+    receiver: &mut broadcast::Receiver<StreamEvent>,
+    filter: &StreamFilter,
+) -> Result<Option<StreamEvent>, broadcast::error::RecvError> {
+    let deadline = tokio::time::sleep(KEEP_ALIVE_INTERVAL);
+    tokio::pin!(deadline);
+    loop {
+        tokio::select! {
+            received = receiver.recv() => match received {
+                Ok(event) if filter.matches(&event) => return Ok(Some(event)),
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    // The client can catch up by reconnecting with its last event id
+                    warn!("Status stream client lagged, skipped {} events", skipped);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            },
+            _ = &mut deadline => return Ok(None),
+        }
+    }
+}
+
+fn sse_frame(event: &StreamEvent) -> web::Bytes {
+    let data = serde_json::to_string(event).unwrap_or_default();
+    web::Bytes::from(format!("id: {}\nevent: status\ndata: {}\n\n", event.id, data))
+}
+
+/// Server-sent events with every status change, optionally for a single user.
+#[utoipa::path(
+    tag = "status",
+    params(StreamFilter),
+    responses((status = 200, description = "`status` events", content_type = "text/event-stream", body = StreamEvent)),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+#[get("/notifications/stream")]
+pub async fn stream_notification_status(
+    req: HttpRequest,
+    query: web::Query<StreamFilter>,
+    auth: Option<web::ReqData<AuthContext>>,
+) -> ActixResult<HttpResponse> {
+    let mut filter = query.into_inner();
+    // Callers only see their own tenant's events
+    if let Some(tenant) = caller_tenant(auth.as_deref()) {
+        filter.tenant = Some(tenant);
+    }
+    // EventSource sends the header on reconnect; the query parameter is for manual resumes
+    if let Some(last_id) = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+    {
+        filter.last_event_id = Some(last_id);
+    }
+
+    let (backlog, receiver) = subscribe(filter.last_event_id);
+    info!(
+        "📺 Status stream opened (user_id: {:?}, tenant: {:?}, replaying {} events)",
+        filter.user_id,
+        filter.tenant,
+        backlog.len()
+    );
+
+    let backlog: Vec<StreamEvent> = backlog.into_iter().filter(|e| filter.matches(e)).collect();
+    let replay = futures_util::stream::iter(
+        backlog
+            .into_iter()
+            .map(|e| Ok::<_, actix_web::Error>(sse_frame(&e))),
+    );
+    let live = futures_util::stream::unfold((receiver, filter), |(mut receiver, filter)| async move {
+        match next_event(&mut receiver, &filter).await {
+            Ok(Some(event)) => Some((Ok(sse_frame(&event)), (receiver, filter))),
+            Ok(None) => Some((Ok(web::Bytes::from_static(b": keep-alive\n\n")), (receiver, filter))),
+            Err(_) => None,
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(replay.chain(live)))
+}
+
+/// The status stream over a WebSocket, one JSON event per text message.
+#[utoipa::path(
+    tag = "status",
+    params(StreamFilter),
+    responses((status = 101, description = "Switched to WebSocket; messages are status events", body = StreamEvent)),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+#[get("/notifications/stream/ws")]
+pub async fn stream_notification_status_ws(
+    req: HttpRequest,
+    body: web::Payload,
+    query: web::Query<StreamFilter>,
+    auth: Option<web::ReqData<AuthContext>>,
+) -> ActixResult<HttpResponse> {
+    let mut filter = query.into_inner();
+    // Callers only see their own tenant's events
+    if let Some(tenant) = caller_tenant(auth.as_deref()) {
+        filter.tenant = Some(tenant);
+    }
+    let (response, mut session, mut msg_stream) = actix_ws::handle(&req, body)?;
+    let (backlog, mut receiver) = subscribe(filter.last_event_id);
+    info!(
+        "📺 Status WebSocket opened (user_id: {:?}, tenant: {:?}, replaying {} events)",
+        filter.user_id,
+        filter.tenant,
+        backlog.len()
+    );
+
+    actix_web::rt::spawn(async move {
+        for event in backlog.iter().filter(|e| filter.matches(e)) {
+            let text = serde_json::to_string(event).unwrap_or_default();
+            if session.text(text).await.is_err() {
+                return;
+            }
+        }
+
+        loop {
+            tokio::select! {
+                event = next_event(&mut receiver, &filter) => match event {
+                    Ok(Some(event)) => {
+                        let text = serde_json::to_string(&event).unwrap_or_default();
+                        if session.text(text).await.is_err() {
+                            return;
+                        }
+                    }
+                    Ok(None) => {
+                        if session.ping(b"").await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(_) => break,
+                },
+                msg = msg_stream.next() => match msg {
+                    Some(Ok(actix_ws::Message::Ping(bytes))) => {
+                        if session.pong(&bytes).await.is_err() {
+                            return;
+                        }
+                    }
+                    Some(Ok(actix_ws::Message::Close(_))) | None | Some(Err(_)) => break,
+                    Some(Ok(_)) => {}
+                },
+            }
+        }
+        let _ = session.close(None).await;
+    });
+
+    Ok(response)
+}
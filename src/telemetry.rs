@@ -0,0 +1,428 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use actix_web::{
+    Error as ActixError,
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+};
+use chrono::{SecondsFormat, Utc};
+use lapin::types::{AMQPValue, FieldTable};
+use opentelemetry::propagation::{Extractor, Injector};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::{Context, global};
+use opentelemetry_otlp::{SpanExporter as OtlpExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::error::{OTelSdkError, OTelSdkResult};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{SdkTracerProvider, SpanData, SpanExporter};
+use serde_json::{Value, json};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Instrument, Span, Subscriber, info};
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::fmt::format::{JsonFields, Writer};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormattedFields};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{
+    EnvFilter, Layer, Registry, layer::SubscriberExt, reload, util::SubscriberInitExt,
+};
+
+use crate::config::Config;
+use crate::error::{Error, FieldError};
+
+/// How log lines are written: human-readable text or one JSON object per line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!(
+                "Unknown LOG_FORMAT '{}', expected 'text' or 'json'",
+                other
+            )),
+        }
+    }
+}
+
+/// The active log filter, which can be replaced while the process runs.
+pub struct LogFilter {
+    handle: reload::Handle<EnvFilter, Registry>,
+    directives: Mutex<String>,
+    /// The binary's filter when `RUST_LOG` is unset.
+    default: String,
+}
+
+impl LogFilter {
+    pub fn default_directives(&self) -> &str {
+        &self.default
+    }
+
+    /// Directives in `RUST_LOG` syntax.
+    pub fn current(&self) -> String {
+        self.directives
+            .lock()
+            .map(|d| d.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn set(&self, directives: &str) -> Result<(), Error> {
+        let filter = EnvFilter::try_new(directives)
+            .map_err(|e| Error::Validation(vec![FieldError::new("filter", e.to_string())]))?;
+        self.handle
+            .reload(filter)
+            .map_err(|e| Error::Config(format!("Failed to replace log filter: {}", e)))?;
+        if let Ok(mut current) = self.directives.lock() {
+            *current = directives.to_string();
+        }
+        Ok(())
+    }
+}
+
+// Singleton global
+lazy_static::lazy_static! {
+    static ref LOG_FILTER: tokio::sync::OnceCell<LogFilter> = tokio::sync::OnceCell::new();
+}
+
+pub fn get_log_filter() -> Result<&'static LogFilter, Error> {
+    LOG_FILTER
+        .get()
+        .ok_or_else(|| Error::Config("Logging not initialized".to_string()))
+}
+
+/// Feeds `tracing` spans to the OpenTelemetry tracer.
+pub type TraceLayer<S> = OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>;
+
+/// Starts exporting spans to the configured OTLP endpoint or trace file and returns the layer
+/// that feeds them from `tracing`. `None` when neither is configured.
+pub fn otel_layer<S>(
+    config: &Config,
+    default_service_name: &str,
+) -> Result<Option<(TraceLayer<S>, SdkTracerProvider)>, Error>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+{
+    let service_name = config
+        .otel_service_name
+        .clone()
+        .unwrap_or_else(|| default_service_name.to_string());
+    let builder = SdkTracerProvider::builder()
+        .with_resource(Resource::builder().with_service_name(service_name).build());
+    let provider = match (
+        &config.otel_exporter_otlp_endpoint,
+        &config.otel_traces_file,
+    ) {
+        (Some(endpoint), _) => {
+            let exporter = OtlpExporter::builder()
+                .with_http()
+                .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+                .build()
+                .map_err(|e| Error::Config(format!("Invalid OTLP exporter: {}", e)))?;
+            builder.with_batch_exporter(exporter).build()
+        }
+        (None, Some(path)) => builder
+            .with_simple_exporter(FileExporter::create(path)?)
+            .build(),
+        (None, None) => return Ok(None),
+    };
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    global::set_tracer_provider(provider.clone());
+    let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("notification-service"));
+    Ok(Some((layer, provider)))
+}
+
+/// Sets up logging for a binary: the filter comes from `RUST_LOG` or `default_filter`, the
+/// format from `LOG_FORMAT`, and spans are exported when the configuration asks for it.
+/// The configuration is optional so a binary can still log why loading it failed.
+pub fn init_tracing(
+    config: Option<&Config>,
+    service_name: &str,
+    default_filter: &str,
+) -> Result<Option<SdkTracerProvider>, Error> {
+    let directives = config
+        .and_then(|c| c.log_filter.clone())
+        .unwrap_or_else(|| default_filter.to_string());
+    let filter = EnvFilter::try_new(&directives)
+        .map_err(|e| Error::Config(format!("Invalid RUST_LOG '{}': {}", directives, e)))?;
+    let (filter, handle) = reload::Layer::new(filter);
+    let format = config.map(|c| c.log_format).unwrap_or(LogFormat::Text);
+    let output = match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .fmt_fields(JsonFields::new())
+            .event_format(JsonFormat)
+            .boxed(),
+    };
+
+    let (otel, provider) = match config.map(|c| otel_layer(c, service_name)).transpose()? {
+        Some(Some((layer, provider))) => (Some(layer), Some(provider)),
+        _ => (None, None),
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(output)
+        .with(otel)
+        .try_init()
+        .map_err(|e| Error::Config(format!("Failed to set up logging: {}", e)))?;
+    LOG_FILTER
+        .set(LogFilter {
+            handle,
+            directives: Mutex::new(directives),
+            default: default_filter.to_string(),
+        })
+        .map_err(|_| Error::Config("Failed to set log filter".to_string()))?;
+
+    if let Some(config) = config
+        && provider.is_some()
+    {
+        let destination = config
+            .otel_exporter_otlp_endpoint
+            .as_ref()
+            .or(config.otel_traces_file.as_ref());
+        info!(
+            "🔭 Exporting traces to {}",
+            destination.map(String::as_str).unwrap_or_default()
+        );
+    }
+    Ok(provider)
+}
+
+/// Adds the notification being handled to the current span, so every log line written while
+/// handling it carries `notification_id` and `user_id`.
+pub fn record_notification(notification_id: &impl std::fmt::Display, user_id: &str) {
+    let span = Span::current();
+    span.record("notification_id", tracing::field::display(notification_id));
+    span.record("user_id", user_id);
+}
+
+/// Writes each event as one JSON object with `timestamp`, `level`, `target`, `span` and
+/// `message`, plus the fields of the event and of every span it happened in. Span fields are
+/// merged in at the top level so `notification_id`, `user_id`, `delivery_tag` and `attempt`
+/// are found under the same name whichever span recorded them.
+struct JsonFormat;
+
+impl<S> FormatEvent<S, JsonFields> for JsonFormat
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, JsonFields>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        let metadata = event.metadata();
+        let mut line = serde_json::Map::new();
+        line.insert(
+            "timestamp".to_string(),
+            json!(Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true)),
+        );
+        line.insert("level".to_string(), json!(metadata.level().as_str()));
+        line.insert("target".to_string(), json!(metadata.target()));
+        if let Some(scope) = ctx.event_scope() {
+            for span in scope.from_root() {
+                line.insert("span".to_string(), json!(span.name()));
+                let extensions = span.extensions();
+                if let Some(fields) = extensions.get::<FormattedFields<JsonFields>>()
+                    && let Ok(Value::Object(fields)) = serde_json::from_str::<Value>(fields)
+                {
+                    // `otel.*` fields only steer the span export
+                    line.extend(fields.into_iter().filter(|(k, _)| !k.starts_with("otel.")));
+                }
+            }
+        }
+        event.record(&mut JsonVisitor(&mut line));
+        writeln!(writer, "{}", Value::Object(line))
+    }
+}
+
+struct JsonVisitor<'a>(&'a mut serde_json::Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), json!(format!("{:?}", value)));
+    }
+}
+
+/// Flushes buffered spans; call before the process exits.
+pub fn shutdown(provider: Option<SdkTracerProvider>) {
+    if let Some(provider) = provider
+        && let Err(e) = provider.shutdown()
+    {
+        eprintln!("Failed to flush traces: {}", e);
+    }
+}
+
+/// Writes every finished span as one JSON line, for tests and local debugging.
+#[derive(Debug)]
+pub struct FileExporter {
+    file: Mutex<File>,
+}
+
+impl FileExporter {
+    pub fn create(path: &str) -> Result<Self, Error> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| Error::Config(format!("Cannot open trace file {}: {}", path, e)))?;
+        Ok(FileExporter {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl SpanExporter for FileExporter {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|e| OTelSdkError::InternalFailure(e.to_string()))?;
+        for span in batch {
+            let attributes: serde_json::Map<String, serde_json::Value> = span
+                .attributes
+                .iter()
+                .map(|kv| (kv.key.to_string(), json!(kv.value.to_string())))
+                .collect();
+            let line = json!({
+                "trace_id": span.span_context.trace_id().to_string(),
+                "span_id": span.span_context.span_id().to_string(),
+                "parent_span_id": span.parent_span_id.to_string(),
+                "name": span.name,
+                "kind": format!("{:?}", span.span_kind),
+                "attributes": attributes,
+            });
+            writeln!(file, "{}", line).map_err(|e| OTelSdkError::InternalFailure(e.to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads and writes W3C trace context in AMQP message headers.
+struct HeaderCarrier<'a>(&'a mut FieldTable);
+
+impl Injector for HeaderCarrier<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        self.0
+            .insert(key.into(), AMQPValue::LongString(value.into()));
+    }
+}
+
+struct HeaderExtractor<'a>(&'a FieldTable);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        match self.0.inner().get(key)? {
+            AMQPValue::LongString(value) => std::str::from_utf8(value.as_bytes()).ok(),
+            _ => None,
+        }
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.inner().keys().map(|k| k.as_str()).collect()
+    }
+}
+
+/// Adds the current span's `traceparent` (and `tracestate`) to outgoing message headers.
+pub fn inject_context(headers: &mut FieldTable) {
+    let context = Span::current().context();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut HeaderCarrier(headers))
+    });
+}
+
+/// The current span's `traceparent` (and `tracestate`), for work that is published later.
+pub fn current_trace_context() -> HashMap<String, String> {
+    let context = Span::current().context();
+    let mut carrier = HashMap::new();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut carrier));
+    carrier
+}
+
+/// A trace context kept by `current_trace_context`.
+pub fn stored_context(carrier: &HashMap<String, String>) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(carrier))
+}
+
+/// The trace context a message was published with, if any.
+pub fn extract_context(headers: Option<&FieldTable>) -> Context {
+    match headers {
+        Some(headers) => global::get_text_map_propagator(|propagator| {
+            propagator.extract(&HeaderExtractor(headers))
+        }),
+        None => Context::new(),
+    }
+}
+
+struct RequestHeaders<'a>(&'a actix_web::http::header::HeaderMap);
+
+impl Extractor for RequestHeaders<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+/// Runs every request in its own span, continuing the caller's trace when it sent `traceparent`.
+pub async fn trace_request(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, ActixError> {
+    let route = req
+        .match_pattern()
+        .unwrap_or_else(|| req.path().to_string());
+    let span = tracing::info_span!(
+        "http_request",
+        otel.name = format!("{} {}", req.method(), route),
+        otel.kind = "server",
+        http.request.method = %req.method(),
+        http.route = %route,
+        http.response.status_code = tracing::field::Empty,
+        notification_id = tracing::field::Empty,
+        user_id = tracing::field::Empty,
+    );
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&RequestHeaders(req.headers()))
+    });
+    let _ = span.set_parent(parent);
+
+    let response = next.call(req).instrument(span.clone()).await?;
+    span.record("http.response.status_code", response.status().as_u16());
+    Ok(response)
+}
@@ -0,0 +1,133 @@
+use crate::secret::SecretString;
+
+/// Tenant used when `TENANTS` is not set: everything runs on the configured vhost, unprefixed.
+pub const DEFAULT_TENANT: &str = "default";
+
+/// How a tenant's exchanges and queues are kept apart from the other tenants'.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Isolation {
+    /// The broker connection's own vhost with unprefixed names. Only used by the default tenant.
+    Shared,
+    /// A dedicated vhost; exchange and queue names are not prefixed.
+    Vhost(String),
+    /// The shared vhost, with every exchange and queue name prefixed by `<prefix>.`.
+    Prefix(String),
+}
+
+/// A product served by this deployment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TenantConfig {
+    pub name: String,
+    pub isolation: Isolation,
+    /// Unacknowledged deliveries the worker takes from this tenant's queues at once.
+    pub prefetch: u16,
+}
+
+impl TenantConfig {
+    pub fn default_tenant(prefetch: u16) -> Self {
+        TenantConfig {
+            name: DEFAULT_TENANT.to_string(),
+            isolation: Isolation::Shared,
+            prefetch,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Name prefix for the tenant's exchanges and queues, if any.
+    pub fn prefix(&self) -> Option<&str> {
+        match &self.isolation {
+            Isolation::Prefix(prefix) => Some(prefix),
+            _ => None,
+        }
+    }
+
+    /// AMQP URL of the tenant's vhost, derived from the base URL.
+    pub fn amqp_url(&self, base_url: &SecretString) -> SecretString {
+        match &self.isolation {
+            Isolation::Vhost(vhost) => url_with_vhost(base_url.expose_secret(), vhost).into(),
+            _ => base_url.clone(),
+        }
+    }
+}
+
+/// Parses `name=vhost:<vhost>,prefetch:<n>;name=prefix:<prefix>` into tenants.
+/// Each tenant needs exactly one of `vhost` or `prefix`; `prefetch` defaults to `default_prefetch`.
+pub fn parse_tenants(raw: &str, default_prefetch: u16) -> Result<Vec<TenantConfig>, String> {
+    let mut tenants: Vec<TenantConfig> = Vec::new();
+    for entry in raw.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let (name, options) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid tenant entry '{}': expected name=options", entry))?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(format!("Invalid tenant entry '{}': empty name", entry));
+        }
+
+        let mut isolation = None;
+        let mut prefetch = default_prefetch;
+        for option in options.split(',').map(str::trim).filter(|o| !o.is_empty()) {
+            let (key, value) = option.split_once(':').ok_or_else(|| {
+                format!(
+                    "Invalid option '{}' for tenant '{}': expected key:value",
+                    option, name
+                )
+            })?;
+            let value = value.trim();
+            match (key.trim(), isolation.is_some()) {
+                ("vhost" | "prefix", true) => {
+                    return Err(format!("Tenant '{}' sets both vhost and prefix", name));
+                }
+                ("vhost", false) if !value.is_empty() => {
+                    isolation = Some(Isolation::Vhost(value.to_string()))
+                }
+                ("prefix", false) if !value.is_empty() => {
+                    isolation = Some(Isolation::Prefix(value.to_string()))
+                }
+                ("prefetch", _) => {
+                    prefetch = value.parse().ok().filter(|p| *p > 0).ok_or_else(|| {
+                        format!("Invalid prefetch '{}' for tenant '{}'", value, name)
+                    })?
+                }
+                _ => return Err(format!("Invalid option '{}' for tenant '{}'", option, name)),
+            }
+        }
+        let isolation =
+            isolation.ok_or_else(|| format!("Tenant '{}' needs a vhost or a prefix", name))?;
+
+        if tenants.iter().any(|t| t.name == name) {
+            return Err(format!("Tenant '{}' is defined twice", name));
+        }
+        if tenants.iter().any(|t| t.isolation == isolation) {
+            return Err(format!(
+                "Tenant '{}' shares its vhost or prefix with another tenant",
+                name
+            ));
+        }
+        tenants.push(TenantConfig {
+            name: name.to_string(),
+            isolation,
+            prefetch,
+        });
+    }
+    if tenants.is_empty() {
+        return Err("Tenant list is empty".to_string());
+    }
+    Ok(tenants)
+}
+
+/// Replaces the vhost (path) of an AMQP URL, keeping credentials, host and query parameters.
+pub fn url_with_vhost(url: &str, vhost: &str) -> String {
+    let vhost = vhost.replace('/', "%2f");
+    let (scheme, rest) = url.split_once("://").unwrap_or(("amqp", url));
+    let (authority, query) = match rest.find(['/', '?']) {
+        Some(index) => {
+            let (authority, tail) = rest.split_at(index);
+            (authority, tail.find('?').map(|q| &tail[q..]).unwrap_or(""))
+        }
+        None => (rest, ""),
+    };
+    format!("{}://{}/{}{}", scheme, authority, vhost, query)
+}
@@ -0,0 +1,351 @@
+use lapin::{
+    Channel, ExchangeKind,
+    options::*,
+    types::{AMQPValue, FieldTable},
+};
+use serde_json::Value;
+use tracing::info;
+
+use crate::config::Config;
+use crate::models::{MAX_PRIORITY, Notification, Priority};
+use crate::status::DeliveryState;
+use crate::tenant::TenantConfig;
+
+pub const DEAD_LETTER_ROUTING_KEY: &str = "dead_letter";
+pub const CALLBACK_BINDING: &str = "callback.#";
+/// Unused per-server queues are deleted after a week.
+pub const INSTANCE_QUEUE_EXPIRES_MS: u32 = 7 * 24 * 60 * 60 * 1000;
+
+/// A queue and the topic patterns it is bound with on the delayed exchange.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub queue: String,
+    pub bindings: Vec<String>,
+}
+
+impl Route {
+    pub fn new(queue: &str, bindings: &[&str]) -> Self {
+        Route {
+            queue: queue.to_string(),
+            bindings: bindings.iter().map(|b| b.to_string()).collect(),
+        }
+    }
+}
+
+/// Exchanges, queues and bindings of one tenant, used by the server and the worker.
+#[derive(Debug, Clone)]
+pub struct Topology {
+    /// Prepended as `<prefix>.` to every name when the tenant shares a vhost with others.
+    pub prefix: Option<String>,
+    pub delayed_exchange: String,
+    pub dlx_exchange: String,
+    pub dead_letter_queue: String,
+    /// Fanout of delivery status events from the worker. Every server binds its own exclusive
+    /// queue, so each replica sees every event.
+    pub status_exchange: String,
+    /// Fanout of inbox entries from the worker and read markers from the servers. Every server
+    /// keeps a durable queue on it, see [`Topology::inbox_queue`].
+    pub inbox_exchange: String,
+    /// Webhook jobs, published on the delayed exchange so retries can back off.
+    pub callback_queue: String,
+    pub routes: Vec<Route>,
+}
+
+impl Topology {
+    pub fn for_tenant(config: &Config, tenant: &TenantConfig) -> Self {
+        let prefix = tenant.prefix().map(str::to_string);
+        let qualify = |name: &str| qualified_name(prefix.as_deref(), name);
+        Topology {
+            delayed_exchange: qualify(&config.delayed_exchange),
+            dlx_exchange: qualify(&config.dlx_exchange),
+            dead_letter_queue: qualify(&config.dead_letter_queue),
+            status_exchange: qualify(&config.status_exchange),
+            inbox_exchange: qualify(&config.inbox_exchange),
+            callback_queue: qualify(&config.callback_queue),
+            routes: config
+                .routing_table
+                .iter()
+                .map(|route| Route {
+                    queue: qualify(&route.queue),
+                    bindings: route.bindings.clone(),
+                })
+                .collect(),
+            prefix,
+        }
+    }
+
+    /// This tenant's name for a queue from the routing table.
+    pub fn qualify(&self, name: &str) -> String {
+        qualified_name(self.prefix.as_deref(), name)
+    }
+
+    /// A server's own inbox queue. It outlives restarts so no entry is missed, and is deleted
+    /// by the broker after a week without a consumer, once the server is gone for good.
+    pub fn inbox_queue(&self, instance_id: &str) -> String {
+        format!("{}.{}", self.inbox_exchange, instance_id)
+    }
+
+    /// Declares and binds a server's inbox queue.
+    pub async fn declare_inbox_queue(
+        &self,
+        channel: &Channel,
+        instance_id: &str,
+    ) -> Result<String, lapin::Error> {
+        let queue = self.inbox_queue(instance_id);
+        let mut args = FieldTable::default();
+        args.insert(
+            "x-expires".into(),
+            AMQPValue::LongUInt(INSTANCE_QUEUE_EXPIRES_MS),
+        );
+        channel
+            .queue_declare(
+                &queue,
+                QueueDeclareOptions {
+                    durable: true,
+                    ..Default::default()
+                },
+                args,
+            )
+            .await?;
+        channel
+            .queue_bind(
+                &queue,
+                &self.inbox_exchange,
+                "",
+                QueueBindOptions::default(),
+                FieldTable::default(),
+            )
+            .await?;
+        Ok(queue)
+    }
+
+    /// Declares an exclusive, server-named status queue bound to the status exchange. The
+    /// broker deletes it with the connection.
+    pub async fn declare_status_queue(&self, channel: &Channel) -> Result<String, lapin::Error> {
+        let queue = channel
+            .queue_declare(
+                "",
+                QueueDeclareOptions {
+                    exclusive: true,
+                    auto_delete: true,
+                    ..Default::default()
+                },
+                FieldTable::default(),
+            )
+            .await?;
+        channel
+            .queue_bind(
+                queue.name().as_str(),
+                &self.status_exchange,
+                "",
+                QueueBindOptions::default(),
+                FieldTable::default(),
+            )
+            .await?;
+        Ok(queue.name().to_string())
+    }
+
+    pub fn queue_names(&self) -> Vec<&str> {
+        self.routes.iter().map(|r| r.queue.as_str()).collect()
+    }
+
+    /// Routing key for a notification: `notify.<type>.<channel>.<priority>`.
+    pub fn routing_key(notification: &Notification) -> String {
+        Self::routing_key_for(
+            &notification.notification_type,
+            &notification.channel,
+            notification.priority,
+        )
+    }
+
+    /// Routing key for an already serialized notification (e.g. when requeueing).
+    pub fn routing_key_for_value(json_value: &Value) -> String {
+        let notification_type = json_value
+            .get("notification_type")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default();
+        let channel = json_value
+            .get("channel")
+            .and_then(|v| v.as_str())
+            .unwrap_or(crate::models::DEFAULT_CHANNEL);
+        Self::routing_key_for(notification_type, channel, priority_of_value(json_value))
+    }
+
+    pub fn routing_key_for(notification_type: &str, channel: &str, priority: Priority) -> String {
+        format!(
+            "notify.{}.{}.{}",
+            routing_word(notification_type),
+            routing_word(channel),
+            priority.as_str()
+        )
+    }
+
+    /// Routing key for a webhook job: `callback.<outcome>`.
+    pub fn callback_routing_key(outcome: DeliveryState) -> String {
+        format!("callback.{}", outcome.as_str())
+    }
+
+    /// Declares every exchange, queue and binding. Safe to call from several processes.
+    pub async fn declare(&self, channel: &Channel) -> Result<(), lapin::Error> {
+        let durable = ExchangeDeclareOptions {
+            durable: true,
+            ..Default::default()
+        };
+
+        let mut delayed_args = FieldTable::default();
+        delayed_args.insert(
+            "x-delayed-type".into(),
+            AMQPValue::LongString("topic".into()),
+        );
+        channel
+            .exchange_declare(
+                &self.delayed_exchange,
+                ExchangeKind::Custom("x-delayed-message".to_string()),
+                durable,
+                delayed_args,
+            )
+            .await?;
+        channel
+            .exchange_declare(
+                &self.dlx_exchange,
+                ExchangeKind::Direct,
+                durable,
+                FieldTable::default(),
+            )
+            .await?;
+
+        let queue_options = QueueDeclareOptions {
+            durable: true,
+            ..Default::default()
+        };
+
+        channel
+            .queue_declare(&self.dead_letter_queue, queue_options, FieldTable::default())
+            .await?;
+        channel
+            .queue_bind(
+                &self.dead_letter_queue,
+                &self.dlx_exchange,
+                DEAD_LETTER_ROUTING_KEY,
+                QueueBindOptions::default(),
+                FieldTable::default(),
+            )
+            .await?;
+
+        for exchange in [&self.status_exchange, &self.inbox_exchange] {
+            channel
+                .exchange_declare(exchange, ExchangeKind::Fanout, durable, FieldTable::default())
+                .await?;
+        }
+
+        channel
+            .queue_declare(&self.callback_queue, queue_options, self.queue_arguments())
+            .await?;
+        channel
+            .queue_bind(
+                &self.callback_queue,
+                &self.delayed_exchange,
+                CALLBACK_BINDING,
+                QueueBindOptions::default(),
+                FieldTable::default(),
+            )
+            .await?;
+
+        for route in &self.routes {
+            channel
+                .queue_declare(&route.queue, queue_options, self.queue_arguments())
+                .await?;
+            for binding in &route.bindings {
+                channel
+                    .queue_bind(
+                        &route.queue,
+                        &self.delayed_exchange,
+                        binding,
+                        QueueBindOptions::default(),
+                        FieldTable::default(),
+                    )
+                    .await?;
+            }
+            info!("🔗 Queue {} bound with {:?}", route.queue, route.bindings);
+        }
+
+        Ok(())
+    }
+
+    fn queue_arguments(&self) -> FieldTable {
+        let mut args = FieldTable::default();
+        args.insert(
+            "x-dead-letter-exchange".into(),
+            AMQPValue::LongString(self.dlx_exchange.clone().into()),
+        );
+        args.insert(
+            "x-dead-letter-routing-key".into(),
+            AMQPValue::LongString(DEAD_LETTER_ROUTING_KEY.into()),
+        );
+        args.insert(
+            "x-max-priority".into(),
+            AMQPValue::ShortShortUInt(MAX_PRIORITY),
+        );
+        args
+    }
+}
+
+fn qualified_name(prefix: Option<&str>, name: &str) -> String {
+    match prefix {
+        Some(prefix) => format!("{}.{}", prefix, name),
+        None => name.to_string(),
+    }
+}
+
+/// Priority of an already serialized notification, `normal` when absent or invalid.
+pub fn priority_of_value(json_value: &Value) -> Priority {
+    json_value
+        .get("priority")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+/// Routing key words cannot contain the topic separator or wildcards.
+fn routing_word(word: &str) -> String {
+    if word.is_empty() {
+        return "unknown".to_string();
+    }
+    word.replace(['.', '*', '#'], "_")
+}
+
+/// Default routing table: immediate notifications get their own queue so they
+/// never wait behind delayed and scheduled ones.
+pub const DEFAULT_ROUTING_TABLE: &str =
+    "immediate_queue=notify.immediate.#;scheduled_queue=notify.delayed.#,notify.scheduled.#";
+
+pub fn default_routing_table() -> Vec<Route> {
+    parse_routing_table(DEFAULT_ROUTING_TABLE).expect("default routing table is valid")
+}
+
+/// Parses `queue=pattern,pattern;queue=pattern` into routes.
+pub fn parse_routing_table(raw: &str) -> Result<Vec<Route>, String> {
+    let mut routes = Vec::new();
+    for entry in raw.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let (queue, patterns) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid routing entry '{}': expected queue=pattern", entry))?;
+        let queue = queue.trim();
+        let bindings: Vec<String> = patterns
+            .split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(str::to_string)
+            .collect();
+        if queue.is_empty() || bindings.is_empty() {
+            return Err(format!("Invalid routing entry '{}': empty queue or pattern", entry));
+        }
+        routes.push(Route {
+            queue: queue.to_string(),
+            bindings,
+        });
+    }
+    if routes.is_empty() {
+        return Err("Routing table is empty".to_string());
+    }
+    Ok(routes)
+}
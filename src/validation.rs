@@ -0,0 +1,343 @@
+use std::net::{IpAddr, Ipv4Addr};
+
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+
+use crate::callbacks::callback_secret;
+use crate::config::Config;
+use crate::error::{Error, FieldError};
+use crate::models::{Notification, ScheduleAtRequest, ScheduleNotificationRequest, expiry_after};
+
+pub const MAX_USER_ID_LENGTH: usize = 128;
+pub const MAX_URL_LENGTH: usize = 2048;
+/// Values a client may send as `notification_type`; the server overwrites it per endpoint.
+const NOTIFICATION_TYPES: [&str; 3] = ["immediate", "delayed", "scheduled"];
+
+/// Collects every problem in a request so clients can fix them in one go.
+#[derive(Debug, Default)]
+pub struct FieldErrors(Vec<FieldError>);
+
+impl FieldErrors {
+    pub fn check(&mut self, ok: bool, field: &str, message: impl Into<String>) {
+        if !ok {
+            self.0.push(FieldError::new(field, message));
+        }
+    }
+
+    pub fn into_result(self) -> Result<(), Error> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Validation(self.0))
+        }
+    }
+}
+
+/// Validates a notification for `/notify` and `/notify-delayed`, or a scheduled payload.
+/// `due_at` is when the notification becomes deliverable.
+pub fn validate_notification(
+    notification: &Notification,
+    due_at: DateTime<Utc>,
+    config: &Config,
+) -> Result<(), Error> {
+    let mut errors = FieldErrors::default();
+    check_notification(&mut errors, "", notification, due_at, config);
+    errors.into_result()
+}
+
+/// Validates a `/notify-delayed` request, which also needs a delay within bounds.
+pub fn validate_delayed(notification: &Notification, config: &Config) -> Result<(), Error> {
+    let mut errors = FieldErrors::default();
+    // An out-of-range delay is reported below; the other checks then use the current time
+    let now = Utc::now();
+    let due_at = i64::try_from(notification.delay_secs)
+        .ok()
+        .and_then(ChronoDuration::try_seconds)
+        .and_then(|delay| now.checked_add_signed(delay))
+        .unwrap_or(now);
+    check_notification(&mut errors, "", notification, due_at, config);
+    errors.check(
+        (1..=config.max_delay_hop_secs).contains(&notification.delay_secs),
+        "delay_secs",
+        format!("must be between 1 and {} seconds", config.max_delay_hop_secs),
+    );
+    errors.into_result()
+}
+
+pub fn validate_schedule_at(request: &ScheduleAtRequest, config: &Config) -> Result<(), Error> {
+    let mut errors = FieldErrors::default();
+    check_user_id(&mut errors, "user_id", &request.user_id);
+    check_message(&mut errors, "message", &request.message, config);
+    check_channel(&mut errors, "channel", &request.channel, config);
+    check_scheduled_at(&mut errors, request.scheduled_at, config);
+    check_expiry(
+        &mut errors,
+        "",
+        request.expires_at,
+        request.ttl_secs,
+        request.scheduled_at,
+        config,
+    );
+    check_callback(
+        &mut errors,
+        "",
+        request.callback_url.as_deref(),
+        request.callback_secret_id.as_deref(),
+    );
+    errors.into_result()
+}
+
+pub fn validate_schedule_request(
+    request: &ScheduleNotificationRequest,
+    config: &Config,
+) -> Result<(), Error> {
+    let mut errors = FieldErrors::default();
+    check_user_id(&mut errors, "user_id", &request.user_id);
+    check_scheduled_at(&mut errors, request.scheduled_at, config);
+
+    match serde_json::from_value::<Notification>(request.payload.clone()) {
+        Ok(notification) => {
+            check_notification(
+                &mut errors,
+                "payload.",
+                &notification,
+                request.scheduled_at,
+                config,
+            );
+            // The payload is what gets delivered, so it cannot target someone else
+            errors.check(
+                notification.user_id == request.user_id,
+                "payload.user_id",
+                "must match user_id",
+            );
+        }
+        Err(e) => errors.check(false, "payload", format!("is not a notification: {}", e)),
+    }
+    errors.into_result()
+}
+
+/// Checks that a callback URL only resolves to public addresses, so callbacks cannot reach the
+/// deployment's own network or a cloud metadata service. The worker checks every connection
+/// again, as DNS answers may change after the notification was accepted.
+pub async fn validate_callback_target(
+    prefix: &str,
+    url: Option<&str>,
+    config: &Config,
+) -> Result<(), Error> {
+    let Some(url) = url.and_then(|url| reqwest::Url::parse(url).ok()) else {
+        return Ok(());
+    };
+    if config.callback_allow_private_targets {
+        return Ok(());
+    }
+    let field = format!("{}callback_url", prefix);
+    let Some(host) = url.host_str() else {
+        return Ok(());
+    };
+    // IPv6 literals come bracketed
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addresses: Vec<IpAddr> = match tokio::net::lookup_host((host, port)).await {
+        Ok(addresses) => addresses.map(|a| a.ip()).collect(),
+        Err(_) => {
+            return Err(Error::Validation(vec![FieldError::new(&field, "cannot be resolved")]));
+        }
+    };
+    if addresses.is_empty() || !addresses.into_iter().all(is_public_address) {
+        return Err(Error::Validation(vec![FieldError::new(
+            &field,
+            "must not point to a loopback, private, link-local or metadata address",
+        )]));
+    }
+    Ok(())
+}
+
+/// Whether a callback may connect to the address: no loopback, private (RFC 1918, unique
+/// local), shared (RFC 6598), link-local (which includes the 169.254.169.254 metadata service),
+/// unspecified, broadcast, multicast or documentation addresses.
+pub fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(v4) => is_public_v4(v4),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local fc00::/7, link-local fe80::/10, documentation 2001:db8::/32
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80
+                    || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+            }
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // Shared address space 100.64.0.0/10 and "this network" 0.0.0.0/8
+        || (a == 100 && (64..128).contains(&b))
+        || a == 0)
+}
+
+/// Checks that `callback_secret_id` names one of the caller's tenant's callback secrets.
+pub fn validate_callback_secret(
+    prefix: &str,
+    secret_id: Option<&str>,
+    tenant: &str,
+) -> Result<(), Error> {
+    let mut errors = FieldErrors::default();
+    if let Some(id) = secret_id {
+        errors.check(
+            callback_secret(tenant, id).is_some(),
+            &format!("{}callback_secret_id", prefix),
+            "is not a callback secret of the tenant",
+        );
+    }
+    errors.into_result()
+}
+
+fn check_notification(
+    errors: &mut FieldErrors,
+    prefix: &str,
+    notification: &Notification,
+    due_at: DateTime<Utc>,
+    config: &Config,
+) {
+    let field = |name: &str| format!("{}{}", prefix, name);
+    check_user_id(errors, &field("user_id"), &notification.user_id);
+    check_message(errors, &field("message"), &notification.message, config);
+    check_channel(errors, &field("channel"), &notification.channel, config);
+    errors.check(
+        notification.notification_type.is_empty()
+            || NOTIFICATION_TYPES.contains(&notification.notification_type.as_str()),
+        &field("notification_type"),
+        format!("must be one of {}", NOTIFICATION_TYPES.join(", ")),
+    );
+    check_expiry(
+        errors,
+        prefix,
+        notification.expires_at,
+        notification.ttl_secs,
+        due_at,
+        config,
+    );
+    check_callback(
+        errors,
+        prefix,
+        notification.callback_url.as_deref(),
+        notification.callback_secret_id.as_deref(),
+    );
+}
+
+fn check_user_id(errors: &mut FieldErrors, field: &str, user_id: &str) {
+    errors.check(!user_id.trim().is_empty(), field, "is required");
+    errors.check(
+        user_id.chars().count() <= MAX_USER_ID_LENGTH,
+        field,
+        format!("must be at most {} characters", MAX_USER_ID_LENGTH),
+    );
+}
+
+fn check_message(errors: &mut FieldErrors, field: &str, message: &str, config: &Config) {
+    errors.check(!message.trim().is_empty(), field, "is required");
+    errors.check(
+        message.len() <= config.max_message_length,
+        field,
+        format!("must be at most {} bytes", config.max_message_length),
+    );
+}
+
+fn check_channel(errors: &mut FieldErrors, field: &str, channel: &str, config: &Config) {
+    errors.check(
+        config.allowed_channels.iter().any(|c| c == channel),
+        field,
+        format!("must be one of {}", config.allowed_channels.join(", ")),
+    );
+}
+
+fn check_scheduled_at(errors: &mut FieldErrors, scheduled_at: DateTime<Utc>, config: &Config) {
+    let now = Utc::now();
+    errors.check(
+        scheduled_at >= now - ChronoDuration::seconds(config.scheduler_grace_period_secs as i64),
+        "scheduled_at",
+        format!(
+            "must not be more than {} seconds in the past",
+            config.scheduler_grace_period_secs
+        ),
+    );
+    errors.check(
+        scheduled_at <= now + ChronoDuration::days(config.max_schedule_ahead_days as i64),
+        "scheduled_at",
+        format!(
+            "must not be more than {} days in the future",
+            config.max_schedule_ahead_days
+        ),
+    );
+}
+
+fn check_expiry(
+    errors: &mut FieldErrors,
+    prefix: &str,
+    expires_at: Option<DateTime<Utc>>,
+    ttl_secs: Option<u64>,
+    due_at: DateTime<Utc>,
+    config: &Config,
+) {
+    if let Some(expires_at) = expires_at {
+        errors.check(
+            expires_at > due_at,
+            &format!("{}expires_at", prefix),
+            "must be after the notification is due",
+        );
+    }
+    if let Some(ttl) = ttl_secs {
+        // A notification cannot usefully live longer than it may be scheduled ahead
+        let field = format!("{}ttl_secs", prefix);
+        let max_ttl = config.max_schedule_ahead_days.saturating_mul(86_400);
+        errors.check(ttl > 0, &field, "must be greater than 0");
+        errors.check(
+            ttl <= max_ttl,
+            &field,
+            format!("must be at most {} seconds", max_ttl),
+        );
+        errors.check(
+            ttl > max_ttl || expiry_after(due_at, ttl).is_some(),
+            &field,
+            "is out of range",
+        );
+    }
+}
+
+fn check_callback(
+    errors: &mut FieldErrors,
+    prefix: &str,
+    url: Option<&str>,
+    secret_id: Option<&str>,
+) {
+    let field = format!("{}callback_url", prefix);
+    match url {
+        Some(url) => {
+            let valid = reqwest::Url::parse(url)
+                .is_ok_and(|u| matches!(u.scheme(), "http" | "https") && u.has_host());
+            errors.check(valid, &field, "must be an absolute http(s) URL");
+            errors.check(
+                url.len() <= MAX_URL_LENGTH,
+                &field,
+                format!("must be at most {} characters", MAX_URL_LENGTH),
+            );
+        }
+        None => errors.check(
+            secret_id.is_none(),
+            &format!("{}callback_secret_id", prefix),
+            "requires callback_url",
+        ),
+    }
+}
@@ -1,4 +1,14 @@
 use crate::models;
+use crate::config::get_config;
+use crate::connection::{TenantPool, publish_confirmed};
+use crate::error::Error;
+use crate::metrics;
+use crate::telemetry::{extract_context, record_notification};
+use crate::health::{ConsumerState, LAST_MESSAGE, set_consumer_state};
+use crate::callbacks::{CallbackJob, enqueue_callback};
+use crate::inbox::{InboxEntry, InboxEvent, publish_inbox_event};
+use crate::status::{DeliveryState, StatusEvent, publish_status_event};
+use crate::topology::{DEAD_LETTER_ROUTING_KEY, Topology, priority_of_value};
 use chrono::{DateTime, Duration as ChronoDuration, Utc};
 use futures_util::stream::StreamExt;
 use lapin::message::Delivery;
@@ -8,126 +18,472 @@ use lapin::{
     types::{AMQPValue, FieldTable},
 };
 use serde_json::Value;
-use tracing::{error, info, warn};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::RwLock;
+use tracing::{Instrument, error, info, warn};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
-/// Main worker loop: consumes messages and handles graceful shutdown.
-pub async fn run_worker() -> Result<(), Box<dyn std::error::Error>> {
-    let pool = crate::connection::get_rabbitmq_pool()
-        .map_err(|e| format!("RabbitMQ pool not initialized: {}", e))?;
+/// Main worker loop: consumes every tenant's queues and handles graceful shutdown. Only
+/// returns `Ok` on shutdown; a consumer that fails is restarted on its own, so one tenant's
+/// broker going away never stops the others.
+pub async fn run_worker() -> Result<(), Error> {
+    let pool = crate::connection::get_rabbitmq_pool()?;
 
-    let channel = pool.get_channel().await?;
-    channel.basic_qos(1, BasicQosOptions::default()).await?;
-    channel
-        .queue_declare(
-            "main_queue",
-            QueueDeclareOptions {
-                passive: true,
-                ..Default::default()
-            },
-            FieldTable::default(),
-        )
-        .await?;
-
-    let mut consumer = channel
-        .basic_consume(
-            "main_queue",
-            "push_worker",
-            BasicConsumeOptions::default(),
-            FieldTable::default(),
-        )
-        .await?;
+    // Every delivery holds a read guard; shutdown takes the write guard to wait for them
+    let in_flight = Arc::new(RwLock::new(()));
+    let mut workers = tokio::task::JoinSet::new();
+    for tenant in pool.tenants() {
+        let in_flight = in_flight.clone();
+        workers.spawn(supervise(tenant, "worker", move |consuming| {
+            run_tenant_worker(tenant, in_flight.clone(), consuming)
+        }));
+        // Webhook callbacks run on their own consumer so slow targets never delay deliveries
+        workers.spawn(supervise(tenant, "callback worker", move |consuming| {
+            crate::callbacks::run_callback_worker(tenant, consuming)
+        }));
+    }
 
     let shutdown = tokio::signal::ctrl_c();
     tokio::pin!(shutdown);
 
+    tokio::select! {
+        Some(result) = workers.join_next() => {
+            // A supervisor only returns once its consumer cannot be restarted
+            workers.shutdown().await;
+            return Err(result.unwrap_or_else(|e| {
+                Error::Connection(format!("Worker task failed: {}", e))
+            }));
+        }
+        _ = &mut shutdown => {
+            info!("🛑 Shutdown signal received. Closing gracefully...");
+        }
+    }
+
+    // Stop consuming, then let deliveries already being processed finish
+    workers.shutdown().await;
+    let processing_timeout = processing_timeout();
+    if tokio::time::timeout(processing_timeout, in_flight.write())
+        .await
+        .is_err()
+    {
+        warn!("Deliveries still in flight after {:?}, they will be redelivered", processing_timeout);
+    }
+    Ok(())
+}
+
+/// Runs one tenant's consumer until it fails for good. A retryable failure, including the
+/// consumer stream ending, restarts it after `WORKER_RETRY_DELAY_SECS`; `get_channel`
+/// reconnects, failing over to another broker node. The consumer gives up after
+/// `WORKER_MAX_RETRIES` restarts in a row that never got back to consuming.
+async fn supervise<F, Fut>(tenant: &'static TenantPool, kind: &'static str, mut run: F) -> Error
+where
+    F: FnMut(Arc<AtomicBool>) -> Fut,
+    Fut: std::future::Future<Output = Result<std::convert::Infallible, Error>>,
+{
+    let mut retry_count = 0;
     loop {
-        tokio::select! {
-            delivery_result = consumer.next() => match delivery_result {
-                Some(Ok(delivery)) => handle_delivery(delivery).await,
-                Some(Err(e)) => handle_consume_error(e).await,
-                None => {
-                    warn!("Consumer stream ended");
-                    break;
-                }
-            },
-            _ = &mut shutdown => {
-                info!("🛑 Shutdown signal received. Closing gracefully...");
-                break;
+        let consuming = Arc::new(AtomicBool::new(false));
+        let Err(e) = run(consuming.clone()).await;
+        error!("Tenant {} {} failed: {}", tenant.name(), kind, e);
+        // Configuration problems fail the same way on every attempt
+        if !e.is_retryable() {
+            return e;
+        }
+        if consuming.load(Ordering::SeqCst) {
+            retry_count = 0;
+        }
+        retry_count += 1;
+        // The retry policy can change on reload
+        let (max_retries, retry_delay) = get_config()
+            .map(|c| (c.worker_max_retries, c.worker_retry_delay_secs))
+            .unwrap_or((3, 5));
+        if retry_count > max_retries {
+            error!("Tenant {} {}: max retries ({}) exceeded", tenant.name(), kind, max_retries);
+            return e;
+        }
+        let retry_delay = std::time::Duration::from_secs(retry_delay);
+        warn!(
+            "Restarting tenant {} {} in {:?} (attempt {}/{})",
+            tenant.name(),
+            kind,
+            retry_delay,
+            retry_count,
+            max_retries
+        );
+        tokio::time::sleep(retry_delay).await;
+    }
+}
+
+/// Consumes one tenant's queues. The tenant's prefetch caps its unacknowledged deliveries
+/// across all of its queues, so a busy tenant cannot take every worker slot.
+async fn run_tenant_worker(
+    tenant: &'static TenantPool,
+    in_flight: Arc<RwLock<()>>,
+    consuming: Arc<AtomicBool>,
+) -> Result<std::convert::Infallible, Error> {
+    let config = crate::config::init_config()?;
+
+    let channel = tenant.get_channel().await?;
+    channel
+        .basic_qos(tenant.prefetch(), BasicQosOptions { global: true })
+        .await?;
+
+    let mut consumers = Vec::new();
+    let mut consumed = Vec::new();
+    for queue in config.consumed_queues() {
+        let queue = tenant.topology().qualify(&queue);
+        channel
+            .queue_declare(
+                &queue,
+                QueueDeclareOptions {
+                    passive: true,
+                    ..Default::default()
+                },
+                FieldTable::default(),
+            )
+            .await?;
+
+        let tag = format!("push_worker.{}", queue);
+        let consumer = channel
+            .basic_consume(
+                &queue,
+                &tag,
+                BasicConsumeOptions::default(),
+                FieldTable::default(),
+            )
+            .await?;
+        set_consumer_state(&tag, tenant.name(), &queue, ConsumerState::Consuming);
+        consumed.push((tag, queue.clone()));
+        info!(
+            "👂 Consuming from queue: {} (tenant: {}, prefetch: {})",
+            queue,
+            tenant.name(),
+            tenant.prefetch()
+        );
+        consumers.push(consumer);
+    }
+    let mut consumer = futures_util::stream::select_all(consumers);
+    consuming.store(true, Ordering::SeqCst);
+
+    while let Some(delivery_result) = consumer.next().await {
+        match delivery_result {
+            Ok(delivery) => {
+                LAST_MESSAGE.beat();
+                let guard = in_flight.clone().read_owned().await;
+                tokio::spawn(async move {
+                    handle_delivery(tenant, delivery).await;
+                    drop(guard);
+                });
             }
+            Err(e) => handle_consume_error(e).await,
         }
     }
-    Ok(())
+    for (tag, queue) in &consumed {
+        set_consumer_state(tag, tenant.name(), queue, ConsumerState::Stopped);
+    }
+    // The broker cancelled the consumers or the channel closed
+    Err(Error::Connection(format!(
+        "Consumer stream of tenant {} ended",
+        tenant.name()
+    )))
+}
+
+/// A delivery being processed, remembering whether it was acked or nacked. Once settled, a
+/// later failure or timeout must not settle it again: the broker closes the channel, and every
+/// consumer on it, when a delivery tag is acknowledged twice.
+pub struct TrackedDelivery<'a> {
+    delivery: &'a Delivery,
+    settled: AtomicBool,
+}
+
+impl<'a> TrackedDelivery<'a> {
+    pub fn new(delivery: &'a Delivery) -> Self {
+        TrackedDelivery {
+            delivery,
+            settled: AtomicBool::new(false),
+        }
+    }
+
+    /// Acknowledges the delivery. It counts as settled even if the ack fails, since a
+    /// failed ack means the channel is gone and the broker requeues the message itself.
+    pub async fn ack(&self) -> Result<(), Error> {
+        self.settled.store(true, Ordering::SeqCst);
+        self.delivery.ack(BasicAckOptions::default()).await?;
+        Ok(())
+    }
+
+    pub async fn requeue(&self) -> Result<(), Error> {
+        self.settled.store(true, Ordering::SeqCst);
+        self.delivery
+            .nack(BasicNackOptions {
+                requeue: true,
+                ..Default::default()
+            })
+            .await?;
+        Ok(())
+    }
+
+    pub fn is_settled(&self) -> bool {
+        self.settled.load(Ordering::SeqCst)
+    }
+}
+
+impl std::ops::Deref for TrackedDelivery<'_> {
+    type Target = Delivery;
+
+    fn deref(&self) -> &Delivery {
+        self.delivery
+    }
 }
 
-/// Handles a single delivery, including timeout and error logging.
-async fn handle_delivery(delivery: Delivery) {
-    if let Err(e) = process_message_with_timeout(delivery).await {
+/// Handles a single delivery, including timeout and error logging. Runs in a span that
+/// continues the trace of the request that published the message.
+async fn handle_delivery(tenant: &'static TenantPool, delivery: Delivery) {
+    let span = tracing::info_span!(
+        "process_message",
+        otel.name = format!("process {}", delivery.routing_key),
+        otel.kind = "consumer",
+        messaging.rabbitmq.destination.routing_key = %delivery.routing_key,
+        tenant = tenant.name(),
+        delivery_tag = delivery.delivery_tag,
+        attempt = delivery_attempt(&delivery),
+        notification_id = tracing::field::Empty,
+        user_id = tracing::field::Empty,
+    );
+    let _ = span.set_parent(extract_context(delivery.properties.headers().as_ref()));
+    if let Err(e) = process_message_with_timeout(tenant, &TrackedDelivery::new(&delivery))
+        .instrument(span)
+        .await
+    {
         error!("Failed to process message: {}", e);
     }
 }
 
+/// Header counting how often the worker sent a message back to its queue after a retryable
+/// failure.
+const RETRY_COUNT_HEADER: &str = "x-retry-count";
+
+/// Which delivery of the message this is, starting at 1. Quorum queues count deliveries in
+/// `x-delivery-count`, and retries of the worker count in `x-retry-count`; otherwise classic
+/// queues only say whether the message was delivered before.
+fn delivery_attempt(delivery: &Delivery) -> u64 {
+    let count = |name: &str| {
+        delivery
+            .properties
+            .headers()
+            .as_ref()
+            .and_then(|headers| match headers.inner().get(name)? {
+                AMQPValue::LongLongInt(n) => u64::try_from(*n).ok(),
+                AMQPValue::LongInt(n) => u64::try_from(*n).ok(),
+                AMQPValue::ShortInt(n) => u64::try_from(*n).ok(),
+                _ => None,
+            })
+    };
+    match count("x-delivery-count").max(count(RETRY_COUNT_HEADER)) {
+        Some(count) => count.saturating_add(1),
+        None if delivery.redelivered => 2,
+        None => 1,
+    }
+}
+
 /// Handles consumer errors (e.g., connection issues).
 async fn handle_consume_error(e: lapin::Error) {
     error!("Error receiving message: {}", e);
     tokio::time::sleep(std::time::Duration::from_millis(100)).await;
 }
 
-/// Processes a message with a timeout.
+/// How long one delivery may take before it is abandoned (`PROCESSING_TIMEOUT_SECS`).
+fn processing_timeout() -> std::time::Duration {
+    std::time::Duration::from_secs(get_config().map(|c| c.processing_timeout_secs).unwrap_or(30))
+}
+
+/// Processes a message with a timeout. A delivery left unsettled by an error is requeued when
+/// the error is retryable and dead-lettered otherwise.
 async fn process_message_with_timeout(
-    delivery: Delivery,
-) -> Result<(), Box<dyn std::error::Error>> {
-    const PROCESSING_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
-    match tokio::time::timeout(PROCESSING_TIMEOUT, process_message(delivery)).await {
+    tenant: &TenantPool,
+    delivery: &TrackedDelivery<'_>,
+) -> Result<(), Error> {
+    let processing_timeout = processing_timeout();
+    let result = match tokio::time::timeout(processing_timeout, process_message(tenant, delivery))
+        .await
+    {
         Ok(result) => result,
         Err(_) => {
             error!(
                 "⏰ Message processing timed out after {:?}",
-                PROCESSING_TIMEOUT
+                processing_timeout
             );
-            Err("Processing timeout".into())
+            Err(Error::Timeout(processing_timeout))
         }
+    };
+    if let Err(e) = &result {
+        settle_failed(tenant, delivery, e).await?;
     }
+    result
 }
 
-/// Processes a message: deserializes, schedules, or delivers.
-async fn process_message(delivery: Delivery) -> Result<(), Box<dyn std::error::Error>> {
-    let payload = delivery.data.clone();
-    info!("📦 Received message of {} bytes", payload.len());
-    let json_value: Value = match serde_json::from_slice(&payload) {
-        Ok(val) => val,
-        Err(e) => {
-            error!("❌ Error deserializing message: {}", e);
-            delivery
-                .nack(BasicNackOptions {
-                    requeue: false,
-                    ..Default::default()
-                })
-                .await?;
-            warn!("🗑️ Malformed message sent to dead letter queue");
-            return Ok(());
+/// What happens to a delivery after processing it failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Settlement {
+    /// Acked or nacked before the failure, e.g. when a status report failed after the ack.
+    AlreadySettled,
+    Requeue,
+    DeadLetter,
+}
+
+/// Requeues after a transient failure and dead-letters after a permanent one, unless the
+/// delivery was already settled. Delivery `attempt` (1-based) failing after `max_retries`
+/// retries is dead-lettered too, so a message that always times out cannot loop forever.
+pub fn settlement_after(
+    error: &Error,
+    settled: bool,
+    attempt: u64,
+    max_retries: u32,
+) -> Settlement {
+    if settled {
+        Settlement::AlreadySettled
+    } else if error.is_retryable() && attempt <= u64::from(max_retries) {
+        Settlement::Requeue
+    } else {
+        Settlement::DeadLetter
+    }
+}
+
+async fn settle_failed(
+    tenant: &TenantPool,
+    delivery: &TrackedDelivery<'_>,
+    error: &Error,
+) -> Result<(), Error> {
+    // The retry policy can change on reload
+    let (max_retries, retry_delay_secs) = get_config()
+        .map(|c| (c.worker_max_retries, c.worker_retry_delay_secs))
+        .unwrap_or((3, 5));
+    let attempt = delivery_attempt(delivery);
+    match settlement_after(error, delivery.is_settled(), attempt, max_retries) {
+        Settlement::AlreadySettled => {
+            warn!("Delivery already settled, not settling it again after: {}", error);
+            Ok(())
         }
+        Settlement::Requeue => {
+            // Later attempts wait longer, so a failing dependency gets time to recover
+            let delay_ms = retry_delay_secs.saturating_mul(1000).saturating_mul(attempt);
+            retry_later(tenant, delivery, attempt, delay_ms).await?;
+            let outcome = match error {
+                Error::Timeout(_) => "timeout",
+                _ => "requeue",
+            };
+            metrics::record_delivery(outcome);
+            warn!(
+                "🔄 Message requeued after retryable error (attempt {}/{}): {}",
+                attempt,
+                u64::from(max_retries) + 1,
+                error
+            );
+            Ok(())
+        }
+        Settlement::DeadLetter => {
+            if error.is_retryable() {
+                error!("❌ Giving up on the message after {} attempts: {}", attempt, error);
+            }
+            dead_letter(tenant, delivery, error.code()).await
+        }
+    }
+}
+
+/// Sends a failed delivery back to its queue after `delay_ms`, counting the retry in
+/// `x-retry-count`, and acks the original once the broker confirmed the copy. If the copy
+/// cannot be published, the original is requeued at once instead.
+async fn retry_later(
+    tenant: &TenantPool,
+    delivery: &TrackedDelivery<'_>,
+    attempt: u64,
+    delay_ms: u64,
+) -> Result<(), Error> {
+    let mut headers = delivery.properties.headers().clone().unwrap_or_default();
+    headers.insert(
+        RETRY_COUNT_HEADER.into(),
+        AMQPValue::LongLongInt(attempt as i64),
+    );
+    headers.insert(
+        "x-delay".into(),
+        AMQPValue::LongInt(delay_ms.min(i32::MAX as u64) as i32),
+    );
+    let mut properties = BasicProperties::default().with_headers(headers);
+    if let Some(priority) = delivery.properties.priority() {
+        properties = properties.with_priority(*priority);
+    }
+
+    let confirmed = async {
+        let channel = tenant.get_publish_channel().await?;
+        let confirm = channel
+            .basic_publish(
+                &tenant.topology().delayed_exchange,
+                delivery.routing_key.as_str(),
+                BasicPublishOptions::default(),
+                &delivery.data,
+                properties,
+            )
+            .await
+            .map_err(|e| Error::Publish(e.to_string()))?
+            .await
+            .map_err(|e| Error::Publish(format!("Publish was not confirmed: {}", e)))?;
+        if confirm.is_nack() {
+            return Err(Error::Publish("Broker rejected the message".to_string()));
+        }
+        Ok(())
     };
+    match confirmed.await {
+        Ok(()) => delivery.ack().await,
+        Err(e) => {
+            warn!("Failed to schedule the retry, requeueing at once: {}", e);
+            delivery.requeue().await
+        }
+    }
+}
+
+/// Processes a message: deserializes, schedules, or delivers.
+async fn process_message(
+    tenant: &TenantPool,
+    delivery: &TrackedDelivery<'_>,
+) -> Result<(), Error> {
+    info!("📦 Received message of {} bytes", delivery.data.len());
+    let json_value: Value = serde_json::from_slice(&delivery.data)
+        .inspect_err(|e| error!("❌ Error deserializing message: {}", e))?;
+    if let Some(id) = json_value.get("id").and_then(|v| v.as_str()) {
+        let user_id = json_value.get("user_id").and_then(|v| v.as_str());
+        record_notification(&id, user_id.unwrap_or_default());
+    }
     let scheduled_at = json_value
         .get("scheduled_at")
         .and_then(|v| v.as_str())
         .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
         .map(|dt| dt.with_timezone(&Utc));
     let now = Utc::now();
-    let max_delay = ChronoDuration::days(7);
+    if let Some(expires_at) = expires_at_of_value(&json_value)
+        && expires_at <= now
+    {
+        return discard_expired(tenant, &json_value, delivery, expires_at).await;
+    }
+    // Longest single hop on the delayed exchange; later dates are requeued hop by hop
+    let max_delay = ChronoDuration::seconds(
+        get_config().map(|c| c.max_delay_hop_secs).unwrap_or(7 * 24 * 60 * 60) as i64,
+    );
     if let Some(scheduled_at) = scheduled_at {
         let remaining = scheduled_at - now;
         if remaining > max_delay || remaining > chrono::Duration::zero() {
             return reschedule_notification(
+                tenant,
                 &json_value,
                 scheduled_at,
                 remaining,
                 max_delay,
-                &delivery,
+                delivery,
             )
             .await;
         }
     }
-    handle_final_delivery(json_value, delivery, |notification| {
+    handle_final_delivery(tenant, json_value, delivery, |notification| {
         let notification = notification.clone();
         Box::pin(process_notification_owned(notification))
     })
@@ -137,14 +493,14 @@ async fn process_message(delivery: Delivery) -> Result<(), Box<dyn std::error::E
 /// Converts owned notification to ref for processing.
 async fn process_notification_owned(
     notification: models::Notification,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), Error> {
     process_notification(&notification).await
 }
 
 /// Simulates sending a push notification.
 async fn process_notification(
     notification: &models::Notification,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), Error> {
     info!(
         "📲 Sending push notification to user: {}",
         notification.user_id
@@ -165,15 +521,15 @@ async fn process_notification(
 }
 
 pub async fn reschedule_notification(
+    tenant: &TenantPool,
     json_value: &Value,
     scheduled_at: DateTime<Utc>,
     remaining: ChronoDuration,
     max_delay: ChronoDuration,
-    delivery: &Delivery,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let pool = crate::connection::get_rabbitmq_pool()
-        .map_err(|e| format!("RabbitMQ pool not initialized: {}", e))?;
-    let channel = pool.get_channel().await?;
+    delivery: &TrackedDelivery<'_>,
+) -> Result<(), Error> {
+    let channel = tenant.get_publish_channel().await?;
+    let topology = tenant.topology();
     let body = serde_json::to_vec(&json_value).map_err(|e| {
         error!("Serialization error: {}", e);
         e
@@ -183,24 +539,41 @@ pub async fn reschedule_notification(
     } else {
         remaining.num_milliseconds() as i32
     };
-    let properties = BasicProperties::default().with_headers({
+    // Keep the original priority across every requeue hop
+    let priority = delivery
+        .properties
+        .priority()
+        .unwrap_or_else(|| priority_of_value(json_value).amqp_priority());
+    let mut properties = BasicProperties::default().with_priority(priority);
+    // Only the last hop may expire in the queue; earlier hops must reach the worker again
+    if remaining <= max_delay
+        && let Some(expires_at) = expires_at_of_value(json_value)
+    {
+        let expiration = (expires_at - scheduled_at).num_milliseconds().max(0);
+        properties = properties.with_expiration(expiration.to_string().into());
+    }
+    let properties = properties.with_headers({
         let mut table = FieldTable::default();
         table.insert("x-delay".into(), AMQPValue::LongInt(delay_ms));
         table
     });
-    channel
-        .basic_publish(
-            "delayed_exchange",
-            "main",
-            BasicPublishOptions::default(),
-            &body,
-            properties,
-        )
-        .await?
-        .await?;
+    let notification_type = json_value
+        .get("notification_type")
+        .and_then(|v| v.as_str())
+        .unwrap_or("scheduled");
+    publish_confirmed(
+        &channel,
+        &topology.delayed_exchange,
+        &Topology::routing_key_for_value(json_value),
+        &body,
+        properties,
+        notification_type,
+    )
+    .await?;
     if remaining > max_delay {
         info!(
-            "🔄 Requeued notification for another 7 days (scheduled_at: {})",
+            "🔄 Requeued notification for another {} s (scheduled_at: {})",
+            max_delay.num_seconds(),
             scheduled_at
         );
     } else {
@@ -210,52 +583,219 @@ pub async fn reschedule_notification(
             scheduled_at
         );
     }
-    delivery.ack(BasicAckOptions::default()).await?;
+    delivery.ack().await?;
+    metrics::record_delivery("rescheduled");
     Ok(())
 }
 
+/// Delivers a notification, e.g. by sending a push.
+pub type NotificationProcessor = fn(
+    &models::Notification,
+) -> std::pin::Pin<
+    Box<dyn std::future::Future<Output = Result<(), Error>> + Send>,
+>;
+
 pub async fn handle_final_delivery(
+    tenant: &TenantPool,
     json_value: Value,
-    delivery: Delivery,
-    process_notification: fn(
-        &models::Notification,
-    ) -> std::pin::Pin<
-        Box<dyn std::future::Future<Output = Result<(), Box<dyn std::error::Error>>> + Send>,
-    >,
-) -> Result<(), Box<dyn std::error::Error>> {
-    match serde_json::from_value::<models::Notification>(json_value) {
+    delivery: &TrackedDelivery<'_>,
+    process_notification: NotificationProcessor,
+) -> Result<(), Error> {
+    match serde_json::from_value::<models::Notification>(json_value.clone()) {
+        Ok(notification) if notification.is_expired(Utc::now()) => {
+            let expires_at = notification.expires_at.unwrap_or_else(Utc::now);
+            discard_expired(tenant, &json_value, delivery, expires_at).await?;
+        }
         Ok(notification) => {
             info!(
-                "📩 Processing notification: user_id={}, type={}, delay={}s",
-                notification.user_id, notification.notification_type, notification.delay_secs
+                "📩 Processing notification: user_id={}, type={}, priority={}, delay={}s",
+                notification.user_id,
+                notification.notification_type,
+                notification.priority.as_str(),
+                notification.delay_secs
             );
-            match process_notification(&notification).await {
+            report_status(tenant, &json_value, DeliveryState::Delivering, None).await;
+            let timer = metrics::WORKER_PROCESSING_DURATION
+                .with_label_values(&[notification.notification_type.as_str()])
+                .start_timer();
+            let result = process_notification(&notification).await;
+            timer.observe_duration();
+            // The inbox entry is confirmed before the ack, so a crash in between redelivers
+            // the notification instead of losing its entry
+            let result = match result {
                 Ok(_) => {
-                    delivery.ack(BasicAckOptions::default()).await?;
+                    let entry = InboxEntry::from_notification(&notification, Utc::now());
+                    publish_inbox_event(tenant, &InboxEvent::Delivered(entry)).await
+                }
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(_) => {
+                    delivery.ack().await?;
+                    metrics::record_delivery("ack");
+                    if let Some(created_at) = notification.created_at {
+                        let lag = (Utc::now() - created_at).num_milliseconds().max(0);
+                        metrics::DELIVERY_LAG
+                            .with_label_values(&[notification.notification_type.as_str()])
+                            .observe(lag as f64 / 1000.0);
+                    }
                     info!("✅ Message acknowledged successfully");
+                    report_status(tenant, &json_value, DeliveryState::Delivered, None).await;
                 }
-                Err(e) => {
+                Err(e) if e.is_retryable() => {
                     error!("❌ Failed to process notification: {}", e);
-                    delivery
-                        .nack(BasicNackOptions {
-                            requeue: true,
-                            ..Default::default()
-                        })
-                        .await?;
-                    warn!("🔄 Message requeued for retry");
+                    report_status(
+                        tenant,
+                        &json_value,
+                        DeliveryState::Queued,
+                        Some(format!("Requeued after error: {}", e)),
+                    )
+                    .await;
+                    settle_failed(tenant, delivery, &e).await?;
+                }
+                Err(e) => {
+                    error!("❌ Notification cannot be delivered: {}", e);
+                    settle_failed(tenant, delivery, &e).await?;
+                    report_status(tenant, &json_value, DeliveryState::Failed, Some(e.to_string()))
+                        .await;
                 }
             }
         }
         Err(e) => {
             error!("❌ Error deserializing notification: {}", e);
-            delivery
-                .nack(BasicNackOptions {
-                    requeue: false,
-                    ..Default::default()
-                })
-                .await?;
-            warn!("🗑️ Malformed message sent to dead letter queue");
+            report_status(tenant, &json_value, DeliveryState::Failed, Some(e.to_string())).await;
+            // Left to the caller, which dead-letters non-retryable errors
+            return Err(e.into());
+        }
+    }
+    Ok(())
+}
+
+fn expires_at_of_value(json_value: &Value) -> Option<DateTime<Utc>> {
+    json_value
+        .get("expires_at")
+        .and_then(|v| v.as_str())
+        .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// Drops a notification that is no longer worth delivering.
+async fn discard_expired(
+    tenant: &TenantPool,
+    json_value: &Value,
+    delivery: &TrackedDelivery<'_>,
+    expires_at: DateTime<Utc>,
+) -> Result<(), Error> {
+    warn!("⌛ Notification expired at {}, not delivering", expires_at);
+    dead_letter(tenant, delivery, "expired").await?;
+    report_status(tenant, json_value, DeliveryState::Expired, None).await;
+    Ok(())
+}
+
+/// Reports a status change to the server. Status tracking never blocks or fails a delivery.
+async fn report_status(
+    tenant: &TenantPool,
+    json_value: &Value,
+    state: DeliveryState,
+    error: Option<String>,
+) {
+    let Some(notification_id) = json_value
+        .get("id")
+        .and_then(|v| v.as_str())
+        .and_then(|s| uuid::Uuid::parse_str(s).ok())
+    else {
+        return;
+    };
+    let user_id = json_value
+        .get("user_id")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+
+    let mut event = StatusEvent::new(notification_id, user_id, state).with_tenant(tenant.name());
+    if let Some(error) = error {
+        event = event.with_error(error);
+    }
+    if let Err(e) = publish_status_event(tenant, &event).await {
+        warn!("Failed to report status {:?} for {}: {}", state, notification_id, e);
+    }
+
+    if state.is_terminal()
+        && let Some(url) = json_value.get("callback_url").and_then(|v| v.as_str())
+    {
+        let job = CallbackJob {
+            notification_id,
+            user_id: event.user_id,
+            url: url.to_string(),
+            secret_id: json_value
+                .get("callback_secret_id")
+                .and_then(|v| v.as_str())
+                .map(str::to_string),
+            outcome: state,
+            error: event.error,
+            occurred_at: event.at,
+            attempt: 0,
+        };
+        if let Err(e) = enqueue_callback(tenant, &job, 0).await {
+            error!("Failed to enqueue callback for {}: {}", notification_id, e);
         }
     }
+}
+
+/// Moves a delivery to the tenant's dead letter queue with an explicit reason and acks the
+/// original once the broker confirmed the dead letter. If it did not, the original is requeued.
+///
+/// `nack` without requeue would also dead-letter the message, but RabbitMQ only records
+/// `rejected` as the reason; the `x-failure-reason` header keeps ours.
+pub async fn dead_letter(
+    tenant: &TenantPool,
+    delivery: &TrackedDelivery<'_>,
+    reason: &str,
+) -> Result<(), Error> {
+    let channel = tenant.get_publish_channel().await?;
+    let topology = tenant.topology();
+
+    let mut headers = delivery.properties.headers().clone().unwrap_or_default();
+    headers.insert(
+        "x-failure-reason".into(),
+        AMQPValue::LongString(reason.into()),
+    );
+    headers.insert(
+        "x-original-routing-key".into(),
+        AMQPValue::LongString(delivery.routing_key.as_str().into()),
+    );
+    // Tells the DLQ tooling when the message was dead-lettered
+    let mut properties = BasicProperties::default()
+        .with_headers(headers)
+        .with_timestamp(Utc::now().timestamp() as u64);
+    if let Some(priority) = delivery.properties.priority() {
+        properties = properties.with_priority(*priority);
+    }
+
+    let confirmed = async {
+        let confirm = channel
+            .basic_publish(
+                &topology.dlx_exchange,
+                DEAD_LETTER_ROUTING_KEY,
+                BasicPublishOptions::default(),
+                &delivery.data,
+                properties,
+            )
+            .await
+            .map_err(|e| Error::Publish(e.to_string()))?
+            .await
+            .map_err(|e| Error::Publish(format!("Publish was not confirmed: {}", e)))?;
+        if confirm.is_nack() {
+            return Err(Error::Publish("Broker rejected the message".to_string()));
+        }
+        Ok(())
+    };
+    // The original goes back to the work queue until the dead letter queue has it
+    if let Err(e) = confirmed.await {
+        delivery.requeue().await?;
+        return Err(e);
+    }
+    delivery.ack().await?;
+    metrics::record_delivery("dead_letter");
+    warn!("🗑️ Message sent to dead letter queue (reason: {})", reason);
     Ok(())
 }
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use integration_rust_rabbitmq::callbacks::{CallbackJob, CallbackSecrets, sign};
+use integration_rust_rabbitmq::status::DeliveryState;
+use integration_rust_rabbitmq::validation::validate_callback_secret;
+use uuid::Uuid;
+
+fn secrets() -> CallbackSecrets {
+    CallbackSecrets::new(HashMap::from([
+        (
+            "acme".to_string(),
+            HashMap::from([("billing".to_string(), "acme-secret".to_string())]),
+        ),
+        (
+            "globex".to_string(),
+            HashMap::from([("billing".to_string(), "globex-secret".to_string())]),
+        ),
+    ]))
+}
+
+#[test]
+fn tenants_only_resolve_their_own_callback_secrets() {
+    let secrets = secrets();
+    assert_eq!(secrets.get("acme", "billing"), Some("acme-secret"));
+    assert_eq!(secrets.get("globex", "billing"), Some("globex-secret"));
+    assert_eq!(secrets.get("acme", "crm"), None);
+    assert_eq!(secrets.get("initech", "billing"), None);
+}
+
+#[test]
+fn callback_jobs_only_carry_the_secret_id() {
+    let job = CallbackJob {
+        notification_id: Uuid::new_v4(),
+        user_id: "user-1".to_string(),
+        url: "https://billing.example.com/outcomes".to_string(),
+        secret_id: Some("billing".to_string()),
+        outcome: DeliveryState::Delivered,
+        error: None,
+        occurred_at: Utc::now(),
+        attempt: 0,
+    };
+    let body = serde_json::to_value(&job).unwrap();
+    assert_eq!(body["secret_id"], "billing");
+    assert!(body.get("secret").is_none());
+    assert!(!body.to_string().contains("acme-secret"));
+}
+
+#[test]
+fn unknown_callback_secret_ids_are_rejected() {
+    // No secrets are loaded in this process
+    assert!(validate_callback_secret("", None, "acme").is_ok());
+    let error = validate_callback_secret("payload.", Some("billing"), "acme").unwrap_err();
+    assert_eq!(error.code(), "validation_failed");
+    assert!(error.to_string().contains("payload.callback_secret_id"));
+}
+
+#[test]
+fn signatures_cover_timestamp_and_body() {
+    let signature = sign("secret", 1_700_000_000, b"{}");
+    assert!(signature.starts_with("sha256="));
+    assert_eq!(signature.len(), "sha256=".len() + 64);
+    assert_eq!(signature, sign("secret", 1_700_000_000, b"{}"));
+    assert_ne!(signature, sign("secret", 1_700_000_001, b"{}"));
+    assert_ne!(signature, sign("secret", 1_700_000_000, b"[]"));
+    assert_ne!(signature, sign("other", 1_700_000_000, b"{}"));
+}
@@ -0,0 +1,81 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use integration_rust_rabbitmq::config::{get_config, init_config_with, reload_config};
+use integration_rust_rabbitmq::settings::{Settings, Source, Sources};
+
+fn write(dir: &Path, name: &str, contents: &str) -> PathBuf {
+    let path = dir.join(name);
+    std::fs::write(&path, contents).expect("file written");
+    path
+}
+
+/// The configuration is process-global, so the whole lifecycle runs in one test. It runs in an
+/// empty directory so no `.env` file takes part.
+#[test]
+fn secrets_come_from_files_and_safe_settings_reload() {
+    let dir = std::env::temp_dir().join(format!("config-reload-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::env::set_current_dir(&dir).unwrap();
+
+    let password = write(&dir, "password", "s3cret\n");
+    let file = write(
+        &dir,
+        "notify.toml",
+        "max_message_length = 100\ncallback_max_attempts = 2\n",
+    );
+    let sources = Sources {
+        file: Some(file.clone()),
+        flags: BTreeMap::from([("RABBITMQ_PASSWORD_FILE", password.display().to_string())]),
+        print_config: false,
+    };
+
+    let settings = Settings::resolve(&sources).unwrap();
+    assert_eq!(settings.get("RABBITMQ_PASSWORD"), Some("s3cret"));
+    assert_eq!(
+        settings.source("RABBITMQ_PASSWORD"),
+        Some(Source::SecretFile)
+    );
+    assert!(settings.get("RABBITMQ_URL").unwrap().contains(":s3cret@"));
+
+    let config = init_config_with(&sources).unwrap();
+    assert_eq!(config.max_message_length, 100);
+
+    // Nothing changed
+    assert!(reload_config().unwrap().is_empty());
+
+    // Safe settings are applied; a reader holding the old configuration keeps it until it
+    // drops it, and then it is freed
+    let before = Arc::downgrade(&config);
+    std::fs::write(
+        &file,
+        "max_message_length = 200\ncallback_max_attempts = 4\n",
+    )
+    .unwrap();
+    assert_eq!(
+        reload_config().unwrap(),
+        ["MAX_MESSAGE_LENGTH", "CALLBACK_MAX_ATTEMPTS"]
+    );
+    assert_eq!(get_config().unwrap().max_message_length, 200);
+    assert_eq!(get_config().unwrap().callback_max_attempts, 4);
+    assert_eq!(config.max_message_length, 100);
+    drop(config);
+    assert!(before.upgrade().is_none());
+
+    // A new broker password needs a reconnect, so the whole reload is rejected
+    std::fs::write(&password, "rotated\n").unwrap();
+    std::fs::write(&file, "max_message_length = 300\n").unwrap();
+    let error = reload_config().unwrap_err().to_string();
+    assert!(error.contains("RABBITMQ_PASSWORD"), "{}", error);
+    assert!(!error.contains("rotated"), "{}", error);
+    assert_eq!(get_config().unwrap().max_message_length, 200);
+
+    // Malformed values are rejected as at startup
+    std::fs::write(&password, "s3cret\n").unwrap();
+    std::fs::write(&file, "max_message_length = \"long\"\n").unwrap();
+    assert!(reload_config().is_err());
+    assert_eq!(get_config().unwrap().max_message_length, 200);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
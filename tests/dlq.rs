@@ -0,0 +1,388 @@
+mod common;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::process::Output;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use chrono::Utc;
+use common::run;
+use integration_rust_rabbitmq::dlq::{
+    AuditAction, AuditLog, AuditRecord, DlqFilter, DlqMessage, merge_patch, message_id,
+};
+use integration_rust_rabbitmq::openapi::ApiDoc;
+use lapin::BasicProperties;
+use lapin::types::{AMQPValue, FieldArray, FieldTable};
+use serde_json::{Value, json};
+use utoipa::OpenApi;
+
+const DLQ: &str = env!("CARGO_BIN_EXE_dlq");
+
+fn x_death(queue: &str, reason: &str, routing_key: &str) -> AMQPValue {
+    let mut death = FieldTable::default();
+    death.insert("queue".into(), AMQPValue::LongString(queue.into()));
+    death.insert("reason".into(), AMQPValue::LongString(reason.into()));
+    death.insert("count".into(), AMQPValue::LongLongInt(2));
+    death.insert(
+        "exchange".into(),
+        AMQPValue::LongString("notifications.delayed".into()),
+    );
+    death.insert(
+        "routing-keys".into(),
+        AMQPValue::FieldArray(FieldArray::from(vec![AMQPValue::LongString(
+            routing_key.into(),
+        )])),
+    );
+    death.insert("time".into(), AMQPValue::Timestamp(1_700_000_000));
+    AMQPValue::FieldTable(death)
+}
+
+#[test]
+fn broker_dead_lettered_messages_show_their_x_death() {
+    let mut headers = FieldTable::default();
+    headers.insert(
+        "x-death".into(),
+        AMQPValue::FieldArray(FieldArray::from(vec![x_death(
+            "notifications.email",
+            "expired",
+            "notify.immediate.email.high",
+        )])),
+    );
+    let body = br#"{"id":"7d1c0a4e-1b9a-4d4e-9a34-2f1f1d6a2b10","user_id":"user-1"}"#;
+    let properties = BasicProperties::default().with_headers(headers);
+    let message = DlqMessage::new("acme", "dead_letter", body, &properties);
+
+    assert_eq!(message.id, "7d1c0a4e-1b9a-4d4e-9a34-2f1f1d6a2b10");
+    assert_eq!(message.tenant, "acme");
+    assert_eq!(message.reason.as_deref(), Some("expired"));
+    assert_eq!(message.routing_key, "notify.immediate.email.high");
+    assert_eq!(message.x_death.len(), 1);
+    assert_eq!(message.x_death[0].queue, "notifications.email");
+    assert_eq!(message.x_death[0].count, 2);
+    assert_eq!(
+        message.dead_lettered_at.map(|t| t.timestamp()),
+        Some(1_700_000_000)
+    );
+    assert_eq!(
+        message.headers.as_ref().unwrap()["x-death"][0]["time"],
+        "2023-11-14T22:13:20+00:00"
+    );
+
+    let summary = message.summary();
+    assert!(summary.body.is_none() && summary.headers.is_none());
+}
+
+#[test]
+fn worker_dead_lettered_messages_show_their_failure_reason() {
+    let mut headers = FieldTable::default();
+    headers.insert(
+        "x-failure-reason".into(),
+        AMQPValue::LongString("callback_failed".into()),
+    );
+    headers.insert(
+        "x-original-routing-key".into(),
+        AMQPValue::LongString("callback.delivered".into()),
+    );
+    let body = br#"{"notification_id":"n-1","url":"https://example.com/hook","secret":"s3cr3t"}"#;
+    let properties = BasicProperties::default()
+        .with_headers(headers)
+        .with_timestamp(1_700_000_000);
+    let message = DlqMessage::new("default", "dead_letter", body, &properties);
+
+    // Callback jobs have no id of their own
+    assert_eq!(message.id, message_id(body));
+    assert!(message.id.starts_with("sha256:"), "{}", message.id);
+    assert_eq!(message.reason.as_deref(), Some("callback_failed"));
+    assert_eq!(message.routing_key, "callback.delivered");
+    assert!(message.x_death.is_empty());
+    assert_eq!(
+        message.dead_lettered_at.map(|t| t.timestamp()),
+        Some(1_700_000_000)
+    );
+    // The webhook secret is not shown
+    assert_eq!(message.body.as_ref().unwrap()["secret"], "***");
+    assert_eq!(message.size, body.len());
+}
+
+#[test]
+fn the_worker_reason_wins_over_the_broker_reason() {
+    // A message the broker dead-lettered and the worker dead-lettered again after a replay
+    let mut headers = FieldTable::default();
+    headers.insert(
+        "x-death".into(),
+        AMQPValue::FieldArray(FieldArray::from(vec![x_death(
+            "notifications.email",
+            "rejected",
+            "notify.immediate.email.high",
+        )])),
+    );
+    headers.insert(
+        "x-failure-reason".into(),
+        AMQPValue::LongString("validation_failed".into()),
+    );
+    headers.insert(
+        "x-original-routing-key".into(),
+        AMQPValue::LongString("notify.scheduled.email.low".into()),
+    );
+    let properties = BasicProperties::default().with_headers(headers);
+    let message = DlqMessage::new("acme", "dead_letter", b"{}", &properties);
+
+    assert_eq!(message.reason.as_deref(), Some("validation_failed"));
+    assert_eq!(message.routing_key, "notify.scheduled.email.low");
+    assert_eq!(message.x_death[0].reason, "rejected");
+}
+
+#[test]
+fn filters_match_reason_and_queue() {
+    let mut headers = FieldTable::default();
+    headers.insert(
+        "x-death".into(),
+        AMQPValue::FieldArray(FieldArray::from(vec![x_death(
+            "notifications.sms",
+            "rejected",
+            "notify.immediate.sms.normal",
+        )])),
+    );
+    let properties = BasicProperties::default().with_headers(headers);
+    let message = DlqMessage::new("default", "dead_letter", b"{}", &properties);
+
+    let filter = |reason: Option<&str>, queue: Option<&str>| DlqFilter {
+        tenant: None,
+        reason: reason.map(str::to_string),
+        queue: queue.map(str::to_string),
+    };
+    assert!(filter(None, None).matches(&message));
+    assert!(filter(Some("rejected"), Some("notifications.sms")).matches(&message));
+    assert!(!filter(Some("expired"), None).matches(&message));
+    assert!(!filter(Some("rejected"), Some("notifications.email")).matches(&message));
+}
+
+#[test]
+fn patches_merge_into_the_body() {
+    let mut body = json!({
+        "id": "n-1",
+        "channel": "email",
+        "priority": "high",
+        "metadata": {"campaign": "spring", "locale": "en"},
+    });
+    merge_patch(
+        &mut body,
+        &json!({"channel": "sms", "priority": null, "metadata": {"locale": "de"}}),
+    );
+    assert_eq!(
+        body,
+        json!({
+            "id": "n-1",
+            "channel": "sms",
+            "metadata": {"campaign": "spring", "locale": "de"},
+        })
+    );
+}
+
+#[test]
+fn audit_records_are_appended_and_read_newest_first() {
+    let path = std::env::temp_dir().join(format!("dlq-audit-{}.jsonl", std::process::id()));
+    std::fs::remove_file(&path).ok();
+    let log = AuditLog::new(&path);
+    assert!(log.recent("default", 10).unwrap().is_empty());
+
+    for (actor, tenant, action) in [
+        ("ops-key", "default", AuditAction::Replay),
+        ("oncall", "default", AuditAction::Purge),
+        ("acme-ops", "acme", AuditAction::Purge),
+    ] {
+        log.append(&AuditRecord {
+            at: Utc::now(),
+            actor: actor.to_string(),
+            tenant: tenant.to_string(),
+            action,
+            ids: vec!["n-1".to_string()],
+            patch: None,
+            filter: None,
+        })
+        .unwrap();
+    }
+
+    let records = log.recent("default", 10).unwrap();
+    let actors: Vec<&str> = records.iter().map(|r| r.actor.as_str()).collect();
+    assert_eq!(actors, ["oncall", "ops-key"]);
+    assert_eq!(log.recent("default", 1).unwrap().len(), 1);
+    let acme = log.recent("acme", 10).unwrap();
+    assert_eq!(acme.len(), 1);
+    assert_eq!(acme[0].actor, "acme-ops");
+    assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn the_list_route_documents_its_filters() {
+    let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+    let params: Vec<&str> = spec["paths"]["/admin/dlq"]["get"]["parameters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|p| p["name"].as_str())
+        .collect();
+    assert_eq!(params, ["tenant", "reason", "queue", "limit"]);
+}
+
+/// A request the stand-in server received.
+#[derive(Debug)]
+struct Request {
+    line: String,
+    headers: Vec<String>,
+    body: Value,
+}
+
+/// Answers one request with `status` and `body`.
+fn stand_in_server(status: u16, body: Value) -> (SocketAddr, JoinHandle<Request>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let handle = std::thread::spawn(move || {
+        let (socket, _) = listener.accept().unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        let mut reader = BufReader::new(socket);
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let mut headers = Vec::new();
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            if header.trim().is_empty() {
+                break;
+            }
+            headers.push(header.trim().to_lowercase());
+        }
+        let length = headers
+            .iter()
+            .find_map(|h| h.strip_prefix("content-length: "))
+            .map_or(0, |n| n.parse().unwrap());
+        let mut request_body = vec![0; length];
+        reader.read_exact(&mut request_body).unwrap();
+
+        let response = body.to_string();
+        write!(
+            reader.get_mut(),
+            "HTTP/1.1 {} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            status,
+            response.len(),
+            response
+        )
+        .unwrap();
+        Request {
+            line: line.trim().to_string(),
+            headers,
+            body: serde_json::from_slice(&request_body).unwrap_or(Value::Null),
+        }
+    });
+    (address, handle)
+}
+
+fn run_cli(address: SocketAddr, args: &[&str]) -> Output {
+    let url = format!("http://{}", address);
+    run(
+        DLQ,
+        &[("NOTIFY_URL", &url), ("NOTIFY_API_KEY", "ops-key")],
+        args,
+    )
+}
+
+#[test]
+fn the_cli_lists_messages_as_a_table() {
+    let (address, server) = stand_in_server(
+        200,
+        json!({
+            "tenant": "acme",
+            "total": 3,
+            "scanned": 3,
+            "messages": [{
+                "id": "n-1",
+                "tenant": "acme",
+                "reason": "expired",
+                "routing_key": "notify.immediate.email.high",
+                "dead_lettered_at": "2023-11-14T22:13:20Z",
+                "x_death": [{"queue": "notifications.email", "reason": "expired", "count": 2}],
+                "size": 42,
+            }],
+        }),
+    );
+    let output = run_cli(
+        address,
+        &[
+            "list", "--tenant", "acme", "--reason", "expired", "--limit", "5",
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    let request = server.join().unwrap();
+    assert_eq!(
+        request.line,
+        "GET /admin/dlq?tenant=acme&reason=expired&limit=5 HTTP/1.1"
+    );
+    assert!(request.headers.contains(&"x-api-key: ops-key".to_string()));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("3 messages in the dead letter queue"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout
+            .lines()
+            .any(|l| l.starts_with("n-1 ") && l.contains("expired") && l.trim_end().ends_with('2')),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn the_cli_replays_with_a_patch() {
+    let (address, server) = stand_in_server(
+        200,
+        json!({"action": "replay", "tenant": "default", "ids": ["n-1"], "count": 1, "missing": ["n-9"]}),
+    );
+    let output = run_cli(
+        address,
+        &["replay", "n-1", "n-9", "--patch", r#"{"channel":"sms"}"#],
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    let request = server.join().unwrap();
+    assert_eq!(request.line, "POST /admin/dlq/replay HTTP/1.1");
+    assert_eq!(
+        request.body,
+        json!({"tenant": null, "ids": ["n-1", "n-9"], "patch": {"channel": "sms"}})
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Replayed 1 messages"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Not found: n-9"));
+
+    // A malformed patch never reaches the server
+    let output = run_cli(address, &["replay", "n-1", "--patch", "{channel"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid patch"));
+}
+
+#[test]
+fn the_cli_reports_server_errors() {
+    let (address, server) = stand_in_server(
+        422,
+        json!({
+            "code": "validation_failed",
+            "message": "Request validation failed",
+            "field_errors": [{"field": "filter", "message": "give ids, a reason or a queue, or set all to purge every message"}],
+        }),
+    );
+    let output = run_cli(address, &["purge"]);
+    assert!(!output.status.success());
+
+    let request = server.join().unwrap();
+    assert_eq!(request.line, "POST /admin/dlq/purge HTTP/1.1");
+    assert_eq!(request.body["all"], false);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Request validation failed"), "{}", stderr);
+    assert!(stderr.contains("filter: give ids"), "{}", stderr);
+}
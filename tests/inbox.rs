@@ -0,0 +1,207 @@
+use std::path::PathBuf;
+
+use chrono::Utc;
+use integration_rust_rabbitmq::inbox::{InboxEntry, InboxEvent, InboxStore};
+use integration_rust_rabbitmq::models::Priority;
+use uuid::Uuid;
+
+fn store_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("inbox-{}-{}.jsonl", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn entry(user_id: &str, message: &str) -> InboxEntry {
+    InboxEntry {
+        notification_id: Uuid::new_v4(),
+        user_id: user_id.to_string(),
+        message: message.to_string(),
+        notification_type: "immediate".to_string(),
+        channel: "push".to_string(),
+        priority: Priority::Normal,
+        delivered_at: Utc::now(),
+        read_at: None,
+    }
+}
+
+#[test]
+fn tenants_do_not_share_inboxes_of_the_same_user() {
+    let path = store_path("tenants");
+    let mut store = InboxStore::open(&path, 10, "default").unwrap();
+    let acme = entry("user-1", "for acme");
+    let globex = entry("user-1", "for globex");
+    store.add("acme", acme.clone()).unwrap();
+    store.add("globex", globex.clone()).unwrap();
+
+    let (items, total) = store.page("acme", "user-1", 1, 20, false);
+    assert_eq!(total, 1);
+    assert_eq!(items[0].message, "for acme");
+    assert_eq!(store.unread_count("other", "user-1"), 0);
+
+    // Marking another tenant's entry finds nothing and changes nothing
+    assert!(
+        store
+            .mark_read("acme", "user-1", &globex.notification_id, Utc::now())
+            .unwrap()
+            .is_none()
+    );
+    assert_eq!(
+        store.mark_all_read("acme", "user-1", Utc::now()).unwrap(),
+        1
+    );
+    assert_eq!(store.unread_count("globex", "user-1"), 1);
+
+    let reopened = InboxStore::open(&path, 10, "default").unwrap();
+    assert_eq!(reopened.unread_count("acme", "user-1"), 0);
+    assert_eq!(reopened.unread_count("globex", "user-1"), 1);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn changes_are_appended_to_the_log() {
+    let path = store_path("append");
+    let mut store = InboxStore::open(&path, 2, "default").unwrap();
+    let first = entry("user-1", "first");
+    store.add("acme", first.clone()).unwrap();
+    store.add("acme", entry("user-1", "second")).unwrap();
+    store
+        .mark_read("acme", "user-1", &first.notification_id, Utc::now())
+        .unwrap();
+    // Already read, so nothing is written
+    store
+        .mark_read("acme", "user-1", &first.notification_id, Utc::now())
+        .unwrap();
+    store.add("acme", entry("user-1", "third")).unwrap();
+    let ops: Vec<String> = std::fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["op"].to_string())
+        .collect();
+    assert_eq!(ops, ["\"add\"", "\"add\"", "\"read\"", "\"add\""]);
+
+    // Reopening replays the log, trimming included, and rewrites it with the kept entries
+    let reopened = InboxStore::open(&path, 2, "default").unwrap();
+    let (items, _) = reopened.page("acme", "user-1", 1, 20, false);
+    let messages: Vec<&str> = items.iter().map(|e| e.message.as_str()).collect();
+    assert_eq!(messages, ["third", "second"]);
+    assert_eq!(reopened.unread_count("acme", "user-1"), 2);
+    assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn read_times_survive_a_rewrite() {
+    let path = store_path("rewrite");
+    let mut store = InboxStore::open(&path, 10, "default").unwrap();
+    let read = entry("user-1", "read");
+    store.add("acme", read.clone()).unwrap();
+    store.add("acme", entry("user-1", "unread")).unwrap();
+    let read_at = Utc::now();
+    store
+        .mark_read("acme", "user-1", &read.notification_id, read_at)
+        .unwrap();
+    drop(store);
+
+    for _ in 0..2 {
+        let store = InboxStore::open(&path, 10, "default").unwrap();
+        let (items, total) = store.page("acme", "user-1", 1, 20, true);
+        assert_eq!(total, 1);
+        assert_eq!(items[0].message, "unread");
+        let (items, _) = store.page("acme", "user-1", 2, 1, false);
+        assert_eq!(items[0].read_at, Some(read_at));
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn inboxes_stored_without_tenants_go_to_the_default_tenant() {
+    let path = store_path("legacy");
+    let legacy = serde_json::json!({ "user-1": [entry("user-1", "before tenants")] });
+    std::fs::write(&path, legacy.to_string()).unwrap();
+
+    let store = InboxStore::open(&path, 10, "default").unwrap();
+    let (items, _) = store.page("default", "user-1", 1, 20, false);
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].message, "before tenants");
+    assert_eq!(store.unread_count("acme", "user-1"), 0);
+    drop(store);
+
+    // The file was converted to the log
+    let store = InboxStore::open(&path, 10, "default").unwrap();
+    assert_eq!(store.len(), 1);
+    let line = std::fs::read_to_string(&path).unwrap();
+    assert!(
+        line.starts_with(r#"{"op":"add","tenant":"default""#),
+        "{}",
+        line
+    );
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn replicas_applying_the_same_events_agree() {
+    let (first_path, second_path) = (store_path("replica-1"), store_path("replica-2"));
+    let mut first = InboxStore::open(&first_path, 10, "default").unwrap();
+    let mut second = InboxStore::open(&second_path, 10, "default").unwrap();
+
+    let older = entry("user-1", "older");
+    let read_at = Utc::now();
+    let mut newer = entry("user-1", "newer");
+    newer.delivered_at = read_at + chrono::Duration::seconds(1);
+    let events = [
+        InboxEvent::Delivered(older.clone()),
+        InboxEvent::Read {
+            user_id: "user-1".to_string(),
+            notification_id: None,
+            read_at,
+        },
+        InboxEvent::Delivered(newer.clone()),
+    ];
+    for event in &events {
+        first.apply("acme", event.clone()).unwrap();
+    }
+    // The second replica gets the newer entry before the marker
+    for event in [&events[0], &events[2], &events[1]] {
+        second.apply("acme", event.clone()).unwrap();
+    }
+    // Its own event coming back changes nothing
+    second.apply("acme", events[1].clone()).unwrap();
+
+    for store in [&first, &second] {
+        let (items, total) = store.page("acme", "user-1", 1, 20, true);
+        assert_eq!(total, 1);
+        assert_eq!(items[0].notification_id, newer.notification_id);
+    }
+    let _ = std::fs::remove_file(&first_path);
+    let _ = std::fs::remove_file(&second_path);
+}
+
+#[test]
+fn pages_past_the_end_are_empty() {
+    let path = store_path("pages");
+    let mut store = InboxStore::open(&path, 10, "default").unwrap();
+    store.add("acme", entry("user-1", "only")).unwrap();
+
+    for page in [2, usize::MAX / 20 + 2, usize::MAX] {
+        let (items, total) = store.page("acme", "user-1", page, 20, false);
+        assert!(items.is_empty(), "page {}", page);
+        assert_eq!(total, 1);
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn inbox_events_are_tagged() {
+    let event = InboxEvent::Read {
+        user_id: "user-1".to_string(),
+        notification_id: None,
+        read_at: Utc::now(),
+    };
+    let value = serde_json::to_value(&event).unwrap();
+    assert_eq!(value["event"], "read");
+    assert!(value.get("notification_id").is_none());
+
+    let delivered = serde_json::to_value(InboxEvent::Delivered(entry("user-1", "hi"))).unwrap();
+    assert_eq!(delivered["event"], "delivered");
+    assert_eq!(delivered["message"], "hi");
+}
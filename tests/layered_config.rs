@@ -0,0 +1,216 @@
+mod common;
+
+use std::path::PathBuf;
+use std::process::Output;
+
+use common::{BINARIES, run};
+
+fn print_config(binary: &str, env: &[(&str, &str)], args: &[&str]) -> Output {
+    run(binary, env, &[&["--print-config"], args].concat())
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    assert!(!output.status.success(), "configuration should be rejected");
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+fn config_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("{}-{}.toml", name, std::process::id()));
+    std::fs::write(&path, contents).expect("config file written");
+    path
+}
+
+#[test]
+fn flags_override_env_which_overrides_the_file() {
+    let file = config_file(
+        "layered",
+        r#"
+server_port = 7000
+worker_prefetch = 4
+processing_timeout_secs = 12
+
+[rabbitmq]
+host = "broker.internal"
+"#,
+    );
+    for binary in BINARIES {
+        let output = print_config(
+            binary,
+            &[("SERVER_PORT", "7001"), ("WORKER_PREFETCH", "8")],
+            &["--config", file.to_str().unwrap(), "--server-port", "7002"],
+        );
+        let printed = stdout(&output);
+        assert!(printed.contains("server_port = 7002 # flag"), "{}", printed);
+        assert!(printed.contains("worker_prefetch = 8 # env"), "{}", printed);
+        assert!(
+            printed.contains("processing_timeout_secs = 12 # file"),
+            "{}",
+            printed
+        );
+        assert!(
+            printed.contains("rabbitmq_host = \"broker.internal\" # file"),
+            "{}",
+            printed
+        );
+        assert!(
+            printed.contains("max_delay_hop_secs = 604800 # default"),
+            "{}",
+            printed
+        );
+    }
+    std::fs::remove_file(file).ok();
+}
+
+#[test]
+fn printed_config_masks_the_password() {
+    for binary in BINARIES {
+        let output = print_config(binary, &[("RABBITMQ_PASSWORD", "hunter2-secret")], &[]);
+        let printed = stdout(&output);
+        assert!(!printed.contains("hunter2-secret"), "{}", printed);
+        assert!(
+            printed.contains("rabbitmq_password = \"***\" # env"),
+            "{}",
+            printed
+        );
+    }
+}
+
+#[test]
+fn malformed_values_fail_startup() {
+    let cases = [
+        ("SERVER_PORT", "abc"),
+        ("RABBITMQ_PORT", "70000"),
+        ("WORKER_PREFETCH", "0"),
+        ("MAX_DELAY_HOP_SECS", "9999999999"),
+        ("LOG_FORMAT", "yaml"),
+    ];
+    for binary in BINARIES {
+        for (key, value) in cases {
+            let output = print_config(binary, &[(key, value)], &[]);
+            let error = stderr(&output);
+            assert!(error.contains(key), "{}={}: {}", key, value, error);
+        }
+    }
+}
+
+#[test]
+fn unknown_file_keys_are_rejected() {
+    let file = config_file("unknown-key", "server_prot = 8080\n");
+    for binary in BINARIES {
+        let output = print_config(binary, &[], &["--config", file.to_str().unwrap()]);
+        let error = stderr(&output);
+        assert!(error.contains("server_prot"), "{}", error);
+    }
+    std::fs::remove_file(file).ok();
+}
+
+#[test]
+fn secrets_can_be_read_from_files() {
+    let password = config_file("password", "from-a-file\n");
+    for binary in BINARIES {
+        let path = password.to_str().unwrap();
+        let output = print_config(binary, &[("RABBITMQ_PASSWORD_FILE", path)], &[]);
+        let printed = stdout(&output);
+        assert!(!printed.contains("from-a-file"), "{}", printed);
+        assert!(
+            printed.contains("# rabbitmq_password = \"***\" # secret file"),
+            "{}",
+            printed
+        );
+
+        // A flag overrides the file from the environment; both in one layer are ambiguous
+        let output = print_config(
+            binary,
+            &[("RABBITMQ_PASSWORD_FILE", path)],
+            &["--rabbitmq-password", "flag"],
+        );
+        assert!(stdout(&output).contains("rabbitmq_password = \"***\" # flag"));
+        let output = print_config(
+            binary,
+            &[
+                ("RABBITMQ_PASSWORD_FILE", path),
+                ("RABBITMQ_PASSWORD", "env"),
+            ],
+            &[],
+        );
+        assert!(stderr(&output).contains("RABBITMQ_PASSWORD_FILE"));
+
+        let output = print_config(
+            binary,
+            &[("RABBITMQ_PASSWORD_FILE", "/nonexistent/password")],
+            &[],
+        );
+        assert!(stderr(&output).contains("cannot read file"));
+    }
+    std::fs::remove_file(password).ok();
+}
+
+#[test]
+fn every_secret_has_a_file_variant() {
+    let api_keys = r#"[{"id":"billing","hash":"abc","tenant":"acme","scopes":[]}]"#;
+    let jwt_secret = "a-shared-secret-of-at-least-32-bytes";
+    let files = [
+        ("api_keys", "API_KEYS_FILE", api_keys),
+        ("jwt_hmac_secret", "JWT_HMAC_SECRET_FILE", jwt_secret),
+        (
+            "rabbitmq_tls_key_password",
+            "RABBITMQ_TLS_KEY_PASSWORD_FILE",
+            "passphrase",
+        ),
+    ]
+    .map(|(key, file_key, secret)| (key, file_key, secret, config_file(key, secret)));
+    let mut env: Vec<(&str, &str)> = files
+        .iter()
+        .map(|(_, file_key, _, path)| (*file_key, path.to_str().unwrap()))
+        .collect();
+    env.extend([
+        ("RABBITMQ_TLS", "true"),
+        ("RABBITMQ_TLS_CERT_FILE", "/etc/notify/client.pem"),
+        ("RABBITMQ_TLS_KEY_FILE", "/etc/notify/client.key"),
+    ]);
+
+    for binary in BINARIES {
+        let printed = stdout(&print_config(binary, &env, &[]));
+        for (key, _, secret, _) in &files {
+            assert!(!printed.contains(secret), "{}", printed);
+            let line = format!("# {} = \"***\" # secret file", key);
+            assert!(printed.contains(&line), "{}", printed);
+        }
+    }
+    for (_, _, _, path) in files {
+        std::fs::remove_file(path).ok();
+    }
+}
+
+#[test]
+fn conflicting_credentials_are_rejected() {
+    let jwt_secret = "a-shared-secret-of-at-least-32-bytes";
+    let cases: [(&[(&str, &str)], &str); 3] = [
+        (
+            &[("API_KEYS", "[]"), ("API_KEYS_PATH", "keys.json")],
+            "conflicts with API_KEYS_PATH",
+        ),
+        (
+            &[
+                ("JWT_HMAC_SECRET", jwt_secret),
+                ("JWT_PUBLIC_KEY_PATH", "gateway.pem"),
+            ],
+            "conflicts with JWT_JWKS_PATH and JWT_PUBLIC_KEY_PATH",
+        ),
+        (&[("JWT_HMAC_SECRET", "short")], "at least 32 bytes"),
+    ];
+    for (env, expected) in cases {
+        let error = stderr(&print_config(BINARIES[0], env, &[]));
+        assert!(error.contains(expected), "{:?}: {}", env, error);
+        assert!(!error.contains(jwt_secret), "{}", error);
+    }
+}
@@ -0,0 +1,68 @@
+use actix_web::App;
+use actix_web::http::StatusCode;
+use integration_rust_rabbitmq::metrics::{
+    DELIVERY_LAG, DLQ_ACTIONS, OUTBOX_REJECTED, OUTBOX_SIZE, RABBITMQ_RECONNECTS,
+    SCHEDULER_CYCLE_DURATION, SCHEDULER_DUE_BACKLOG, WORKER_PROCESSING_DURATION, metrics,
+    record_delivery, record_publish, render,
+};
+
+/// Dashboards and alerts are built on these names, so renaming one is a breaking change.
+#[actix_web::test]
+async fn metrics_are_exported_under_their_documented_names() {
+    record_publish("immediate", true, true);
+    record_publish("scheduled", false, false);
+    record_delivery("dead_letter");
+    SCHEDULER_CYCLE_DURATION.observe(0.01);
+    SCHEDULER_DUE_BACKLOG.set(3);
+    WORKER_PROCESSING_DURATION
+        .with_label_values(&["immediate"])
+        .observe(0.2);
+    DELIVERY_LAG.with_label_values(&["immediate"]).observe(1.5);
+    OUTBOX_SIZE.set(2);
+    OUTBOX_REJECTED.inc();
+    DLQ_ACTIONS.with_label_values(&["replay"]).inc();
+    RABBITMQ_RECONNECTS.with_label_values(&["acme"]).inc();
+
+    let app = actix_web::test::init_service(App::new().service(metrics)).await;
+    let request = actix_web::test::TestRequest::get()
+        .uri("/metrics")
+        .to_request();
+    let response = actix_web::test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(content_type.starts_with("text/plain"), "{}", content_type);
+    let body = actix_web::test::read_body(response).await;
+    let body = String::from_utf8(body.to_vec()).unwrap();
+
+    let expected = [
+        "# TYPE notify_messages_published_total counter",
+        "notify_messages_published_total{type=\"immediate\"} 1",
+        "notify_messages_confirmed_total{type=\"immediate\"} 1",
+        "notify_messages_failed_total{type=\"scheduled\"} 1",
+        "notify_worker_deliveries_total{outcome=\"dead_letter\"} 1",
+        "# TYPE notify_scheduler_cycle_duration_seconds histogram",
+        "notify_scheduler_cycle_duration_seconds_count 1",
+        "# TYPE notify_scheduler_due_backlog gauge",
+        "notify_scheduler_due_backlog 3",
+        "notify_worker_processing_duration_seconds_count{type=\"immediate\"} 1",
+        "notify_delivery_lag_seconds_count{type=\"immediate\"} 1",
+        "notify_outbox_size 2",
+        "notify_outbox_rejected_total 1",
+        "notify_dlq_messages_total{action=\"replay\"} 1",
+        "notify_rabbitmq_reconnects_total{tenant=\"acme\"} 1",
+    ];
+    for line in expected {
+        assert!(
+            body.lines().any(|l| l == line),
+            "{} missing in\n{}",
+            line,
+            body
+        );
+    }
+    assert_eq!(render(), body);
+}
@@ -0,0 +1,193 @@
+use std::collections::BTreeSet;
+
+use actix_web::http::{Method, StatusCode};
+use actix_web::{App, HttpResponse, web};
+use integration_rust_rabbitmq::admin::{
+    get_dlq_audit, get_dlq_message, get_log_level, list_dlq, purge_dlq, replay_dlq, set_log_level,
+};
+use integration_rust_rabbitmq::handlers::{
+    get_notification_status, schedule_notification, send_notification, send_notification_at,
+    send_notification_delayed,
+};
+use integration_rust_rabbitmq::health::{healthz, readyz};
+use integration_rust_rabbitmq::inbox::{
+    get_inbox, get_inbox_unread_count, mark_inbox_entry_read, mark_inbox_read,
+};
+use integration_rust_rabbitmq::metrics::metrics;
+use integration_rust_rabbitmq::openapi::ApiDoc;
+use integration_rust_rabbitmq::stream::{
+    stream_notification_status, stream_notification_status_ws,
+};
+use utoipa::OpenApi;
+
+type Register = fn(&mut web::ServiceConfig);
+
+/// Each documented handler with a function registering it alone, as `main.rs` does.
+macro_rules! handlers {
+    ($($handler:ident),* $(,)?) => {
+        [$((
+            stringify!($handler),
+            (|config: &mut web::ServiceConfig| {
+                config.service($handler);
+            }) as Register,
+        )),*]
+    };
+}
+
+fn handlers() -> Vec<(&'static str, Register)> {
+    handlers![
+        send_notification_delayed,
+        send_notification,
+        schedule_notification,
+        send_notification_at,
+        stream_notification_status_ws,
+        stream_notification_status,
+        get_notification_status,
+        get_inbox,
+        get_inbox_unread_count,
+        mark_inbox_read,
+        mark_inbox_entry_read,
+        healthz,
+        readyz,
+        metrics,
+        get_log_level,
+        set_log_level,
+        list_dlq,
+        get_dlq_audit,
+        get_dlq_message,
+        replay_dlq,
+        purge_dlq,
+    ]
+    .to_vec()
+}
+
+/// Answers requests no route matched, so they can be told apart from handler errors.
+const UNROUTED: StatusCode = StatusCode::IM_A_TEAPOT;
+
+/// Services in `main.rs` that serve the documentation itself rather than the API.
+const UNDOCUMENTED_SERVICES: [&str; 2] = ["openapi_json", "docs"];
+
+/// Handler names passed to `.service(...)` in `main.rs`.
+fn registered_services() -> BTreeSet<String> {
+    let main = include_str!("../src/main.rs");
+    main.split(".service(")
+        .skip(1)
+        .map(|rest| {
+            rest.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        })
+        .filter(|name| !UNDOCUMENTED_SERVICES.contains(&name.as_str()))
+        .collect()
+}
+
+/// `(method, path, operation id)` of every operation in the spec; utoipa uses the handler's
+/// function name as operation id.
+fn documented_routes() -> Vec<(Method, String, String)> {
+    ApiDoc::openapi()
+        .paths
+        .paths
+        .iter()
+        .flat_map(|(path, item)| {
+            [
+                (Method::GET, &item.get),
+                (Method::PUT, &item.put),
+                (Method::POST, &item.post),
+                (Method::DELETE, &item.delete),
+                (Method::OPTIONS, &item.options),
+                (Method::HEAD, &item.head),
+                (Method::PATCH, &item.patch),
+                (Method::TRACE, &item.trace),
+            ]
+            .into_iter()
+            .filter_map(|(method, operation)| {
+                let id = operation.as_ref()?.operation_id.clone().unwrap_or_default();
+                Some((method, path.clone(), id))
+            })
+            .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn documented_operations() -> BTreeSet<String> {
+    documented_routes()
+        .into_iter()
+        .map(|(_, _, id)| id)
+        .collect()
+}
+
+/// A concrete URI for a spec path; every parameter gets a UUID, which all id types accept.
+fn concrete(path: &str) -> String {
+    path.split('/')
+        .map(|segment| {
+            if segment.starts_with('{') {
+                "3f2504e0-4f89-11d3-9a0c-0305e82c3301"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[test]
+fn spec_documents_exactly_the_registered_routes() {
+    let registered = registered_services();
+    let documented = documented_operations();
+    assert!(!registered.is_empty(), "no services found in main.rs");
+
+    let undocumented: Vec<_> = registered.difference(&documented).collect();
+    let unregistered: Vec<_> = documented.difference(&registered).collect();
+    assert!(
+        undocumented.is_empty(),
+        "routes registered in main.rs but missing from the OpenAPI spec: {:?}",
+        undocumented
+    );
+    assert!(
+        unregistered.is_empty(),
+        "operations in the OpenAPI spec that main.rs does not register: {:?}",
+        unregistered
+    );
+}
+
+#[test]
+fn every_registered_handler_is_checked() {
+    let checked: BTreeSet<String> = handlers()
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .collect();
+    assert_eq!(checked, registered_services());
+}
+
+/// Serves each handler alone and sends it the method and path the spec documents for it, so a
+/// spec path or method that differs from the route macro fails.
+#[actix_web::test]
+async fn each_documented_method_and_path_reaches_its_handler() {
+    let handlers = handlers();
+    for (method, path, id) in documented_routes() {
+        let (_, register) = handlers
+            .iter()
+            .find(|(name, _)| *name == id)
+            .unwrap_or_else(|| panic!("no handler named {}", id));
+        let app = actix_web::test::init_service(
+            App::new()
+                .configure(*register)
+                .default_service(web::to(|| async { HttpResponse::new(UNROUTED) })),
+        )
+        .await;
+        let request = actix_web::test::TestRequest::default()
+            .method(method.clone())
+            .uri(&concrete(&path))
+            .to_request();
+        let status = actix_web::test::call_service(&app, request).await.status();
+        assert!(
+            status != UNROUTED && status != StatusCode::METHOD_NOT_ALLOWED,
+            "{} {} is documented for {} but the handler does not serve it ({})",
+            method,
+            path,
+            id,
+            status
+        );
+    }
+}
@@ -0,0 +1,181 @@
+mod common;
+
+use chrono::{Duration, Utc};
+use common::{BROKER_UNREACHABLE, server_without_broker};
+use integration_rust_rabbitmq::error::Error;
+use integration_rust_rabbitmq::models::{Notification, Priority};
+use integration_rust_rabbitmq::outbox::{OutboxEntry, OutboxStore};
+use std::io::Write;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+fn notification(user_id: &str) -> Notification {
+    serde_json::from_value(serde_json::json!({
+        "id": Uuid::new_v4(),
+        "user_id": user_id,
+        "message": "Your order shipped",
+        "notification_type": "immediate",
+        "channel": "email",
+        "priority": "high",
+    }))
+    .unwrap()
+}
+
+fn store_file(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("outbox-{}-{}.jsonl", name, std::process::id()));
+    std::fs::remove_file(&path).ok();
+    path
+}
+
+#[test]
+fn entries_survive_a_restart_until_removed() {
+    let file = store_file("restart");
+    let first = OutboxEntry::new("default", &notification("user-1"), Utc::now()).unwrap();
+    let second = OutboxEntry::new("default", &notification("user-2"), Utc::now()).unwrap();
+
+    let mut store = OutboxStore::open(file.clone()).unwrap();
+    store.push(first.clone(), 10).unwrap();
+    store.push(second.clone(), 10).unwrap();
+
+    // Reopened as after a crash, oldest first
+    let mut store = OutboxStore::open(file.clone()).unwrap();
+    let ids: Vec<Uuid> = store.entries().iter().map(|e| e.id).collect();
+    assert_eq!(ids, [first.id, second.id]);
+    assert_eq!(store.entries()[0].payload["message"], "Your order shipped");
+
+    assert!(store.remove(&first.id).unwrap());
+    assert!(!store.remove(&first.id).unwrap());
+    let store = OutboxStore::open(file.clone()).unwrap();
+    assert_eq!(store.len(), 1);
+    assert_eq!(store.entries()[0].id, second.id);
+
+    std::fs::remove_file(&file).ok();
+}
+
+#[test]
+fn a_full_outbox_refuses_new_entries() {
+    let file = store_file("full");
+    let mut store = OutboxStore::open(file.clone()).unwrap();
+    for user in ["user-1", "user-2"] {
+        let entry = OutboxEntry::new("default", &notification(user), Utc::now()).unwrap();
+        store.push(entry, 2).unwrap();
+    }
+
+    let entry = OutboxEntry::new("default", &notification("user-3"), Utc::now()).unwrap();
+    let error = store.push(entry.clone(), 2).unwrap_err();
+    assert!(matches!(error, Error::OutboxFull(2)), "{:?}", error);
+    assert_eq!(error.code(), "outbox_full");
+    assert!(error.is_retryable());
+    assert_eq!(store.len(), 2);
+
+    // A raised limit takes effect on the next push
+    store.push(entry, 3).unwrap();
+    assert_eq!(OutboxStore::open(file.clone()).unwrap().len(), 3);
+
+    std::fs::remove_file(&file).ok();
+}
+
+#[test]
+fn entries_keep_what_the_publish_needs() {
+    let mut notification = notification("user-1");
+    let release_at = Utc::now() + Duration::seconds(30);
+    notification.ttl_secs = Some(60);
+    notification.resolve_expiry(release_at).unwrap();
+
+    let entry = OutboxEntry::new("acme", &notification, release_at).unwrap();
+    assert_eq!(entry.id, notification.id);
+    assert_eq!(entry.tenant, "acme");
+    assert_eq!(entry.priority, Priority::High.amqp_priority());
+    assert_eq!(entry.routing_key, "notify.immediate.email.high");
+    assert_eq!(entry.release_at, release_at);
+    assert!(!entry.is_expired(Utc::now()));
+    assert!(entry.is_expired(release_at + Duration::seconds(60)));
+}
+
+#[test]
+fn changes_are_appended_to_the_log() {
+    let file = store_file("append");
+    let first = OutboxEntry::new("default", &notification("user-1"), Utc::now()).unwrap();
+    let second = OutboxEntry::new("default", &notification("user-2"), Utc::now()).unwrap();
+
+    let mut store = OutboxStore::open(file.clone()).unwrap();
+    store.push(first.clone(), 10).unwrap();
+    store.push(second.clone(), 10).unwrap();
+    store.remove(&first.id).unwrap();
+    let ops: Vec<String> = std::fs::read_to_string(&file)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["op"].to_string())
+        .collect();
+    assert_eq!(ops, ["\"push\"", "\"push\"", "\"remove\""]);
+
+    // Reopening rewrites the log with the waiting entries only
+    let store = OutboxStore::open(file.clone()).unwrap();
+    assert_eq!(store.len(), 1);
+    assert_eq!(std::fs::read_to_string(&file).unwrap().lines().count(), 1);
+
+    std::fs::remove_file(&file).ok();
+}
+
+#[test]
+fn a_record_cut_short_by_a_crash_is_ignored() {
+    let file = store_file("torn");
+    let entry = OutboxEntry::new("default", &notification("user-1"), Utc::now()).unwrap();
+    let mut store = OutboxStore::open(file.clone()).unwrap();
+    store.push(entry.clone(), 10).unwrap();
+    drop(store);
+    let mut log = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&file)
+        .unwrap();
+    log.write_all(br#"{"op":"push","entry":{"id":"#).unwrap();
+
+    let store = OutboxStore::open(file.clone()).unwrap();
+    assert_eq!(store.len(), 1);
+    assert_eq!(store.entries()[0].id, entry.id);
+
+    // Anywhere else, a broken record is an error rather than lost notifications
+    std::fs::write(&file, "not json\n{}\n").unwrap();
+    assert!(OutboxStore::open(file.clone()).is_err());
+
+    std::fs::remove_file(&file).ok();
+}
+
+#[test]
+fn an_outbox_written_as_a_json_array_is_converted() {
+    let file = store_file("legacy");
+    let entry = OutboxEntry::new("default", &notification("user-1"), Utc::now()).unwrap();
+    std::fs::write(&file, serde_json::to_vec(&vec![&entry]).unwrap()).unwrap();
+
+    let mut store = OutboxStore::open(file.clone()).unwrap();
+    assert_eq!(store.entries()[0].id, entry.id);
+    assert!(store.remove(&entry.id).unwrap());
+    assert!(OutboxStore::open(file.clone()).unwrap().is_empty());
+
+    std::fs::remove_file(&file).ok();
+}
+
+#[tokio::test]
+async fn the_server_accepts_notifications_while_the_broker_is_down() {
+    let (mut server, url) = server_without_broker(&[]);
+    let logs = server.logs_after(BROKER_UNREACHABLE);
+    assert!(logs.contains(BROKER_UNREACHABLE), "{}", logs);
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/notify", url))
+        .json(&notification("user-1"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        response.status(),
+        reqwest::StatusCode::ACCEPTED,
+        "{}",
+        server.logs()
+    );
+
+    let accepted: serde_json::Value = response.json().await.unwrap();
+    let id = accepted["id"].as_str().unwrap();
+    let outbox = std::fs::read_to_string(server.path("data/outbox.jsonl")).unwrap();
+    assert!(outbox.contains(id), "{}", outbox);
+}
@@ -0,0 +1,39 @@
+use integration_rust_rabbitmq::health::{Check, Readiness};
+
+fn failed() -> Result<(), String> {
+    Err("down".to_string())
+}
+
+#[test]
+fn a_broker_outage_only_degrades_the_server() {
+    let checks = [
+        Check::degrading("broker", failed()),
+        Check::degrading("channel", failed()),
+        Check::new("outbox", Ok(())),
+    ];
+    assert_eq!(Readiness::status_of(&checks), "degraded");
+}
+
+#[test]
+fn a_failed_critical_check_makes_it_unready() {
+    let checks = [
+        Check::degrading("broker", Ok(())),
+        Check::new("outbox", failed()),
+    ];
+    assert_eq!(Readiness::status_of(&checks), "not_ready");
+
+    let checks = [
+        Check::degrading("broker", Ok(())),
+        Check::new("outbox", Ok(())),
+    ];
+    assert_eq!(Readiness::status_of(&checks), "ready");
+}
+
+#[test]
+fn checks_say_whether_they_are_critical() {
+    let check = serde_json::to_value(Check::degrading("broker", failed())).unwrap();
+    assert_eq!(
+        check,
+        serde_json::json!({"name": "broker", "ok": false, "critical": false, "detail": "down"})
+    );
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use integration_rust_rabbitmq::models::{MAX_PRIORITY, Priority};
+use integration_rust_rabbitmq::topology::{
+    DEFAULT_ROUTING_TABLE, Topology, parse_routing_table, priority_of_value,
+};
+use serde_json::json;
+
+#[test]
+fn routing_keys_end_with_the_priority() {
+    let cases = [
+        (Priority::Low, "notify.immediate.push.low"),
+        (Priority::Normal, "notify.immediate.push.normal"),
+        (Priority::High, "notify.immediate.push.high"),
+    ];
+    for (priority, expected) in cases {
+        assert_eq!(
+            Topology::routing_key_for("immediate", "push", priority),
+            expected
+        );
+    }
+}
+
+#[test]
+fn serialized_notifications_keep_their_priority() {
+    let high = json!({"notification_type": "scheduled", "channel": "sms", "priority": "high"});
+    assert_eq!(priority_of_value(&high), Priority::High);
+    assert_eq!(
+        Topology::routing_key_for_value(&high),
+        "notify.scheduled.sms.high"
+    );
+
+    // Missing or unknown priorities and channels fall back to the defaults
+    for value in [
+        json!({"notification_type": "delayed"}),
+        json!({"notification_type": "delayed", "priority": "urgent"}),
+    ] {
+        assert_eq!(priority_of_value(&value), Priority::Normal);
+        assert_eq!(
+            Topology::routing_key_for_value(&value),
+            "notify.delayed.push.normal"
+        );
+    }
+}
+
+#[test]
+fn routing_words_cannot_inject_topic_wildcards() {
+    assert_eq!(
+        Topology::routing_key_for("a.b", "#", Priority::High),
+        "notify.a_b._.high"
+    );
+    assert_eq!(
+        Topology::routing_key_for("", "*", Priority::Low),
+        "notify.unknown._.low"
+    );
+}
+
+#[test]
+fn amqp_priorities_are_ordered_within_the_queue_maximum() {
+    let priorities = [Priority::Low, Priority::Normal, Priority::High].map(|p| p.amqp_priority());
+    assert!(priorities.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(priorities.iter().all(|p| (1..=MAX_PRIORITY).contains(p)));
+}
+
+#[test]
+fn the_default_table_routes_every_priority_by_type() {
+    let routes = parse_routing_table(DEFAULT_ROUTING_TABLE).unwrap();
+    let bindings: Vec<(&str, Vec<&str>)> = routes
+        .iter()
+        .map(|r| {
+            (
+                r.queue.as_str(),
+                r.bindings.iter().map(String::as_str).collect(),
+            )
+        })
+        .collect();
+    // Bindings end in `#`, so no priority is left without a queue
+    assert_eq!(
+        bindings,
+        [
+            ("immediate_queue", vec!["notify.immediate.#"]),
+            (
+                "scheduled_queue",
+                vec!["notify.delayed.#", "notify.scheduled.#"]
+            ),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn accept_responses_report_the_routed_priority() {
+    let (server, url) = common::server_without_broker(&[]);
+    let client = reqwest::Client::new();
+    let soon = chrono::Utc::now() + chrono::Duration::hours(1);
+    let notification = json!({"user_id": "user-1", "message": "hi", "priority": "high"});
+    let requests = [
+        ("/notify", notification.clone()),
+        (
+            "/notify-delayed",
+            json!({"user_id": "user-1", "message": "hi", "priority": "high", "delay_secs": 60}),
+        ),
+        (
+            "/notify-at",
+            json!({"user_id": "user-1", "message": "hi", "priority": "high", "scheduled_at": soon}),
+        ),
+        (
+            "/schedule-notification",
+            json!({"user_id": "user-1", "scheduled_at": soon, "payload": notification}),
+        ),
+    ];
+    for (path, body) in requests {
+        let response = client
+            .post(format!("{}{}", url, path))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert!(
+            response.status().is_success(),
+            "{}\n{}",
+            path,
+            server.logs()
+        );
+        let accepted: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(accepted["priority"], "high", "{}: {}", path, accepted);
+    }
+}
@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+use integration_rust_rabbitmq::error::{Error, FieldError};
+use integration_rust_rabbitmq::worker_utils::{Settlement, settlement_after};
+
+const MAX_RETRIES: u32 = 3;
+
+#[test]
+fn transient_failures_are_requeued() {
+    for error in [
+        Error::Connection("channel closed".into()),
+        Error::Publish("not confirmed".into()),
+        Error::Store("disk full".into()),
+        Error::Timeout(Duration::from_secs(30)),
+    ] {
+        assert_eq!(
+            settlement_after(&error, false, 1, MAX_RETRIES),
+            Settlement::Requeue,
+            "{}",
+            error
+        );
+    }
+}
+
+#[test]
+fn transient_failures_are_dead_lettered_after_the_last_retry() {
+    let error = Error::Timeout(Duration::from_secs(30));
+    // Every failed attempt up to `MAX_RETRIES` gets another one
+    for attempt in 1..=u64::from(MAX_RETRIES) {
+        assert_eq!(
+            settlement_after(&error, false, attempt, MAX_RETRIES),
+            Settlement::Requeue,
+            "attempt {}",
+            attempt
+        );
+    }
+    for attempt in [u64::from(MAX_RETRIES) + 1, u64::MAX] {
+        assert_eq!(
+            settlement_after(&error, false, attempt, MAX_RETRIES),
+            Settlement::DeadLetter,
+            "attempt {}",
+            attempt
+        );
+    }
+    assert_eq!(
+        settlement_after(&error, false, 1, 0),
+        Settlement::DeadLetter
+    );
+    assert_eq!(
+        settlement_after(&error, true, u64::MAX, MAX_RETRIES),
+        Settlement::AlreadySettled
+    );
+}
+
+#[test]
+fn permanent_failures_are_dead_lettered() {
+    let malformed = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+    for error in [
+        Error::Serialization(malformed),
+        Error::Validation(vec![FieldError::new("user_id", "must not be empty")]),
+        Error::BadRequest {
+            code: "bad_request",
+            message: "unsupported channel".into(),
+        },
+    ] {
+        assert_eq!(
+            settlement_after(&error, false, 1, MAX_RETRIES),
+            Settlement::DeadLetter,
+            "{}",
+            error
+        );
+    }
+}
+
+#[test]
+fn settled_deliveries_are_never_settled_again() {
+    // e.g. the ack went through and the status report after it timed out
+    for error in [
+        Error::Timeout(Duration::from_secs(30)),
+        Error::Connection("channel closed".into()),
+        Error::NotFound("gone".into()),
+    ] {
+        assert_eq!(
+            settlement_after(&error, true, 1, MAX_RETRIES),
+            Settlement::AlreadySettled,
+            "{}",
+            error
+        );
+    }
+}
+
+#[test]
+fn dead_letter_reasons_are_the_error_codes() {
+    // Recorded as `x-failure-reason` and used by the DLQ tooling's reason filter
+    let malformed = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+    let cases = [
+        (Error::Serialization(malformed), "serialization_error"),
+        (
+            Error::Validation(vec![FieldError::new("user_id", "must not be empty")]),
+            "validation_failed",
+        ),
+        (
+            Error::BadRequest {
+                code: "unsupported_channel",
+                message: "unsupported channel".into(),
+            },
+            "unsupported_channel",
+        ),
+    ];
+    for (error, reason) in cases {
+        assert_eq!(
+            settlement_after(&error, false, 1, MAX_RETRIES),
+            Settlement::DeadLetter
+        );
+        assert_eq!(error.code(), reason);
+    }
+}
@@ -0,0 +1,74 @@
+use chrono::{Duration, Utc};
+use integration_rust_rabbitmq::status::{DeliveryState, StatusEvent, StatusStore};
+use uuid::Uuid;
+
+fn event_at(state: DeliveryState, minutes_ago: i64) -> StatusEvent {
+    let mut event = StatusEvent::new(Uuid::new_v4(), "user-1", state);
+    event.at = Utc::now() - Duration::minutes(minutes_ago);
+    event
+}
+
+#[test]
+fn terminal_states_are_final() {
+    let mut store = StatusStore::default();
+    let delivered = event_at(DeliveryState::Delivered, 0);
+    assert!(store.apply(&delivered));
+
+    let mut late = StatusEvent::new(delivered.notification_id, "user-1", DeliveryState::Queued);
+    late = late.with_error("retry");
+    assert!(!store.apply(&late));
+    assert_eq!(
+        store.get(&delivered.notification_id).unwrap().state,
+        DeliveryState::Delivered
+    );
+}
+
+#[test]
+fn statuses_past_their_retention_are_dropped() {
+    let mut store = StatusStore::default();
+    let old = event_at(DeliveryState::Delivered, 120);
+    let recent = event_at(DeliveryState::Queued, 5);
+    store.apply(&old);
+    store.apply(&recent);
+
+    store.evict(Utc::now(), 100, Duration::hours(1));
+    assert!(store.get(&old.notification_id).is_none());
+    assert!(store.get(&recent.notification_id).is_some());
+}
+
+#[test]
+fn a_full_store_drops_the_least_recently_updated_statuses() {
+    let mut store = StatusStore::default();
+    let events: Vec<StatusEvent> = (0..21)
+        .rev()
+        .map(|minutes_ago| event_at(DeliveryState::Accepted, minutes_ago))
+        .collect();
+    for event in &events {
+        store.apply(event);
+    }
+
+    store.evict(Utc::now(), 20, Duration::days(1));
+    // Down to nine tenths of the limit, oldest first
+    assert_eq!(store.len(), 18);
+    for event in &events[..3] {
+        assert!(store.get(&event.notification_id).is_none());
+    }
+    for event in &events[3..] {
+        assert!(store.get(&event.notification_id).is_some());
+    }
+}
+
+#[test]
+fn retention_sweeps_wait_for_the_interval_while_the_store_has_room() {
+    let mut store = StatusStore::default();
+    let now = Utc::now();
+    store.evict(now, 100, Duration::hours(1));
+
+    let old = event_at(DeliveryState::Delivered, 120);
+    store.apply(&old);
+    store.evict(now + Duration::seconds(10), 100, Duration::hours(1));
+    assert!(store.get(&old.notification_id).is_some());
+
+    store.evict(now + Duration::seconds(61), 100, Duration::hours(1));
+    assert!(store.get(&old.notification_id).is_none());
+}
@@ -0,0 +1,121 @@
+use actix_web::{App, http::StatusCode, middleware::from_fn, test};
+use integration_rust_rabbitmq::config::{Config, init_config};
+use integration_rust_rabbitmq::handlers::send_notification;
+use integration_rust_rabbitmq::outbox::{get_outbox_store, init_outbox_store};
+use integration_rust_rabbitmq::telemetry::{
+    extract_context, inject_context, otel_layer, trace_request,
+};
+use lapin::types::{AMQPValue, FieldTable};
+use serde_json::json;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const CLIENT_SPAN_ID: &str = "00f067aa0ba902b7";
+
+fn traceparent(headers: &FieldTable) -> String {
+    match headers.inner().get("traceparent") {
+        Some(AMQPValue::LongString(value)) => value.to_string(),
+        other => panic!("no traceparent header: {:?}", other),
+    }
+}
+
+fn spans(path: &std::path::Path) -> Vec<serde_json::Value> {
+    std::fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[actix_web::test]
+async fn trace_continues_from_http_request_through_the_outbox_to_amqp_headers() {
+    let path = std::env::temp_dir().join(format!("traces-{}.jsonl", std::process::id()));
+    let outbox = std::env::temp_dir().join(format!("trace-outbox-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(&outbox);
+    // SAFETY: the only test of this binary, run before anything else reads the environment
+    unsafe {
+        std::env::set_var("OUTBOX_PATH", &outbox);
+        std::env::set_var("AUTH_DISABLED", "true");
+    }
+    init_config().unwrap();
+    init_outbox_store().unwrap();
+    let config = Config {
+        otel_traces_file: Some(path.to_string_lossy().into_owned()),
+        ..Config::default()
+    };
+    let (layer, provider) = otel_layer(&config, "test").unwrap().unwrap();
+    tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer)).unwrap();
+
+    // The server continues the caller's trace and keeps it with the outbox entry
+    let app = test::init_service(
+        App::new()
+            .wrap(from_fn(trace_request))
+            .service(send_notification),
+    )
+    .await;
+    let request = test::TestRequest::post()
+        .uri("/notify")
+        .insert_header((
+            "traceparent",
+            format!("00-{}-{}-01", TRACE_ID, CLIENT_SPAN_ID),
+        ))
+        .set_json(json!({"user_id": "user-1", "message": "Your order shipped"}))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let entry = get_outbox_store().unwrap().lock().unwrap().entries()[0].clone();
+    let accepted = entry.trace_context["traceparent"].clone();
+    assert!(
+        accepted.starts_with(&format!("00-{}-", TRACE_ID)),
+        "{}",
+        accepted
+    );
+
+    // The relay publishes the entry within that trace, whenever the broker takes it
+    let mut headers = FieldTable::default();
+    entry.relay_span().in_scope(|| inject_context(&mut headers));
+    let published = traceparent(&headers);
+
+    // The worker parents its span on the message headers
+    let mut headers = FieldTable::default();
+    headers.insert(
+        "traceparent".into(),
+        AMQPValue::LongString(published.clone().into()),
+    );
+    let span = tracing::info_span!("process_message");
+    let _ = span.set_parent(extract_context(Some(&headers)));
+    span.in_scope(|| {});
+    drop(span);
+    provider.shutdown().unwrap();
+
+    let spans = spans(&path);
+    let http = spans
+        .iter()
+        .find(|s| s["name"] == "POST /notify")
+        .expect("request span exported");
+    assert_eq!(http["trace_id"], TRACE_ID);
+    assert_eq!(http["parent_span_id"], CLIENT_SPAN_ID);
+    assert_eq!(http["kind"], "Server");
+    assert_eq!(accepted.split('-').nth(2), http["span_id"].as_str());
+
+    let relay = spans
+        .iter()
+        .find(|s| s["name"] == "outbox_relay")
+        .expect("relay span exported");
+    assert_eq!(relay["trace_id"], TRACE_ID);
+    assert_eq!(relay["parent_span_id"], http["span_id"]);
+
+    let worker = spans
+        .iter()
+        .find(|s| s["name"] == "process_message")
+        .expect("worker span exported");
+    assert_eq!(worker["trace_id"], TRACE_ID);
+    assert_eq!(
+        published.split('-').nth(2),
+        worker["parent_span_id"].as_str()
+    );
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(&outbox);
+}
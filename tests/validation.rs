@@ -0,0 +1,212 @@
+use actix_web::http::StatusCode;
+use actix_web::{App, web};
+use chrono::Utc;
+use integration_rust_rabbitmq::config::{Config, init_config};
+use integration_rust_rabbitmq::error::{Error, json_error_handler};
+use integration_rust_rabbitmq::handlers::send_notification;
+use integration_rust_rabbitmq::models::Notification;
+use integration_rust_rabbitmq::validation::{
+    is_public_address, validate_callback_target, validate_delayed, validate_notification,
+};
+use serde_json::json;
+
+fn notification(fields: serde_json::Value) -> Notification {
+    let mut value = json!({
+        "user_id": "user-1",
+        "message": "Your order shipped",
+        "channel": "email",
+    });
+    value
+        .as_object_mut()
+        .unwrap()
+        .extend(fields.as_object().unwrap().clone());
+    serde_json::from_value(value).unwrap()
+}
+
+/// Field names of a validation error.
+fn invalid_fields(result: Result<(), Error>) -> Vec<String> {
+    match result {
+        Err(Error::Validation(errors)) => errors.into_iter().map(|e| e.field).collect(),
+        other => panic!("expected a validation error, got {:?}", other),
+    }
+}
+
+#[test]
+fn ttl_is_bounded_by_the_schedule_horizon() {
+    let config = Config::default();
+    let max_ttl = config.max_schedule_ahead_days * 86_400;
+
+    let accepted = notification(json!({"ttl_secs": max_ttl}));
+    assert!(validate_notification(&accepted, Utc::now(), &config).is_ok());
+
+    for ttl in [0, max_ttl + 1, 9_000_000_000_000, u64::MAX] {
+        let rejected = notification(json!({ "ttl_secs": ttl }));
+        let fields = invalid_fields(validate_notification(&rejected, Utc::now(), &config));
+        assert!(
+            fields.iter().all(|f| f == "ttl_secs"),
+            "{}: {:?}",
+            ttl,
+            fields
+        );
+        assert!(!fields.is_empty());
+    }
+}
+
+#[test]
+fn out_of_range_expiries_are_errors_not_panics() {
+    let mut huge = notification(json!({"ttl_secs": u64::MAX}));
+    let error = huge.resolve_expiry(Utc::now()).unwrap_err();
+    assert_eq!(error.code(), "validation_failed");
+    assert!(huge.expires_at.is_none());
+
+    let mut regular = notification(json!({"ttl_secs": 60}));
+    let due_at = Utc::now();
+    regular.resolve_expiry(due_at).unwrap();
+    assert_eq!(
+        regular.expires_at,
+        Some(due_at + chrono::Duration::seconds(60))
+    );
+}
+
+#[test]
+fn huge_delays_are_rejected() {
+    let config = Config::default();
+    let delayed = notification(json!({"delay_secs": u64::MAX, "ttl_secs": 60}));
+    assert_eq!(
+        invalid_fields(validate_delayed(&delayed, &config)),
+        ["delay_secs"]
+    );
+}
+
+#[test]
+fn internal_addresses_are_not_public() {
+    for ip in [
+        "127.0.0.1",
+        "10.1.2.3",
+        "172.16.0.1",
+        "192.168.1.1",
+        "169.254.169.254",
+        "100.64.0.1",
+        "0.0.0.0",
+        "::1",
+        "fe80::1",
+        "fd00:ec2::254",
+        "::ffff:10.0.0.1",
+    ] {
+        assert!(
+            !is_public_address(ip.parse().unwrap()),
+            "{} counted as public",
+            ip
+        );
+    }
+    for ip in ["93.184.216.34", "1.1.1.1", "2606:4700:4700::1111"] {
+        assert!(
+            is_public_address(ip.parse().unwrap()),
+            "{} counted as internal",
+            ip
+        );
+    }
+}
+
+#[tokio::test]
+async fn callbacks_to_internal_addresses_are_rejected() {
+    let config = Config::default();
+    for url in [
+        "http://127.0.0.1:8081/hook",
+        "http://169.254.169.254/latest/meta-data",
+        "http://[::1]/hook",
+        "https://10.0.0.5/hook",
+        "http://localhost/hook",
+    ] {
+        let fields = invalid_fields(validate_callback_target("", Some(url), &config).await);
+        assert_eq!(fields, vec!["callback_url"], "{} was accepted", url);
+    }
+    assert!(validate_callback_target("", None, &config).await.is_ok());
+    assert!(
+        validate_callback_target("", Some("http://93.184.216.34/hook"), &config)
+            .await
+            .is_ok()
+    );
+
+    let config = Config {
+        callback_allow_private_targets: true,
+        ..Config::default()
+    };
+    assert!(
+        validate_callback_target("", Some("http://127.0.0.1/hook"), &config)
+            .await
+            .is_ok()
+    );
+}
+
+/// Sends a body to `POST /notify` and returns the status and JSON error body.
+async fn post_notify(content_type: &str, body: &str) -> (StatusCode, serde_json::Value) {
+    init_config().unwrap();
+    let app = actix_web::test::init_service(
+        App::new()
+            .app_data(
+                web::JsonConfig::default()
+                    .limit(256)
+                    .error_handler(json_error_handler),
+            )
+            .service(send_notification),
+    )
+    .await;
+    let request = actix_web::test::TestRequest::post()
+        .uri("/notify")
+        .insert_header(("Content-Type", content_type))
+        .set_payload(body.to_string())
+        .to_request();
+    let response = actix_web::test::call_service(&app, request).await;
+    let status = response.status();
+    (status, actix_web::test::read_body_json(response).await)
+}
+
+#[actix_web::test]
+async fn invalid_fields_are_listed_in_the_error_body() {
+    let body = json!({"user_id": "", "message": "Hi", "channel": "pigeon"}).to_string();
+    let (status, error) = post_notify("application/json", &body).await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(error["code"], "validation_failed");
+    assert_eq!(error["message"], "Request validation failed");
+    let fields: Vec<&str> = error["field_errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, ["user_id", "channel"]);
+    assert!(error["field_errors"][0]["message"].is_string());
+}
+
+#[actix_web::test]
+async fn unreadable_bodies_get_the_same_error_body() {
+    let cases = [
+        (
+            "application/json",
+            "{\"user_id\":",
+            StatusCode::BAD_REQUEST,
+            "invalid_json",
+        ),
+        (
+            "application/json",
+            &format!("{{\"message\":\"{}\"}}", "x".repeat(300)),
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "payload_too_large",
+        ),
+        (
+            "text/plain",
+            "{}",
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "unsupported_media_type",
+        ),
+    ];
+    for (content_type, body, expected_status, expected_code) in cases {
+        let (status, error) = post_notify(content_type, body).await;
+        assert_eq!(status, expected_status, "{}", error);
+        assert_eq!(error["code"], expected_code);
+        assert!(error["message"].is_string());
+        assert_eq!(error["field_errors"], json!([]));
+    }
+}